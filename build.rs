@@ -10,14 +10,31 @@ fn main() {
     let arch = parts[0];
     let sys = parts[2];
 
-    if sys != "windows" {
+    println!("cargo:rustc-check-cfg=cfg(rust_engine)");
+
+    if sys == "linux" && arch != "x86_64" {
+        panic!("Architecture '{arch}' not supported.");
+    }
+
+    if sys != "linux" && sys != "windows" {
         panic!("Platform '{sys}' not supported.");
     }
 
-    let hde = match arch {
-        "i686" => "hde/hde32.c",
-        "x86_64" => "hde/hde64.c",
-        _ => panic!("Architecture '{arch}' not supported."),
+    if !matches!(arch, "i686" | "x86_64") {
+        panic!("Architecture '{arch}' not supported.");
+    }
+
+    // On Linux, or with the `pure-rust` feature, the hooking engine is implemented in Rust, so there is nothing to
+    // compile.
+    if sys == "linux" || env::var_os("CARGO_FEATURE_PURE_RUST").is_some() {
+        println!("cargo:rustc-cfg=rust_engine");
+        return;
+    }
+
+    let hde = if arch == "i686" {
+        "hde/hde32.c"
+    } else {
+        "hde/hde64.c"
     };
 
     let mh_src_dir = Path::new(&root_dir).join("minhook/src");
@@ -0,0 +1,218 @@
+//! Procedural macros for the [`minhook`](https://docs.rs/minhook) crate. Use them through the `minhook` crate, which
+//! re-exports them with the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{ToTokens, quote};
+use syn::{
+    Error, Expr, FnArg, ItemFn, LitStr, Path, Result, meta::ParseNestedMeta, parse_macro_input,
+    parse_quote, spanned::Spanned,
+};
+
+/// Turns a function into a detour and generates a module of the same name to install it.
+///
+/// The target is either an exported function of a loaded module, given by `module` and optionally `symbol` (which
+/// defaults to the name of the function), or any expression evaluating to a function, given by `target`.
+///
+/// The generated module contains:
+///
+/// - `Type`, the function pointer type of the detour,
+/// - `unsafe fn install() -> Result<(), HookError>`, which creates and enables the hook,
+/// - `unsafe fn uninstall() -> Result<(), HookError>`, which disables and removes the hook,
+/// - `fn is_installed() -> bool`,
+/// - `fn original() -> Type`, which returns the original function and panics if the hook is not installed.
+///
+/// The module imports everything from its parent, so the types in the signature and the `target` expression resolve
+/// as they do next to the function.
+///
+/// The generated code refers to the `minhook` crate as `::minhook`. If the crate is renamed or re-exported, its path
+/// can be given by `crate`, as in `#[hook(crate = my_crate::minhook, target = ...)]`. It resolves like the `target`
+/// expression.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// #[minhook::hook(module = "kernel32.dll", symbol = "GetCurrentProcessId")]
+/// extern "system" fn get_current_process_id() -> u32 {
+///     get_current_process_id::original()() + 1
+/// }
+///
+/// unsafe { get_current_process_id::install()? };
+/// ```
+#[proc_macro_attribute]
+pub fn hook(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut target = HookTarget::default();
+    let mut krate = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("crate") {
+            set_once(&mut krate, meta.value()?.parse()?, &meta)
+        } else {
+            target.parse(meta)
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let function = parse_macro_input!(item as ItemFn);
+    let krate = krate.unwrap_or_else(|| parse_quote!(::minhook));
+
+    expand(target, &krate, function)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct HookTarget {
+    module: Option<LitStr>,
+    symbol: Option<LitStr>,
+    target: Option<Expr>,
+}
+
+impl HookTarget {
+    fn parse(&mut self, meta: ParseNestedMeta) -> Result<()> {
+        if meta.path.is_ident("module") {
+            set_once(&mut self.module, meta.value()?.parse()?, &meta)
+        } else if meta.path.is_ident("symbol") {
+            set_once(&mut self.symbol, meta.value()?.parse()?, &meta)
+        } else if meta.path.is_ident("target") {
+            set_once(&mut self.target, meta.value()?.parse()?, &meta)
+        } else {
+            Err(meta.error("expected `module`, `symbol`, `target` or `crate`"))
+        }
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, meta: &ParseNestedMeta) -> Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate argument"));
+    }
+
+    *slot = Some(value);
+    Ok(())
+}
+
+fn expand(target: HookTarget, krate: &Path, function: ItemFn) -> Result<TokenStream2> {
+    let sig = &function.sig;
+    let name = &sig.ident;
+
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(asyncness.span(), "a detour cannot be `async`"));
+    }
+    if let Some(constness) = &sig.constness {
+        return Err(Error::new(constness.span(), "a detour cannot be `const`"));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "a detour cannot be generic",
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(Error::new(variadic.span(), "a detour cannot be variadic"));
+    }
+
+    let mut inputs = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Typed(pat) => inputs.push(&pat.ty),
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "a detour cannot take `self`"));
+            }
+        }
+    }
+
+    let unsafety = &sig.unsafety;
+    let abi = &sig.abi;
+    let output = &sig.output;
+    let ty = quote!(#unsafety #abi fn(#(#inputs),*) #output);
+
+    let install = match target {
+        HookTarget {
+            module: Some(module),
+            symbol,
+            target: None,
+        } => {
+            let symbol = symbol.unwrap_or_else(|| LitStr::new(&name.to_string(), name.span()));
+            quote! {
+                unsafe { HOOK.install_api(#module, #symbol, super::#name) }
+            }
+        }
+        HookTarget {
+            module: None,
+            symbol: None,
+            target: Some(target),
+        } => quote! {
+            let target: Type = #target;
+            unsafe { HOOK.install(target, super::#name) }
+        },
+        HookTarget {
+            target: Some(target),
+            ..
+        } => {
+            return Err(Error::new(
+                target.span(),
+                "`target` cannot be combined with `module` or `symbol`",
+            ));
+        }
+        HookTarget {
+            symbol: Some(symbol),
+            ..
+        } => return Err(Error::new(symbol.span(), "`symbol` requires `module`")),
+        HookTarget { .. } => {
+            return Err(Error::new(
+                Span::call_site(),
+                "expected `module = \"...\"` or `target = ...`",
+            ));
+        }
+    };
+
+    let vis = &function.vis;
+    let doc = format!("The hook for [`{name}`](fn@{name}).");
+    let function = function.to_token_stream();
+
+    Ok(quote! {
+        #function
+
+        #[doc = #doc]
+        #vis mod #name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// The function pointer type of the target and the detour.
+            pub type Type = #ty;
+
+            static HOOK: #krate::__private::HookCell<Type> = #krate::__private::HookCell::new();
+
+            /// Creates and enables the hook.
+            ///
+            /// # Safety
+            ///
+            /// See `MinHook::create_hook` and `MinHook::enable_hook`.
+            pub unsafe fn install() -> ::core::result::Result<(), #krate::HookError> {
+                #install
+            }
+
+            /// Disables and removes the hook.
+            ///
+            /// # Safety
+            ///
+            /// See `MinHook::remove_hook`.
+            pub unsafe fn uninstall() -> ::core::result::Result<(), #krate::HookError> {
+                unsafe { HOOK.uninstall() }
+            }
+
+            /// Returns `true` if the hook is installed.
+            pub fn is_installed() -> bool {
+                HOOK.is_installed()
+            }
+
+            /// Returns the original function.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the hook is not installed.
+            pub fn original() -> Type {
+                HOOK.original()
+            }
+        }
+    })
+}
@@ -0,0 +1,272 @@
+//! Dry runs of hook creation, which report how a target function would be patched without changing it.
+
+use crate::{
+    TrampolineInfo,
+    disasm::{self, Instruction, Mode},
+    trampoline::{self, JMP_REL_SHORT_SIZE, JMP_REL_SIZE, Target, TrampolineError},
+};
+use std::{ffi::c_void, fmt};
+
+/// How [`MinHook::create_hook`](crate::MinHook::create_hook) would hook a function, as returned by
+/// [`MinHook::analyze`](crate::MinHook::analyze).
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::MinHook;
+///
+/// let analysis = MinHook::analyze(return_0 as _).unwrap();
+/// assert!(analysis.is_supported());
+/// assert!(analysis.relocated_len() >= analysis.patch_len());
+///
+/// for (offset, instruction) in analysis.instructions() {
+///     println!("{offset:#04x}: {} bytes", instruction.len());
+/// }
+///
+/// fn return_0() -> i32 {
+///     std::hint::black_box(0)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    target: usize,
+    instructions: Vec<(usize, Instruction)>,
+    hot_patch_area: bool,
+    trampoline: Option<TrampolineInfo>,
+    issues: Vec<Issue>,
+}
+
+/// A reason a function cannot be hooked safely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Issue {
+    /// The instruction at the given offset could not be decoded.
+    UndecodableInstruction { offset: usize },
+    /// A `LOOP` or `JCXZ` instruction at the given offset jumps out of the relocated code, which cannot be relocated.
+    UnsupportedBranch { offset: usize },
+    /// The instruction at the given offset is the target of a branch in the relocated code, but changes its size when
+    /// it is relocated.
+    ResizedBranchTarget { offset: usize },
+    /// The RIP-relative instruction at the given offset cannot reach its operand from the trampoline.
+    OperandOutOfRange { offset: usize },
+    /// The relocated code does not fit into a trampoline.
+    TrampolineTooLarge,
+    /// The relocated code has more instructions than a trampoline can map.
+    TooManyInstructions,
+    /// The function is shorter than the jump that is written over it, and is followed by neither padding nor preceded
+    /// by a hot-patch area.
+    TooShort,
+    /// The branch at the given offset, after the relocated code, jumps to `destination`, which is overwritten by the
+    /// patch or lies within the rest of an instruction the patch ends inside of.
+    BranchIntoPatch { offset: usize, destination: usize },
+}
+
+impl Analysis {
+    /// Analyzes the function at `target`, which has been checked to be executable.
+    pub(crate) fn new(target: &Target) -> Self {
+        let hot_patch_area = target
+            .above
+            .is_some_and(|above| trampoline::is_uniform_padding(&above));
+
+        let address = trampoline_address(target.address);
+        let built = trampoline::create(target, address, 0, Mode::NATIVE);
+        let relocated_len = built.as_ref().map_or(JMP_REL_SIZE, |built| built.len);
+
+        let mut instructions = Vec::new();
+        for result in disasm::instructions(&target.code, Mode::NATIVE) {
+            match result {
+                Ok((offset, _)) if offset >= relocated_len => break,
+                Ok(instruction) => instructions.push(instruction),
+                Err(_) => break,
+            }
+        }
+
+        let mut issues = Vec::new();
+        let trampoline = match built {
+            Ok(built) => {
+                let trampoline = TrampolineInfo::new(target, address, built);
+                issues.extend(branches_into_patch(target, &instructions, &trampoline));
+                Some(trampoline)
+            }
+            Err(error) => {
+                issues.push(error.into());
+                None
+            }
+        };
+
+        Self {
+            target: target.address,
+            instructions,
+            hot_patch_area,
+            trampoline,
+            issues,
+        }
+    }
+
+    /// Returns the target function.
+    pub fn target(&self) -> *mut c_void {
+        self.target as *mut c_void
+    }
+
+    /// Returns `true` if the function can be hooked, which is the case if there are no issues.
+    pub fn is_supported(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the reasons the function cannot be hooked safely.
+    ///
+    /// Hook creation fails with `MH_ERROR_UNSUPPORTED_FUNCTION` for all issues except
+    /// [`Issue::BranchIntoPatch`], which MinHook does not detect and which makes the hooked function crash when the
+    /// branch is taken.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Returns the instructions at the start of the function that are relocated into the trampoline, together with
+    /// their offsets. If the trampoline cannot be built, these are the instructions that would be overwritten by the
+    /// patch, up to the first one that cannot be decoded.
+    pub fn instructions(&self) -> &[(usize, Instruction)] {
+        &self.instructions
+    }
+
+    /// Returns the number of bytes at the start of the function that are relocated into the trampoline, or 0 if the
+    /// trampoline cannot be built.
+    pub fn relocated_len(&self) -> usize {
+        self.trampoline
+            .as_ref()
+            .map_or(0, |trampoline| trampoline.relocated_len)
+    }
+
+    /// Returns the number of bytes at the start of the function that are overwritten when the hook is enabled. The
+    /// bytes between the patch and [`relocated_len`](Self::relocated_len) are left in place but no longer executed.
+    pub fn patch_len(&self) -> usize {
+        if self.uses_hot_patch_area() {
+            JMP_REL_SHORT_SIZE
+        } else {
+            JMP_REL_SIZE
+        }
+    }
+
+    /// Returns `true` if the function is preceded by padding the long jump to the detour can be placed in, so that
+    /// only a short jump to it is written over the function.
+    pub fn has_hot_patch_area(&self) -> bool {
+        self.hot_patch_area
+    }
+
+    /// Returns `true` if the hot-patch area is used, because the function is too short for a long jump.
+    pub fn uses_hot_patch_area(&self) -> bool {
+        self.trampoline
+            .as_ref()
+            .is_some_and(|trampoline| trampoline.patch_above)
+    }
+
+    /// Returns the trampoline that would be built, or `None` if it cannot be built.
+    ///
+    /// The trampoline is built at the memory slot it would be placed in if the hook was created now. Another slot may
+    /// be used once other hooks have been created or removed, which changes the relative operands in its code but not
+    /// its layout. Its relay function jumps to a null detour.
+    pub fn trampoline(&self) -> Option<&TrampolineInfo> {
+        self.trampoline.as_ref()
+    }
+}
+
+/// Returns the address the trampoline for `target` would be placed at, or `target` itself if no memory slot within
+/// reach can be found.
+fn trampoline_address(target: usize) -> usize {
+    #[cfg(rust_engine)]
+    let slot = crate::engine::trampoline_placement(target);
+    // The C library places trampolines the same way, but its memory blocks cannot be inspected, so a new block is
+    // assumed.
+    #[cfg(not(rust_engine))]
+    let slot = crate::buffer::Buffer::default().placement(target);
+
+    slot.unwrap_or(target)
+}
+
+impl From<TrampolineError> for Issue {
+    fn from(error: TrampolineError) -> Self {
+        match error {
+            TrampolineError::Decode { offset } => Issue::UndecodableInstruction { offset },
+            TrampolineError::UnsupportedBranch { offset } => Issue::UnsupportedBranch { offset },
+            TrampolineError::ResizedBranchTarget { offset } => {
+                Issue::ResizedBranchTarget { offset }
+            }
+            TrampolineError::OutOfRange { offset } => Issue::OperandOutOfRange { offset },
+            TrampolineError::TooLarge => Issue::TrampolineTooLarge,
+            TrampolineError::TooManyInstructions => Issue::TooManyInstructions,
+            TrampolineError::TooShort => Issue::TooShort,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::UndecodableInstruction { offset } => {
+                write!(
+                    f,
+                    "The instruction at offset {offset:#x} cannot be decoded."
+                )
+            }
+            Issue::UnsupportedBranch { offset } => write!(
+                f,
+                "The LOOP or JCXZ instruction at offset {offset:#x} jumps out of the relocated code."
+            ),
+            Issue::ResizedBranchTarget { offset } => write!(
+                f,
+                "The instruction at offset {offset:#x} is a branch target and changes its size when relocated."
+            ),
+            Issue::OperandOutOfRange { offset } => write!(
+                f,
+                "The RIP-relative operand of the instruction at offset {offset:#x} is out of range of the trampoline."
+            ),
+            Issue::TrampolineTooLarge => write!(f, "The relocated code is too large."),
+            Issue::TooManyInstructions => {
+                write!(f, "The relocated code has too many instructions.")
+            }
+            Issue::TooShort => write!(f, "The function is too short to be patched."),
+            Issue::BranchIntoPatch {
+                offset,
+                destination,
+            } => write!(
+                f,
+                "The branch at offset {offset:#x} jumps into the patched code at offset {destination:#x}."
+            ),
+        }
+    }
+}
+
+/// Finds the branches after the relocated code that jump into the patch, or into the rest of the instruction the
+/// patch ends inside of. Such code would be overwritten by the jump to the detour.
+fn branches_into_patch(
+    target: &Target,
+    relocated: &[(usize, Instruction)],
+    trampoline: &TrampolineInfo,
+) -> Vec<Issue> {
+    let patch_len = if trampoline.patch_above {
+        JMP_REL_SHORT_SIZE
+    } else {
+        JMP_REL_SIZE
+    };
+    let patched_end = relocated
+        .iter()
+        .map(|(offset, instruction)| offset + instruction.len())
+        .find(|&end| end >= patch_len)
+        .unwrap_or(patch_len);
+
+    let start = trampoline.relocated_len;
+    disasm::instructions(&target.code[start.min(target.code.len())..], Mode::NATIVE)
+        .map_while(Result::ok)
+        .filter_map(|(offset, instruction)| {
+            let offset = start + offset;
+            let destination = trampoline::relative_branch(target.address + offset, &instruction)?
+                .wrapping_sub(target.address);
+            (1..patched_end)
+                .contains(&destination)
+                .then_some(Issue::BranchIntoPatch {
+                    offset,
+                    destination,
+                })
+        })
+        .collect()
+}
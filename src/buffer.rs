@@ -0,0 +1,162 @@
+//! Port of MinHook's `buffer.c`: hands out executable memory slots for trampolines, allocated close enough to the
+//! target function to be reachable with 32-bit relative jumps.
+
+use crate::{os, trampoline::MEMORY_SLOT_SIZE};
+
+/// Size of each memory block.
+const MEMORY_BLOCK_SIZE: usize = 0x1000;
+
+/// Max range for seeking a memory block. (= 1024MB)
+const MAX_MEMORY_RANGE: usize = 0x4000_0000;
+
+struct MemoryBlock {
+    address: usize,
+    free: Vec<usize>,
+}
+
+impl MemoryBlock {
+    fn new(address: usize) -> Self {
+        let free = (0..MEMORY_BLOCK_SIZE / MEMORY_SLOT_SIZE)
+            .rev()
+            .map(|slot| address + slot * MEMORY_SLOT_SIZE)
+            .collect();
+        Self { address, free }
+    }
+
+    fn contains(&self, slot: usize) -> bool {
+        (self.address..self.address + MEMORY_BLOCK_SIZE).contains(&slot)
+    }
+
+    fn is_unused(&self) -> bool {
+        self.free.len() == MEMORY_BLOCK_SIZE / MEMORY_SLOT_SIZE
+    }
+}
+
+/// Executable memory blocks, split into slots for trampolines.
+#[derive(Default)]
+pub(crate) struct Buffer {
+    blocks: Vec<MemoryBlock>,
+}
+
+impl Buffer {
+    /// Allocates a memory slot within reach of `origin`.
+    pub(crate) fn allocate(&mut self, origin: usize) -> Option<usize> {
+        let block = self.memory_block(origin)?;
+        block.free.pop()
+    }
+
+    /// Returns a memory slot to its block, releasing the block once none of its slots is in use.
+    pub(crate) fn free(&mut self, slot: usize) {
+        let Some(index) = self.blocks.iter().position(|block| block.contains(slot)) else {
+            return;
+        };
+
+        let block = &mut self.blocks[index];
+        block.free.push(slot);
+
+        // Free the block if it is completely unused.
+        if block.is_unused() {
+            let block = self.blocks.swap_remove(index);
+            unsafe { os::free(block.address, MEMORY_BLOCK_SIZE) };
+        }
+    }
+
+    /// Returns the memory slot [`allocate`](Self::allocate) would hand out for `origin`, without allocating it. A new
+    /// block is assumed to be placed in the first free region that is found, as it is unless mapping it fails.
+    pub(crate) fn placement(&self, origin: usize) -> Option<usize> {
+        let (min_addr, max_addr) = Self::reachable_range(origin);
+        if let Some(index) = self.reachable_block(min_addr, max_addr) {
+            return self.blocks[index].free.last().copied();
+        }
+
+        if cfg!(target_pointer_width = "64") {
+            os::find_prev_free_region(origin, min_addr, MEMORY_BLOCK_SIZE)
+                .or_else(|| os::find_next_free_region(origin, max_addr, MEMORY_BLOCK_SIZE))
+        } else {
+            None
+        }
+    }
+
+    fn memory_block(&mut self, origin: usize) -> Option<&mut MemoryBlock> {
+        let (min_addr, max_addr) = Self::reachable_range(origin);
+
+        // Look the registered blocks for a reachable one.
+        if let Some(index) = self.reachable_block(min_addr, max_addr) {
+            return Some(&mut self.blocks[index]);
+        }
+
+        let address = if cfg!(target_pointer_width = "64") {
+            Self::allocate_block_near(origin, min_addr, max_addr)
+        } else {
+            // In x86 mode, a memory block can be placed anywhere.
+            unsafe { os::allocate(None, MEMORY_BLOCK_SIZE) }
+        }?;
+
+        self.blocks.push(MemoryBlock::new(address));
+        self.blocks.last_mut()
+    }
+
+    /// Returns the range of block addresses within reach of `origin`.
+    fn reachable_range(origin: usize) -> (usize, usize) {
+        let (mut min_addr, mut max_addr) = os::application_address_range();
+
+        if cfg!(target_pointer_width = "64") {
+            // origin ± 1024MB
+            if origin > MAX_MEMORY_RANGE && min_addr < origin - MAX_MEMORY_RANGE {
+                min_addr = origin - MAX_MEMORY_RANGE;
+            }
+
+            if max_addr > origin + MAX_MEMORY_RANGE {
+                max_addr = origin + MAX_MEMORY_RANGE;
+            }
+
+            // Make room for MEMORY_BLOCK_SIZE bytes.
+            max_addr -= MEMORY_BLOCK_SIZE - 1;
+        }
+
+        (min_addr, max_addr)
+    }
+
+    /// Returns the first registered block with a free slot within the range.
+    fn reachable_block(&self, min_addr: usize, max_addr: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| {
+            (min_addr..max_addr).contains(&block.address) && !block.free.is_empty()
+        })
+    }
+
+    fn allocate_block_near(origin: usize, min_addr: usize, max_addr: usize) -> Option<usize> {
+        // Alloc a new block above if not found.
+        let mut alloc = origin;
+        while alloc >= min_addr {
+            let Some(free) = os::find_prev_free_region(alloc, min_addr, MEMORY_BLOCK_SIZE) else {
+                break;
+            };
+            if let Some(block) = unsafe { os::allocate(Some(free), MEMORY_BLOCK_SIZE) } {
+                return Some(block);
+            }
+            alloc = free;
+        }
+
+        // Alloc a new block below if not found.
+        let mut alloc = origin;
+        while alloc <= max_addr {
+            let Some(free) = os::find_next_free_region(alloc, max_addr, MEMORY_BLOCK_SIZE) else {
+                break;
+            };
+            if let Some(block) = unsafe { os::allocate(Some(free), MEMORY_BLOCK_SIZE) } {
+                return Some(block);
+            }
+            alloc = free;
+        }
+
+        None
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        for block in self.blocks.drain(..) {
+            unsafe { os::free(block.address, MEMORY_BLOCK_SIZE) };
+        }
+    }
+}
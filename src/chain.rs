@@ -0,0 +1,374 @@
+//! Several detours stacked on the same target function.
+
+use crate::{
+    ErrorKind, FnPtr, HookError, HookTarget, MH_STATUS, MinHook, Operation, thunk::JumpThunk,
+};
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tracing::error;
+
+/// A detour in a chain of detours on the same target function.
+///
+/// Every target with chained hooks has a single hook, which jumps to the first enabled detour in the chain. The
+/// original function of each detour jumps to the next enabled detour, and the last one to the trampoline of the hook.
+/// Detours with a higher priority are called first, and detours with the same priority in the order they were added.
+///
+/// Detours can be enabled, disabled and removed without affecting the others. The hook of the target is enabled while
+/// any of its detours is enabled, and removed with the last detour. A detour is removed when its handle is dropped,
+/// and errors while removing it are reported through `tracing`.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{ChainedHook, HookError};
+///
+/// fn main() -> Result<(), HookError> {
+///     unsafe {
+///         let add_one = ChainedHook::new(return_0 as fn() -> i32, add_one, 0)?;
+///         let double = ChainedHook::new(return_0 as fn() -> i32, double, 1)?;
+///         add_one.enable()?;
+///         double.enable()?;
+///
+///         // `double` has the higher priority, so it is called first.
+///         assert_eq!(return_0(), 2);
+///
+///         double.remove()?;
+///         assert_eq!(return_0(), 1);
+///         add_one.remove()?;
+///     }
+///
+///     Ok(())
+/// }
+///
+/// fn return_0() -> i32 {
+///     std::hint::black_box(0)
+/// }
+///
+/// fn add_one() -> i32 {
+///     1
+/// }
+///
+/// fn double() -> i32 {
+///     2
+/// }
+/// ```
+pub struct ChainedHook<F: FnPtr> {
+    id: u64,
+    target: F,
+    detour: F,
+    original: F,
+    priority: i32,
+}
+
+struct Chain {
+    target: usize,
+    /// The jump the hook of the target detours to.
+    entry: JumpThunk,
+    trampoline: usize,
+    /// The detours, ordered by priority.
+    layers: Vec<Layer>,
+}
+
+struct Layer {
+    id: u64,
+    detour: usize,
+    priority: i32,
+    enabled: bool,
+    /// The jump to the next enabled detour, which is the original function of this detour.
+    next: JumpThunk,
+}
+
+static CHAINS: Mutex<Vec<Chain>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Locks the chains, dropping those whose hook was removed behind their back, e.g. by [`MinHook::shutdown`].
+fn lock() -> MutexGuard<'static, Vec<Chain>> {
+    let mut chains = CHAINS.lock().unwrap_or_else(PoisonError::into_inner);
+    chains.retain(|chain| MinHook::is_created(chain.target as *mut _));
+    chains
+}
+
+impl<F: FnPtr> ChainedHook<F> {
+    /// Adds a detour to the chain of the target function, creating the hook of the target if it has no chain yet. The
+    /// detour is added disabled.
+    ///
+    /// Returns `MH_ERROR_ALREADY_CREATED` if the target is hooked without a chain.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`]. The detour is removed when the handle is dropped, which frees its original
+    /// function, so no thread may be about to call the original function at that point.
+    pub unsafe fn new(target: F, detour: F, priority: i32) -> Result<Self, HookError> {
+        let error = |status| {
+            HookError::new(
+                Operation::CreateHook,
+                Some(HookTarget::Address(target.to_ptr() as usize)),
+                ErrorKind::Status(status),
+            )
+        };
+
+        let mut chains = lock();
+        let index = match chains
+            .iter()
+            .position(|chain| chain.target == target.to_ptr() as usize)
+        {
+            Some(index) => index,
+            None => {
+                let entry = JumpThunk::new(0).ok_or(error(MH_STATUS::MH_ERROR_MEMORY_ALLOC))?;
+                let trampoline = unsafe {
+                    MinHook::create_hook(target.to_ptr(), entry.address() as *mut _)? as usize
+                };
+                entry.set_destination(trampoline);
+
+                chains.push(Chain {
+                    target: target.to_ptr() as usize,
+                    entry,
+                    trampoline,
+                    layers: Vec::new(),
+                });
+                chains.len() - 1
+            }
+        };
+        let chain = &mut chains[index];
+
+        let Some(next) = JumpThunk::new(chain.trampoline) else {
+            if chain.layers.is_empty() {
+                unsafe { remove_chain(&mut chains, index)? };
+            }
+            return Err(error(MH_STATUS::MH_ERROR_MEMORY_ALLOC));
+        };
+        let original = unsafe { F::from_ptr(next.address() as *mut _) };
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let position = chain
+            .layers
+            .iter()
+            .position(|layer| layer.priority < priority)
+            .unwrap_or(chain.layers.len());
+        chain.layers.insert(
+            position,
+            Layer {
+                id,
+                detour: detour.to_ptr() as usize,
+                priority,
+                enabled: false,
+                next,
+            },
+        );
+        chain.link();
+
+        Ok(Self {
+            id,
+            target,
+            detour,
+            original,
+            priority,
+        })
+    }
+
+    /// Returns the target function.
+    pub fn target(&self) -> F {
+        self.target
+    }
+
+    /// Returns the detour function.
+    pub fn detour(&self) -> F {
+        self.detour
+    }
+
+    /// Returns the function that calls the next enabled detour in the chain, or the original function if there is
+    /// none.
+    pub fn original(&self) -> F {
+        self.original
+    }
+
+    /// Returns the priority of the detour.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns `true` if the detour is enabled.
+    pub fn is_enabled(&self) -> bool {
+        let chains = lock();
+        self.find(&chains)
+            .is_some_and(|(index, position)| chains[index].layers[position].enabled)
+    }
+
+    /// Enables the detour.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::enable_hook`].
+    pub unsafe fn enable(&self) -> Result<(), HookError> {
+        unsafe { self.set_enabled(true) }
+    }
+
+    /// Disables the detour.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::disable_hook`].
+    pub unsafe fn disable(&self) -> Result<(), HookError> {
+        unsafe { self.set_enabled(false) }
+    }
+
+    /// Removes the detour from the chain, and the hook of the target if it was the last detour.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::remove_hook`] and [`ChainedHook::new`].
+    pub unsafe fn remove(self) -> Result<(), HookError> {
+        let this = ManuallyDrop::new(self);
+        unsafe { this.remove_layer() }
+    }
+
+    /// Releases the handle without removing the detour, which stays in the chain in its current state.
+    pub fn leak(self) {
+        let _ = ManuallyDrop::new(self);
+    }
+
+    unsafe fn remove_layer(&self) -> Result<(), HookError> {
+        let mut chains = lock();
+        // The hook of the target is already gone, together with the chain.
+        let Some((index, position)) = self.find(&chains) else {
+            return Ok(());
+        };
+        let chain = &mut chains[index];
+
+        if chain.layers.len() == 1 {
+            return unsafe { remove_chain(&mut chains, index) };
+        }
+
+        let layer = chain.layers.remove(position);
+        chain.link();
+        if let Err(error) = unsafe { chain.sync() } {
+            chain.layers.insert(position, layer);
+            chain.link();
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    unsafe fn set_enabled(&self, enable: bool) -> Result<(), HookError> {
+        let (operation, status) = if enable {
+            (Operation::EnableHook, MH_STATUS::MH_ERROR_ENABLED)
+        } else {
+            (Operation::DisableHook, MH_STATUS::MH_ERROR_DISABLED)
+        };
+        let error = |status| {
+            HookError::new(
+                operation,
+                Some(HookTarget::Address(self.target.to_ptr() as usize)),
+                ErrorKind::Status(status),
+            )
+        };
+
+        let mut chains = lock();
+        let (index, position) = self
+            .find(&chains)
+            .ok_or(error(MH_STATUS::MH_ERROR_NOT_CREATED))?;
+        let chain = &mut chains[index];
+
+        if chain.layers[position].enabled == enable {
+            return Err(error(status));
+        }
+
+        chain.layers[position].enabled = enable;
+        chain.link();
+        if let Err(error) = unsafe { chain.sync() } {
+            chain.layers[position].enabled = !enable;
+            chain.link();
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the index of the chain of the detour and its position in the chain, or `None` if the chain was dropped
+    /// because its hook was removed.
+    fn find(&self, chains: &[Chain]) -> Option<(usize, usize)> {
+        chains.iter().enumerate().find_map(|(index, chain)| {
+            let position = chain.layers.iter().position(|layer| layer.id == self.id)?;
+            Some((index, position))
+        })
+    }
+}
+
+impl<F: FnPtr> Drop for ChainedHook<F> {
+    fn drop(&mut self) {
+        if let Err(status) = unsafe { self.remove_layer() } {
+            error!(
+                "Could not remove the chained hook for {:?}: {:?}",
+                self.target.to_ptr(),
+                status
+            );
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for ChainedHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChainedHook")
+            .field("target", &self.target.to_ptr())
+            .field("detour", &self.detour.to_ptr())
+            .field("original", &self.original.to_ptr())
+            .field("priority", &self.priority)
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+impl Chain {
+    /// Points the entry and the original function of every detour at the next enabled detour.
+    fn link(&self) {
+        let mut next = self.trampoline;
+        for layer in self.layers.iter().rev() {
+            layer.next.set_destination(next);
+            if layer.enabled {
+                next = layer.detour;
+            }
+        }
+
+        self.entry.set_destination(next);
+    }
+
+    /// Enables the hook of the target while any detour is enabled, and disables it otherwise.
+    ///
+    /// The state of the hook is queried rather than remembered, since it can also be changed for all hooks at once.
+    unsafe fn sync(&self) -> Result<(), HookError> {
+        let enable = self.layers.iter().any(|layer| layer.enabled);
+        if enable == MinHook::is_enabled(self.target as *mut _) {
+            return Ok(());
+        }
+
+        if enable {
+            unsafe { MinHook::enable_hook(self.target as *mut _) }
+        } else {
+            unsafe { MinHook::disable_hook(self.target as *mut _) }
+        }
+    }
+}
+
+/// Removes the hook of a chain and frees its jumps. The chain is also dropped if its hook no longer exists.
+unsafe fn remove_chain(chains: &mut Vec<Chain>, index: usize) -> Result<(), HookError> {
+    match unsafe { MinHook::remove_hook(chains[index].target as *mut _) } {
+        Err(error)
+            if error != MH_STATUS::MH_ERROR_NOT_CREATED
+                && error != MH_STATUS::MH_ERROR_NOT_INITIALIZED =>
+        {
+            return Err(error);
+        }
+        _ => {}
+    }
+
+    chains.swap_remove(index);
+    Ok(())
+}
@@ -0,0 +1,358 @@
+//! Detours implemented by Rust closures.
+//!
+//! Every closure hook gets a small thunk that records a pointer to the closure in a thread-local slot and jumps to a
+//! handler monomorphized for the function pointer type. The handler reads the closure back and calls it with the
+//! original function and the arguments. All caller-saved registers are preserved by the thunk, so the handler receives
+//! the arguments exactly as the caller passed them.
+
+use crate::{
+    ErrorKind, FnPtr, Hook, HookError, HookTarget, MH_STATUS, Operation,
+    thunk::{BoundThunk, Thunk, ThunkHook},
+};
+use std::{arch::global_asm, cell::Cell, ffi::c_void, fmt, ops::Deref, ptr::null, sync::OnceLock};
+
+thread_local! {
+    static CONTEXT: Cell<*const c_void> = const { Cell::new(null()) };
+}
+
+/// Called by the closure entry before jumping to the handler.
+extern "C" fn set_context(context: *const c_void) {
+    CONTEXT.set(context);
+}
+
+fn take_context() -> *const c_void {
+    CONTEXT.replace(null())
+}
+
+// Saves the caller-saved registers, stores the context passed in r10 and jumps to the handler passed in r11.
+#[cfg(target_arch = "x86_64")]
+global_asm!(
+    ".text",
+    ".globl minhook_closure_entry",
+    ".p2align 4",
+    "minhook_closure_entry:",
+    "push rax",
+    "push rcx",
+    "push rdx",
+    "push rsi",
+    "push rdi",
+    "push r8",
+    "push r9",
+    "push r11",
+    // Space for xmm0-15 and the shadow space of the Windows x64 ABI, keeping the stack 16-byte aligned.
+    "sub rsp, 0x128",
+    "movups [rsp + 0x20], xmm0",
+    "movups [rsp + 0x30], xmm1",
+    "movups [rsp + 0x40], xmm2",
+    "movups [rsp + 0x50], xmm3",
+    "movups [rsp + 0x60], xmm4",
+    "movups [rsp + 0x70], xmm5",
+    "movups [rsp + 0x80], xmm6",
+    "movups [rsp + 0x90], xmm7",
+    "movups [rsp + 0xA0], xmm8",
+    "movups [rsp + 0xB0], xmm9",
+    "movups [rsp + 0xC0], xmm10",
+    "movups [rsp + 0xD0], xmm11",
+    "movups [rsp + 0xE0], xmm12",
+    "movups [rsp + 0xF0], xmm13",
+    "movups [rsp + 0x100], xmm14",
+    "movups [rsp + 0x110], xmm15",
+    // The first argument is passed in rdi on System V and in rcx on Windows.
+    "mov rdi, r10",
+    "mov rcx, r10",
+    "call {set_context}",
+    "movups xmm0, [rsp + 0x20]",
+    "movups xmm1, [rsp + 0x30]",
+    "movups xmm2, [rsp + 0x40]",
+    "movups xmm3, [rsp + 0x50]",
+    "movups xmm4, [rsp + 0x60]",
+    "movups xmm5, [rsp + 0x70]",
+    "movups xmm6, [rsp + 0x80]",
+    "movups xmm7, [rsp + 0x90]",
+    "movups xmm8, [rsp + 0xA0]",
+    "movups xmm9, [rsp + 0xB0]",
+    "movups xmm10, [rsp + 0xC0]",
+    "movups xmm11, [rsp + 0xD0]",
+    "movups xmm12, [rsp + 0xE0]",
+    "movups xmm13, [rsp + 0xF0]",
+    "movups xmm14, [rsp + 0x100]",
+    "movups xmm15, [rsp + 0x110]",
+    "add rsp, 0x128",
+    "pop r11",
+    "pop r9",
+    "pop r8",
+    "pop rdi",
+    "pop rsi",
+    "pop rdx",
+    "pop rcx",
+    "pop rax",
+    "jmp r11",
+    set_context = sym set_context,
+);
+
+// Saves the caller-saved registers and stores the context pushed by the thunk. The thunk pushes the handler below
+// the context, so `ret` jumps to the handler once the context has been popped.
+#[cfg(target_arch = "x86")]
+global_asm!(
+    ".text",
+    ".globl _minhook_closure_entry",
+    ".p2align 4",
+    "_minhook_closure_entry:",
+    "push eax",
+    "push ecx",
+    "push edx",
+    "sub esp, 0x80",
+    "movups [esp], xmm0",
+    "movups [esp + 0x10], xmm1",
+    "movups [esp + 0x20], xmm2",
+    "movups [esp + 0x30], xmm3",
+    "movups [esp + 0x40], xmm4",
+    "movups [esp + 0x50], xmm5",
+    "movups [esp + 0x60], xmm6",
+    "movups [esp + 0x70], xmm7",
+    "push dword ptr [esp + 0x8C]",
+    "call {set_context}",
+    "add esp, 4",
+    "movups xmm0, [esp]",
+    "movups xmm1, [esp + 0x10]",
+    "movups xmm2, [esp + 0x20]",
+    "movups xmm3, [esp + 0x30]",
+    "movups xmm4, [esp + 0x40]",
+    "movups xmm5, [esp + 0x50]",
+    "movups xmm6, [esp + 0x60]",
+    "movups xmm7, [esp + 0x70]",
+    "add esp, 0x80",
+    "pop edx",
+    "pop ecx",
+    "pop eax",
+    "add esp, 4",
+    "ret",
+    set_context = sym set_context,
+);
+
+unsafe extern "C" {
+    fn minhook_closure_entry();
+}
+
+/// Generates the thunk that enters `handler` with `context`.
+fn closure_thunk(context: *const c_void, handler: *mut c_void) -> Option<Thunk> {
+    let entry = minhook_closure_entry as *const () as usize;
+
+    Thunk::new(|address| {
+        if cfg!(target_arch = "x86_64") {
+            [
+                // mov r10, context
+                &[0x49, 0xBA][..],
+                &(context as u64).to_le_bytes(),
+                // mov r11, handler
+                &[0x49, 0xBB],
+                &(handler as u64).to_le_bytes(),
+                // jmp [rip+0]
+                &[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00],
+                &(entry as u64).to_le_bytes(),
+            ]
+            .concat()
+        } else {
+            [
+                // push handler
+                &[0x68][..],
+                &(handler as u32).to_le_bytes(),
+                // push context
+                &[0x68],
+                &(context as u32).to_le_bytes(),
+                // jmp entry
+                &[0xE9],
+                &(entry.wrapping_sub(address + 15) as u32).to_le_bytes(),
+            ]
+            .concat()
+        }
+    })
+}
+
+/// A function pointer type whose hooks can be detoured to a closure.
+///
+/// The closure receives the original function followed by the arguments of the call. This trait is implemented for
+/// the same function pointer types as [`FnPtr`].
+///
+/// # Safety
+///
+/// `handler` must return a function of type `Self` that calls the closure of the current [`ClosureHook`].
+pub unsafe trait ClosureDetour: FnPtr {
+    /// The type of the closure, `dyn Fn(Self, Args...) -> Ret + Send + Sync`.
+    type Closure: ?Sized + Send + Sync;
+
+    /// Returns the handler that forwards calls to the closure.
+    fn handler() -> Self;
+}
+
+/// State shared between a closure hook and its handler.
+struct ClosureContext<F: ClosureDetour> {
+    original: OnceLock<F>,
+    closure: Box<F::Closure>,
+}
+
+impl<F: ClosureDetour> ClosureContext<F> {
+    /// Returns the context of the closure hook that is being entered on this thread.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called once, at the beginning of the handler for `F`.
+    unsafe fn current<'a>() -> (F, &'a F::Closure) {
+        let context = unsafe { &*(take_context() as *const Self) };
+        let original = *context
+            .original
+            .get()
+            .expect("closure hook entered before it was created");
+        (original, &context.closure)
+    }
+}
+
+macro_rules! impl_closure_detour {
+    ($abi:literal; $($arg:ident),*) => {
+        unsafe impl<Ret: 'static, $($arg: 'static),*> ClosureDetour for extern $abi fn($($arg),*) -> Ret {
+            type Closure = dyn Fn(Self, $($arg),*) -> Ret + Send + Sync;
+
+            fn handler() -> Self {
+                #[allow(non_snake_case, clippy::too_many_arguments)]
+                extern $abi fn handler<Ret: 'static, $($arg: 'static),*>($($arg: $arg),*) -> Ret {
+                    let (original, closure) =
+                        unsafe { ClosureContext::<extern $abi fn($($arg),*) -> Ret>::current() };
+                    closure(original, $($arg),*)
+                }
+
+                handler::<Ret, $($arg),*>
+            }
+        }
+
+        unsafe impl<Ret: 'static, $($arg: 'static),*> ClosureDetour for unsafe extern $abi fn($($arg),*) -> Ret {
+            type Closure = dyn Fn(Self, $($arg),*) -> Ret + Send + Sync;
+
+            fn handler() -> Self {
+                #[allow(non_snake_case, clippy::too_many_arguments)]
+                unsafe extern $abi fn handler<Ret: 'static, $($arg: 'static),*>($($arg: $arg),*) -> Ret {
+                    let (original, closure) =
+                        unsafe { ClosureContext::<unsafe extern $abi fn($($arg),*) -> Ret>::current() };
+                    closure(original, $($arg),*)
+                }
+
+                handler::<Ret, $($arg),*>
+            }
+        }
+    };
+}
+
+macro_rules! impl_closure_detours {
+    ($($arg:ident),*) => {
+        impl_closure_detour!("Rust"; $($arg),*);
+        impl_closure_detour!("C"; $($arg),*);
+        impl_closure_detour!("system"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_closure_detour!("stdcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_closure_detour!("fastcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_closure_detour!("thiscall"; $($arg),*);
+    };
+}
+
+impl_closure_detours!();
+impl_closure_detours!(A);
+impl_closure_detours!(A, B);
+impl_closure_detours!(A, B, C);
+impl_closure_detours!(A, B, C, D);
+impl_closure_detours!(A, B, C, D, E);
+impl_closure_detours!(A, B, C, D, E, F);
+impl_closure_detours!(A, B, C, D, E, F, G);
+impl_closure_detours!(A, B, C, D, E, F, G, H);
+impl_closure_detours!(A, B, C, D, E, F, G, H, I);
+impl_closure_detours!(A, B, C, D, E, F, G, H, I, J);
+impl_closure_detours!(A, B, C, D, E, F, G, H, I, J, K);
+impl_closure_detours!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// A hook whose detour is a closure, which can capture state and receives the original function as its first argument.
+///
+/// Dropping it removes the hook as a [`HookGuard`](crate::HookGuard) does, and then releases the closure. The methods
+/// of [`Hook`] are available through `Deref`.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{ClosureHook, HookError};
+///
+/// fn main() -> Result<(), HookError> {
+///     let offset = 10;
+///     let hook = unsafe {
+///         ClosureHook::<fn(i32) -> i32>::new(
+///             double,
+///             Box::new(move |original, x| original(x) + offset),
+///         )?
+///     };
+///     unsafe { hook.enable()? };
+///
+///     assert_eq!(double(2), 14);
+///
+///     Ok(())
+/// }
+///
+/// fn double(x: i32) -> i32 {
+///     x * 2
+/// }
+/// ```
+pub struct ClosureHook<F: ClosureDetour> {
+    hook: ThunkHook<F, BoundThunk<ClosureContext<F>>>,
+}
+
+impl<F: ClosureDetour> ClosureHook<F> {
+    /// Creates a hook for the target function and detours it to `closure`. The hook is created disabled.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`](crate::MinHook::create_hook). The hook must not be dropped while another thread
+    /// may still be executing the closure.
+    pub unsafe fn new(target: F, closure: Box<F::Closure>) -> Result<Self, HookError> {
+        let context = ClosureContext {
+            original: OnceLock::new(),
+            closure,
+        };
+
+        let thunk = BoundThunk::new(context, |context| {
+            closure_thunk(context as *const c_void, F::handler().to_ptr())
+        })
+        .ok_or_else(|| {
+            HookError::new(
+                Operation::CreateHook,
+                Some(HookTarget::Address(target.to_ptr() as usize)),
+                ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_ALLOC),
+            )
+        })?;
+
+        let hook = unsafe { Hook::new(target, F::from_ptr(thunk.address() as *mut c_void))? };
+        let _ = thunk.state().original.set(hook.original());
+
+        Ok(Self {
+            hook: ThunkHook::new(hook.into_guard(), thunk),
+        })
+    }
+
+    /// Releases the hook without removing it. The closure and its thunk stay alive for the rest of the process.
+    pub fn leak(self) -> Hook<F> {
+        self.hook.leak()
+    }
+}
+
+impl<F: ClosureDetour> Deref for ClosureHook<F> {
+    type Target = Hook<F>;
+
+    fn deref(&self) -> &Hook<F> {
+        self.hook.guard()
+    }
+}
+
+impl<F: ClosureDetour> fmt::Debug for ClosureHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClosureHook")
+            .field("hook", &**self.hook.guard())
+            .field("thunk", &(self.hook.thunks().address() as *const c_void))
+            .finish()
+    }
+}
@@ -0,0 +1,60 @@
+//! Options for initializing MinHook.
+
+/// Options for [`MinHook::init`](crate::MinHook::init).
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{Config, MinHook};
+///
+/// MinHook::init(Config::new().auto_initialize(false)).unwrap();
+/// assert!(MinHook::is_initialized());
+///
+/// unsafe { MinHook::shutdown().unwrap() };
+/// assert!(!MinHook::is_initialized());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub(crate) auto_initialize: bool,
+    #[cfg_attr(not(rust_engine), allow(dead_code))]
+    pub(crate) freeze_threads: bool,
+}
+
+impl Config {
+    /// Creates the default configuration, which is used when MinHook is initialized on first use.
+    pub const fn new() -> Self {
+        Self {
+            auto_initialize: true,
+            freeze_threads: true,
+        }
+    }
+
+    /// Sets whether the hooking functions initialize MinHook when it is not initialized. When disabled, they return
+    /// `MH_ERROR_NOT_INITIALIZED` after [`MinHook::shutdown`](crate::MinHook::shutdown) until MinHook is initialized
+    /// again. Enabled by default.
+    pub const fn auto_initialize(mut self, enabled: bool) -> Self {
+        self.auto_initialize = enabled;
+        self
+    }
+
+    /// Sets whether the other threads of the process are suspended while hooks are enabled or disabled, so none of
+    /// them is left executing code that is being patched. Disabling it makes enabling and disabling hooks faster, but
+    /// is only safe if no other thread can run the target functions meanwhile. Enabled by default.
+    ///
+    /// On Linux, the other threads are suspended by a handler of the real-time signal `SIGRTMAX - 1`, which is
+    /// installed the first time hooks are enabled or disabled. Threads that block the signal keep running, and if the
+    /// application already installed a handler for it, that handler is kept and no threads are suspended. Both are
+    /// reported through `tracing`.
+    ///
+    /// Only the Rust engine supports this option; the C library always suspends the other threads.
+    pub const fn freeze_threads(mut self, enabled: bool) -> Self {
+        self.freeze_threads = enabled;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
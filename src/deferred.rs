@@ -0,0 +1,551 @@
+//! Hooks on exported functions of modules that may not be loaded yet.
+//!
+//! The functions of the loader that load and unload modules are hooked when the first deferred hook is created:
+//! `dlopen` and `dlclose` on Linux, and `LdrLoadDll` and `LdrUnloadDll` on Windows. After a module is loaded, the
+//! pending deferred hooks are created and enabled. Before a module is unloaded, the hooks on its functions are removed,
+//! and those whose module stays loaded are created again after the unload. The loader functions are unhooked again
+//! with the last deferred hook.
+//!
+//! The loader calls back into this module while it holds its own lock, for example when a module loads another one
+//! from its constructor. So no lock is held here while calling the loader, and a hook that another thread is already
+//! changing is skipped rather than waited for.
+
+use crate::{FnPtr, HookError, HookTarget, MH_STATUS, MinHook, Operation, os};
+use std::{
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+use tracing::{debug, error, warn};
+
+/// A deferred hook, shared between its handle and the list of deferred hooks.
+struct State {
+    module: String,
+    symbol: String,
+    detour: usize,
+    target: AtomicUsize,
+    original: AtomicUsize,
+    /// Set while a thread installs or uninstalls the hook.
+    changing: AtomicBool,
+    /// Set once the handle removed the hook, so it is not installed again.
+    removed: AtomicBool,
+}
+
+/// Marks a hook as being changed by this thread until dropped.
+struct Change<'a>(&'a State);
+
+impl Drop for Change<'_> {
+    fn drop(&mut self) {
+        self.0.changing.store(false, Ordering::SeqCst);
+    }
+}
+
+impl State {
+    fn is_installed(&self) -> bool {
+        self.target.load(Ordering::Acquire) != 0
+    }
+
+    /// Marks the hook as being changed by this thread, unless another thread is already changing it.
+    fn try_change(&self) -> Option<Change<'_>> {
+        (!self.changing.swap(true, Ordering::SeqCst)).then_some(Change(self))
+    }
+
+    /// Changes the hook with `f`, unless another thread is already changing it or the handle removed it. If the handle
+    /// removed it meanwhile, it is uninstalled here, since [`remove`] left that to this thread.
+    fn change(&self, f: impl FnOnce(&Self)) {
+        let Some(change) = self.try_change() else {
+            return;
+        };
+        if !self.removed.load(Ordering::SeqCst) {
+            f(self);
+        }
+        drop(change);
+
+        while self.removed.load(Ordering::SeqCst) && self.is_installed() {
+            let Some(_change) = self.try_change() else {
+                return;
+            };
+            if let Err(error) = self.uninstall() {
+                error!(
+                    "Could not remove the deferred hook for {}!{}: {}",
+                    self.module, self.symbol, error
+                );
+                return;
+            }
+        }
+    }
+
+    /// Creates and enables the hook if the module is loaded. Returns `MH_ERROR_MODULE_NOT_FOUND` if it is not.
+    fn install(&self) -> Result<(), HookError> {
+        let (original, target) = unsafe {
+            MinHook::create_hook_api_ex(&self.module, &self.symbol, self.detour as *mut c_void)?
+        };
+        self.original.store(original as usize, Ordering::Release);
+
+        if let Err(error) = unsafe { MinHook::enable_hook(target) } {
+            let _ = unsafe { MinHook::remove_hook(target) };
+            self.original.store(0, Ordering::Release);
+            return Err(error);
+        }
+
+        self.target.store(target as usize, Ordering::Release);
+        debug!(
+            "installed deferred hook for {}!{}",
+            self.module, self.symbol
+        );
+        Ok(())
+    }
+
+    fn uninstall(&self) -> Result<(), HookError> {
+        let target = self.target.load(Ordering::Acquire);
+        if target != 0 {
+            unsafe { MinHook::remove_hook(target as *mut c_void)? };
+            self.target.store(0, Ordering::Release);
+            self.original.store(0, Ordering::Release);
+        }
+        Ok(())
+    }
+}
+
+/// The created deferred hooks. The lock is only held to change the list, never while calling the loader.
+static DEFERRED: Mutex<Vec<Arc<State>>> = Mutex::new(Vec::new());
+
+fn lock() -> MutexGuard<'static, Vec<Arc<State>>> {
+    DEFERRED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the deferred hooks that are installed, or those that are not.
+fn snapshot(installed: bool) -> Vec<Arc<State>> {
+    lock()
+        .iter()
+        .filter(|state| state.is_installed() == installed)
+        .cloned()
+        .collect()
+}
+
+/// Installs the pending hooks whose module is loaded now.
+fn loaded() {
+    if os::is_looking_up() {
+        return;
+    }
+
+    for state in snapshot(false) {
+        state.change(|state| {
+            if state.is_installed() {
+                return;
+            }
+
+            match state.install() {
+                Ok(()) => {}
+                Err(error) if error == MH_STATUS::MH_ERROR_MODULE_NOT_FOUND => {}
+                Err(error) => warn!("Could not install deferred hook: {}", error),
+            }
+        });
+    }
+}
+
+/// Removes the hooks on the functions of a module that is about to be unloaded. Returns `true` if any were removed.
+fn unloading(handle: usize) -> bool {
+    if os::is_looking_up() {
+        return false;
+    }
+
+    let mut removed = false;
+    for state in snapshot(true) {
+        if os::module_handle(&state.module) != Some(handle) {
+            continue;
+        }
+
+        state.change(|state| match state.uninstall() {
+            Ok(()) => removed = true,
+            Err(error) => warn!("Could not remove deferred hook before unloading: {}", error),
+        });
+    }
+    removed
+}
+
+#[cfg(target_os = "linux")]
+mod loader {
+    use std::{
+        ffi::{c_char, c_int, c_void},
+        mem,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// The modules exporting the loader functions, which moved from `libdl` to `libc` in glibc 2.34.
+    pub(super) const MODULES: [&str; 2] = ["libc.so.6", "libdl.so.2"];
+    pub(super) const LOAD: &str = "dlopen";
+    pub(super) const UNLOAD: &str = "dlclose";
+
+    pub(super) static LOAD_ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static UNLOAD_ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+
+    pub(super) unsafe extern "C" fn load(filename: *const c_char, flags: c_int) -> *mut c_void {
+        let original: unsafe extern "C" fn(*const c_char, c_int) -> *mut c_void =
+            unsafe { mem::transmute(LOAD_ORIGINAL.load(Ordering::Acquire)) };
+
+        let handle = unsafe { original(filename, flags) };
+        if !handle.is_null() && flags & libc::RTLD_NOLOAD == 0 {
+            super::loaded();
+        }
+        handle
+    }
+
+    pub(super) unsafe extern "C" fn unload(handle: *mut c_void) -> c_int {
+        let original: unsafe extern "C" fn(*mut c_void) -> c_int =
+            unsafe { mem::transmute(UNLOAD_ORIGINAL.load(Ordering::Acquire)) };
+
+        let removed = super::unloading(handle as usize);
+        let status = unsafe { original(handle) };
+        if removed {
+            super::loaded();
+        }
+        status
+    }
+}
+
+#[cfg(windows)]
+mod loader {
+    use std::{
+        ffi::c_void,
+        mem,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    pub(super) const MODULES: [&str; 1] = ["ntdll.dll"];
+    pub(super) const LOAD: &str = "LdrLoadDll";
+    pub(super) const UNLOAD: &str = "LdrUnloadDll";
+
+    pub(super) static LOAD_ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+    pub(super) static UNLOAD_ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+
+    type LoadFn =
+        unsafe extern "system" fn(*const u16, *const u32, *const c_void, *mut *mut c_void) -> i32;
+
+    pub(super) unsafe extern "system" fn load(
+        path: *const u16,
+        characteristics: *const u32,
+        name: *const c_void,
+        handle: *mut *mut c_void,
+    ) -> i32 {
+        let original: LoadFn = unsafe { mem::transmute(LOAD_ORIGINAL.load(Ordering::Acquire)) };
+
+        let status = unsafe { original(path, characteristics, name, handle) };
+        // Success and informational statuses are not negative.
+        if status >= 0 {
+            super::loaded();
+        }
+        status
+    }
+
+    pub(super) unsafe extern "system" fn unload(handle: *mut c_void) -> i32 {
+        let original: unsafe extern "system" fn(*mut c_void) -> i32 =
+            unsafe { mem::transmute(UNLOAD_ORIGINAL.load(Ordering::Acquire)) };
+
+        let removed = super::unloading(handle as usize);
+        let status = unsafe { original(handle) };
+        if removed {
+            super::loaded();
+        }
+        status
+    }
+}
+
+/// The targets of the hooks on the loader functions. The lock is taken before [`DEFERRED`], and held while deferred
+/// hooks are added and the last one is removed, so the loader stays hooked while there are any.
+static LOADER_TARGETS: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+
+fn lock_loader() -> MutexGuard<'static, Option<(usize, usize)>> {
+    LOADER_TARGETS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Hooks the loader functions, unless they are still hooked. The hooks are removed by [`MinHook::shutdown`] like all
+/// others, so they are created again by the next deferred hook.
+fn hook_loader(targets: &mut Option<(usize, usize)>) -> Result<(), HookError> {
+    if let Some((load, unload)) = *targets {
+        if MinHook::is_created(load as *mut c_void) && MinHook::is_created(unload as *mut c_void) {
+            return Ok(());
+        }
+        unsafe {
+            let _ = MinHook::remove_hook(load as *mut c_void);
+            let _ = MinHook::remove_hook(unload as *mut c_void);
+        }
+    }
+
+    let hook = |symbol: &str, detour: *const (), original: &AtomicUsize| {
+        let mut result = Err(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND.into());
+        for module in loader::MODULES {
+            result = unsafe { MinHook::create_hook_api_ex(module, symbol, detour as *mut c_void) };
+            match &result {
+                Err(error) if error == &MH_STATUS::MH_ERROR_MODULE_NOT_FOUND => continue,
+                Err(error) if error == &MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND => continue,
+                _ => break,
+            }
+        }
+
+        let (trampoline, target) = result?;
+        original.store(trampoline as usize, Ordering::Release);
+        Ok::<_, HookError>(target)
+    };
+
+    let load = hook(
+        loader::LOAD,
+        loader::load as *const (),
+        &loader::LOAD_ORIGINAL,
+    )?;
+    let unload = match hook(
+        loader::UNLOAD,
+        loader::unload as *const (),
+        &loader::UNLOAD_ORIGINAL,
+    ) {
+        Ok(unload) => unload,
+        Err(error) => {
+            let _ = unsafe { MinHook::remove_hook(load) };
+            return Err(error);
+        }
+    };
+
+    unsafe {
+        if let Err(error) = MinHook::enable_hook(load).and_then(|()| MinHook::enable_hook(unload)) {
+            let _ = MinHook::remove_hook(load);
+            let _ = MinHook::remove_hook(unload);
+            return Err(error);
+        }
+    }
+
+    *targets = Some((load as usize, unload as usize));
+    Ok(())
+}
+
+/// Unhooks the loader functions after the last deferred hook was removed.
+fn unhook_loader(targets: &mut Option<(usize, usize)>) {
+    let Some((load, unload)) = targets.take() else {
+        return;
+    };
+
+    for target in [load, unload] {
+        match unsafe { MinHook::remove_hook(target as *mut c_void) } {
+            Ok(()) => {}
+            // Removed by `MinHook::shutdown`.
+            Err(error)
+                if error == MH_STATUS::MH_ERROR_NOT_CREATED
+                    || error == MH_STATUS::MH_ERROR_NOT_INITIALIZED => {}
+            Err(error) => warn!("Could not unhook the loader: {}", error),
+        }
+    }
+}
+
+/// A hook on an exported function of a module that may not be loaded yet, which is created and enabled as soon as the
+/// module is loaded.
+///
+/// The module and the function are looked up as for [`MinHook::create_hook_api`]. If the module is already loaded, the
+/// hook is installed right away. Otherwise, it is installed when the module is loaded, which is noticed by hooking the
+/// functions of the loader that load and unload modules. When the module is unloaded, the hook is removed, and it is
+/// installed again if the module is loaded again.
+///
+/// The hook is removed when dropped. Errors while removing it cannot be returned from `Drop`, so they are reported
+/// through `tracing` instead, like errors while installing it when the module is loaded.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use minhook::{DeferredHook, HookError};
+/// use std::sync::OnceLock;
+///
+/// type PluginVersion = extern "C" fn(i32) -> i32;
+///
+/// static HOOK: OnceLock<DeferredHook<PluginVersion>> = OnceLock::new();
+///
+/// extern "C" fn plugin_version(x: i32) -> i32 {
+///     // The original function changes each time the hook is installed.
+///     let original = HOOK.get().and_then(|hook| hook.original()).unwrap();
+///     original(x) + 1
+/// }
+///
+/// fn main() -> Result<(), HookError> {
+///     let hook = unsafe {
+///         DeferredHook::<PluginVersion>::new("libplugin.so", "plugin_version", plugin_version)?
+///     };
+///     HOOK.get_or_init(|| hook);
+///
+///     // The hook is installed once the plugin is loaded.
+///     Ok(())
+/// }
+/// ```
+pub struct DeferredHook<F: FnPtr = *mut c_void> {
+    state: Arc<State>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FnPtr> DeferredHook<F> {
+    /// Creates a hook on the function `symbol` of the module `module_name`, which is installed when the module is
+    /// loaded, or right away if it is already loaded.
+    ///
+    /// Returns the error of [`MinHook::create_hook_api`] if the module is loaded but the hook cannot be created, or an
+    /// error if the loader functions cannot be hooked.
+    ///
+    /// # Safety
+    ///
+    /// The function must have the signature and calling convention of `F`. See also [`MinHook::create_hook`] and
+    /// [`MinHook::enable_hook`], which are called whenever the module is loaded.
+    pub unsafe fn new<T: AsRef<str>>(
+        module_name: T,
+        symbol: T,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        let (module_name, symbol) = (module_name.as_ref(), symbol.as_ref());
+        crate::encode_api_names(module_name, symbol, detour.to_ptr()).map_err(|kind| {
+            HookError::new(
+                Operation::CreateDeferredHook,
+                Some(HookTarget::Symbol {
+                    module: module_name.to_owned(),
+                    symbol: symbol.to_owned(),
+                }),
+                kind,
+            )
+        })?;
+
+        let state = Arc::new(State {
+            module: module_name.to_owned(),
+            symbol: symbol.to_owned(),
+            detour: detour.to_ptr() as usize,
+            target: AtomicUsize::new(0),
+            original: AtomicUsize::new(0),
+            changing: AtomicBool::new(false),
+            removed: AtomicBool::new(false),
+        });
+
+        {
+            let mut targets = lock_loader();
+            hook_loader(&mut targets)?;
+            lock().push(state.clone());
+        }
+
+        // The hook is listed before it is installed, so a module loaded meanwhile by another thread installs it. In that
+        // case, that thread reports any error.
+        if let Some(change) = state.try_change() {
+            if let Err(error) = state.install() {
+                if error != MH_STATUS::MH_ERROR_MODULE_NOT_FOUND {
+                    drop(change);
+                    forget(&state, &mut lock_loader());
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(Self {
+            state,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the name of the module.
+    pub fn module_name(&self) -> &str {
+        &self.state.module
+    }
+
+    /// Returns the name of the function.
+    pub fn symbol(&self) -> &str {
+        &self.state.symbol
+    }
+
+    /// Returns the detour function.
+    pub fn detour(&self) -> F {
+        unsafe { F::from_ptr(self.state.detour as *mut c_void) }
+    }
+
+    /// Returns `true` if the module is loaded and the hook is installed.
+    pub fn is_installed(&self) -> bool {
+        self.state.is_installed()
+    }
+
+    /// Returns the hooked function while the hook is installed.
+    pub fn target(&self) -> Option<F> {
+        match self.state.target.load(Ordering::Acquire) {
+            0 => None,
+            target => Some(unsafe { F::from_ptr(target as *mut c_void) }),
+        }
+    }
+
+    /// Returns the trampoline that calls the original function while the hook is installed. It changes each time the
+    /// hook is installed, so it should not be kept across calls of the detour.
+    pub fn original(&self) -> Option<F> {
+        match self.state.original.load(Ordering::Acquire) {
+            0 => None,
+            original => Some(unsafe { F::from_ptr(original as *mut c_void) }),
+        }
+    }
+
+    /// Removes the hook if it is installed, and stops installing it when the module is loaded.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::remove_hook`].
+    pub unsafe fn remove(self) -> Result<(), HookError> {
+        let this = ManuallyDrop::new(self);
+        let state = unsafe { std::ptr::read(&this.state) };
+        remove(&state)
+    }
+
+    /// Releases the handle without removing the hook, which keeps being installed whenever the module is loaded.
+    pub fn leak(self) {
+        let this = ManuallyDrop::new(self);
+        drop(unsafe { std::ptr::read(&this.state) });
+    }
+}
+
+fn remove(state: &Arc<State>) -> Result<(), HookError> {
+    state.removed.store(true, Ordering::SeqCst);
+    // Otherwise, the thread that is changing the hook uninstalls it when it is done.
+    if let Some(_change) = state.try_change() {
+        if let Err(error) = state.uninstall() {
+            state.removed.store(false, Ordering::SeqCst);
+            return Err(error);
+        }
+    }
+
+    forget(state, &mut lock_loader());
+    Ok(())
+}
+
+/// Drops a deferred hook from the list, and unhooks the loader if it was the last one.
+fn forget(state: &Arc<State>, targets: &mut Option<(usize, usize)>) {
+    let mut deferred = lock();
+    deferred.retain(|other| !Arc::ptr_eq(other, state));
+    let empty = deferred.is_empty();
+    drop(deferred);
+
+    if empty {
+        unhook_loader(targets);
+    }
+}
+
+impl<F: FnPtr> Drop for DeferredHook<F> {
+    fn drop(&mut self) {
+        if let Err(status) = remove(&self.state) {
+            error!(
+                "Could not remove the deferred hook for {}!{}: {:?}",
+                self.state.module, self.state.symbol, status
+            );
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for DeferredHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeferredHook")
+            .field("module_name", &self.state.module)
+            .field("symbol", &self.state.symbol)
+            .field("detour", &self.detour().to_ptr())
+            .field("target", &self.target().map(FnPtr::to_ptr))
+            .field("original", &self.original().map(FnPtr::to_ptr))
+            .finish()
+    }
+}
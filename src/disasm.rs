@@ -0,0 +1,643 @@
+//! A small x86/x64 instruction length decoder, modelled after the HDE32/HDE64 disassemblers bundled with MinHook.
+//!
+//! The decoder finds the length and the fields of an instruction, which is what is needed to walk code and relocate
+//! it, but does not name the instruction or its operands.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minhook::disasm::{self, Mode};
+//!
+//! // mov rax, [rip+0x10]; ret
+//! let code = [0x48, 0x8B, 0x05, 0x10, 0x00, 0x00, 0x00, 0xC3];
+//!
+//! let instruction = disasm::decode(&code, Mode::X64).unwrap();
+//! assert_eq!(instruction.len(), 7);
+//! assert_eq!(instruction.opcode(), 0x8B);
+//! assert!(instruction.is_rip_relative());
+//! assert_eq!(instruction.displacement().unwrap().value(), 0x10);
+//!
+//! let lengths = disasm::instructions(&code, Mode::X64)
+//!     .map(|result| result.map(|(_, instruction)| instruction.len()))
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! assert_eq!(lengths, [7, 1]);
+//! ```
+
+use std::{error::Error, fmt};
+
+/// The processor mode used to decode instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// 32-bit protected mode.
+    X86,
+    /// 64-bit long mode.
+    X64,
+}
+
+impl Mode {
+    /// The mode of the current process.
+    pub const NATIVE: Mode = if cfg!(target_pointer_width = "64") {
+        Mode::X64
+    } else {
+        Mode::X86
+    };
+}
+
+/// The opcode map an instruction belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Map {
+    /// One-byte opcodes.
+    Primary,
+    /// Two-byte opcodes starting with `0F`.
+    Secondary,
+    /// Three-byte opcodes starting with `0F 38`.
+    Escape38,
+    /// Three-byte opcodes starting with `0F 3A`.
+    Escape3A,
+    /// EVEX map 5 or 6.
+    Evex(u8),
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub(crate) len: usize,
+    pub(crate) map: Map,
+    pub(crate) opcode: u8,
+    pub(crate) modrm: Option<u8>,
+    pub(crate) sib: Option<u8>,
+    pub(crate) prefixes: Prefixes,
+    pub(crate) disp_offset: usize,
+    pub(crate) disp_size: usize,
+    pub(crate) disp: i64,
+    pub(crate) imm_offset: usize,
+    pub(crate) imm_size: usize,
+    pub(crate) imm: u64,
+    pub(crate) rip_relative: bool,
+}
+
+/// The prefixes of an instruction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Prefixes {
+    lock: bool,
+    rep: bool,
+    repne: bool,
+    segment: Option<u8>,
+    operand_size: bool,
+    address_size: bool,
+    rex: Option<u8>,
+    vex: Option<u8>,
+    w: bool,
+}
+
+impl Prefixes {
+    /// Returns `true` if the instruction has a `LOCK` (`F0`) prefix.
+    pub fn lock(&self) -> bool {
+        self.lock
+    }
+
+    /// Returns `true` if the instruction has a `REP`/`REPE` (`F3`) prefix.
+    pub fn rep(&self) -> bool {
+        self.rep
+    }
+
+    /// Returns `true` if the instruction has a `REPNE` (`F2`) prefix.
+    pub fn repne(&self) -> bool {
+        self.repne
+    }
+
+    /// Returns the last segment override prefix (`26`, `2E`, `36`, `3E`, `64` or `65`).
+    pub fn segment(&self) -> Option<u8> {
+        self.segment
+    }
+
+    /// Returns `true` if the instruction has an operand size (`66`) prefix.
+    pub fn operand_size(&self) -> bool {
+        self.operand_size
+    }
+
+    /// Returns `true` if the instruction has an address size (`67`) prefix.
+    pub fn address_size(&self) -> bool {
+        self.address_size
+    }
+
+    /// Returns the REX prefix, which only exists in 64-bit mode.
+    pub fn rex(&self) -> Option<u8> {
+        self.rex
+    }
+
+    /// Returns the first byte of the VEX (`C4` or `C5`) or EVEX (`62`) prefix.
+    pub fn vex(&self) -> Option<u8> {
+        self.vex
+    }
+
+    /// Returns the `W` bit of the REX, VEX or EVEX prefix, which selects 64-bit operands for most instructions.
+    pub fn w(&self) -> bool {
+        self.w
+    }
+}
+
+/// The displacement of a memory operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Displacement {
+    offset: usize,
+    size: usize,
+    value: i64,
+}
+
+impl Displacement {
+    /// Returns the offset of the displacement from the start of the instruction.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the size of the displacement in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the sign-extended displacement.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+}
+
+/// The immediate operand of an instruction, which includes the target of relative branches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Immediate {
+    offset: usize,
+    size: usize,
+    value: u64,
+}
+
+impl Immediate {
+    /// Returns the offset of the immediate from the start of the instruction.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the size of the immediate in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the immediate, zero-extended from its size.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the immediate, sign-extended from its size.
+    pub fn signed_value(&self) -> i64 {
+        sign_extend(self.value, self.size)
+    }
+}
+
+impl Instruction {
+    /// Returns the length of the instruction in bytes.
+    #[allow(clippy::len_without_is_empty)] // An instruction is never empty.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the opcode map of the instruction.
+    pub fn map(&self) -> Map {
+        self.map
+    }
+
+    /// Returns the last byte of the opcode.
+    pub fn opcode(&self) -> u8 {
+        self.opcode
+    }
+
+    /// Returns the ModR/M byte.
+    pub fn modrm(&self) -> Option<u8> {
+        self.modrm
+    }
+
+    /// Returns the `reg` field of the ModR/M byte.
+    pub fn modrm_reg(&self) -> Option<u8> {
+        self.modrm.map(|modrm| (modrm >> 3) & 7)
+    }
+
+    /// Returns the SIB byte.
+    pub fn sib(&self) -> Option<u8> {
+        self.sib
+    }
+
+    /// Returns the prefixes of the instruction.
+    pub fn prefixes(&self) -> Prefixes {
+        self.prefixes
+    }
+
+    /// Returns the displacement of the memory operand.
+    pub fn displacement(&self) -> Option<Displacement> {
+        (self.disp_size > 0).then_some(Displacement {
+            offset: self.disp_offset,
+            size: self.disp_size,
+            value: self.disp,
+        })
+    }
+
+    /// Returns the immediate operand.
+    pub fn immediate(&self) -> Option<Immediate> {
+        (self.imm_size > 0).then_some(Immediate {
+            offset: self.imm_offset,
+            size: self.imm_size,
+            value: self.imm,
+        })
+    }
+
+    /// Returns `true` if the memory operand is addressed relative to the next instruction, which only exists in 64-bit
+    /// mode.
+    pub fn is_rip_relative(&self) -> bool {
+        self.rip_relative
+    }
+
+    /// Returns `true` if the instruction is a multi-byte or single-byte NOP.
+    pub fn is_nop(&self) -> bool {
+        match self.map {
+            Map::Primary => self.opcode == 0x90,
+            Map::Secondary => self.opcode == 0x1F && self.modrm_reg() == Some(0),
+            _ => false,
+        }
+    }
+}
+
+/// An error returned when decoding an instruction fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The byte slice ended in the middle of an instruction.
+    Truncated,
+    /// The opcode is not valid in the selected mode.
+    InvalidOpcode,
+    /// The instruction is longer than 15 bytes.
+    TooLong,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            DecodeError::Truncated => "The code ends in the middle of an instruction.",
+            DecodeError::InvalidOpcode => "The opcode is not valid in the selected mode.",
+            DecodeError::TooLong => "The instruction is longer than 15 bytes.",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+impl Error for DecodeError {}
+
+const MAX_INSTRUCTION_LEN: usize = 15;
+
+struct Reader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Result<u8, DecodeError> {
+        self.code
+            .get(self.pos)
+            .copied()
+            .ok_or(DecodeError::Truncated)
+    }
+
+    fn next(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn value(&mut self, size: usize) -> Result<u64, DecodeError> {
+        let bytes = self
+            .code
+            .get(self.pos..self.pos + size)
+            .ok_or(DecodeError::Truncated)?;
+        self.pos += size;
+
+        let mut value = [0u8; 8];
+        value[..size].copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(value))
+    }
+}
+
+/// Decodes the instruction at the beginning of `code`.
+pub fn decode(code: &[u8], mode: Mode) -> Result<Instruction, DecodeError> {
+    let mut reader = Reader { code, pos: 0 };
+    let mut prefixes = Prefixes::default();
+
+    // Legacy prefixes and REX. A REX prefix is only honored if it immediately precedes the opcode.
+    let mut byte = loop {
+        let byte = reader.next()?;
+        match byte {
+            0x66 => prefixes.operand_size = true,
+            0x67 => prefixes.address_size = true,
+            0xF0 => prefixes.lock = true,
+            0xF2 => prefixes.repne = true,
+            0xF3 => prefixes.rep = true,
+            0x26 | 0x2E | 0x36 | 0x3E | 0x64 | 0x65 => prefixes.segment = Some(byte),
+            0x40..=0x4F if mode == Mode::X64 => prefixes.rex = Some(byte),
+            _ => break byte,
+        }
+        if !(0x40..=0x4F).contains(&byte) || mode != Mode::X64 {
+            prefixes.rex = None;
+        }
+        if reader.pos >= MAX_INSTRUCTION_LEN {
+            return Err(DecodeError::TooLong);
+        }
+    };
+    prefixes.w = prefixes.rex.is_some_and(|rex| rex & 0x08 != 0);
+
+    // VEX and EVEX. In 32-bit mode these encodings overlap LES, LDS and BOUND, which are
+    // distinguished by a register-form ModR/M byte.
+    let extended = matches!(byte, 0xC4 | 0xC5 | 0x62)
+        && (mode == Mode::X64 || reader.peek().is_ok_and(|next| next & 0xC0 == 0xC0));
+
+    let (map, opcode) = if extended {
+        prefixes.vex = Some(byte);
+        let map = match byte {
+            0xC5 => {
+                reader.next()?;
+                1
+            }
+            0xC4 => {
+                let map = reader.next()? & 0x1F;
+                prefixes.w = reader.next()? & 0x80 != 0;
+                map
+            }
+            _ => {
+                let map = reader.next()? & 0x07;
+                prefixes.w = reader.next()? & 0x80 != 0;
+                reader.next()?;
+                map
+            }
+        };
+        let map = match map {
+            1 => Map::Secondary,
+            2 => Map::Escape38,
+            3 => Map::Escape3A,
+            5 | 6 if byte == 0x62 => Map::Evex(map),
+            _ => return Err(DecodeError::InvalidOpcode),
+        };
+        (map, reader.next()?)
+    } else if byte == 0x0F {
+        byte = reader.next()?;
+        match byte {
+            0x38 => (Map::Escape38, reader.next()?),
+            0x3A => (Map::Escape3A, reader.next()?),
+            _ => (Map::Secondary, byte),
+        }
+    } else {
+        (Map::Primary, byte)
+    };
+
+    if !is_valid(map, opcode, mode) {
+        return Err(DecodeError::InvalidOpcode);
+    }
+
+    let mut instruction = Instruction {
+        len: 0,
+        map,
+        opcode,
+        modrm: None,
+        sib: None,
+        prefixes,
+        disp_offset: 0,
+        disp_size: 0,
+        disp: 0,
+        imm_offset: 0,
+        imm_size: 0,
+        imm: 0,
+        rip_relative: false,
+    };
+
+    let has_modrm = if extended {
+        !(map == Map::Secondary && opcode == 0x77)
+    } else {
+        has_modrm(map, opcode)
+    };
+
+    if has_modrm {
+        let modrm = reader.next()?;
+        instruction.modrm = Some(modrm);
+
+        let md = modrm >> 6;
+        let rm = modrm & 7;
+        let disp_size = if mode == Mode::X86 && prefixes.address_size {
+            // 16-bit addressing has no SIB byte.
+            match (md, rm) {
+                (0, 6) | (2, _) => 2,
+                (1, _) => 1,
+                _ => 0,
+            }
+        } else {
+            // In 64-bit mode, ModR/M = 00???101B addresses memory relative to RIP.
+            instruction.rip_relative = mode == Mode::X64 && md == 0 && rm == 5;
+            let mut disp_size = match (md, rm) {
+                (0, 5) | (2, _) => 4,
+                (1, _) => 1,
+                _ => 0,
+            };
+            if md != 3 && rm == 4 {
+                let sib = reader.next()?;
+                instruction.sib = Some(sib);
+                if md == 0 && sib & 7 == 5 {
+                    disp_size = 4;
+                }
+            }
+            disp_size
+        };
+
+        if disp_size > 0 {
+            instruction.disp_offset = reader.pos;
+            instruction.disp_size = disp_size;
+            instruction.disp = sign_extend(reader.value(disp_size)?, disp_size);
+        }
+    }
+
+    let imm_size = if extended {
+        match map {
+            Map::Escape3A => 1,
+            Map::Secondary if matches!(opcode, 0x70..=0x73 | 0xC2 | 0xC4..=0xC6) => 1,
+            _ => 0,
+        }
+    } else {
+        immediate_size(
+            map,
+            opcode,
+            instruction.modrm_reg(),
+            &instruction.prefixes,
+            mode,
+        )
+    };
+
+    if imm_size > 0 {
+        instruction.imm_offset = reader.pos;
+        instruction.imm_size = imm_size;
+        instruction.imm = reader.value(imm_size)?;
+    }
+
+    if reader.pos > MAX_INSTRUCTION_LEN {
+        return Err(DecodeError::TooLong);
+    }
+
+    instruction.len = reader.pos;
+    Ok(instruction)
+}
+
+/// Decodes the instructions in `code` one after another, together with their offsets. Decoding stops after the first
+/// error.
+pub fn instructions(code: &[u8], mode: Mode) -> Instructions<'_> {
+    Instructions {
+        code,
+        mode,
+        offset: 0,
+        failed: false,
+    }
+}
+
+/// An iterator over the instructions in a byte slice, created by [`instructions`].
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    mode: Mode,
+    offset: usize,
+    failed: bool,
+}
+
+impl Iterator for Instructions<'_> {
+    type Item = Result<(usize, Instruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.offset >= self.code.len() {
+            return None;
+        }
+
+        match decode(&self.code[self.offset..], self.mode) {
+            Ok(instruction) => {
+                let offset = self.offset;
+                self.offset += instruction.len;
+                Some(Ok((offset, instruction)))
+            }
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Instructions<'_> {}
+
+fn sign_extend(value: u64, size: usize) -> i64 {
+    let shift = 64 - size * 8;
+    ((value << shift) as i64) >> shift
+}
+
+fn is_valid(map: Map, opcode: u8, mode: Mode) -> bool {
+    match map {
+        Map::Primary => {
+            mode == Mode::X86
+                || !matches!(
+                    opcode,
+                    0x06 | 0x07
+                        | 0x0E
+                        | 0x16
+                        | 0x17
+                        | 0x1E
+                        | 0x1F
+                        | 0x27
+                        | 0x2F
+                        | 0x37
+                        | 0x3F
+                        | 0x60
+                        | 0x61
+                        | 0x82
+                        | 0x9A
+                        | 0xCE
+                        | 0xD4
+                        | 0xD5
+                        | 0xD6
+                        | 0xEA
+                )
+        }
+        Map::Secondary => !matches!(
+            opcode,
+            0x04 | 0x0A | 0x0C | 0x24..=0x27 | 0x36 | 0x39 | 0x3B..=0x3F | 0x7A | 0x7B | 0xA6 | 0xA7
+        ),
+        _ => true,
+    }
+}
+
+fn has_modrm(map: Map, opcode: u8) -> bool {
+    match map {
+        Map::Primary => match opcode {
+            0x00..=0x3F => opcode & 7 < 4,
+            0x62 | 0x63 | 0x69 | 0x6B | 0x80..=0x8F => true,
+            0xC0 | 0xC1 | 0xC4..=0xC7 | 0xD0..=0xD3 | 0xD8..=0xDF => true,
+            0xF6 | 0xF7 | 0xFE | 0xFF => true,
+            _ => false,
+        },
+        Map::Secondary => !matches!(
+            opcode,
+            0x05..=0x09 | 0x0B | 0x0E | 0x30..=0x37 | 0x77 | 0x80..=0x8F | 0xA0..=0xA2 | 0xA8..=0xAA | 0xC8..=0xCF
+        ),
+        _ => true,
+    }
+}
+
+fn immediate_size(
+    map: Map,
+    opcode: u8,
+    modrm_reg: Option<u8>,
+    prefixes: &Prefixes,
+    mode: Mode,
+) -> usize {
+    // Size of a word or doubleword immediate, depending on the operand size.
+    let z = if prefixes.operand_size && !prefixes.w {
+        2
+    } else {
+        4
+    };
+    // Size of a near branch displacement.
+    let rel = if mode == Mode::X86 && prefixes.operand_size {
+        2
+    } else {
+        4
+    };
+
+    match map {
+        Map::Primary => match opcode {
+            0x00..=0x3F if opcode & 7 == 4 => 1,
+            0x00..=0x3F if opcode & 7 == 5 => z,
+            0x68 | 0x69 | 0x81 | 0xA9 | 0xC7 => z,
+            0x6A | 0x6B | 0x70..=0x7F | 0x80 | 0x82 | 0x83 | 0xA8 | 0xB0..=0xB7 => 1,
+            0xC0 | 0xC1 | 0xC6 | 0xCD | 0xD4 | 0xD5 | 0xE0..=0xE7 | 0xEB => 1,
+            0xB8..=0xBF if prefixes.w => 8,
+            0xB8..=0xBF => z,
+            0xC2 | 0xCA => 2,
+            0xC8 => 3,
+            0xA0..=0xA3 => match (mode, prefixes.address_size) {
+                (Mode::X64, false) => 8,
+                (Mode::X64, true) | (Mode::X86, false) => 4,
+                (Mode::X86, true) => 2,
+            },
+            0xE8 | 0xE9 => rel,
+            0x9A | 0xEA => z + 2,
+            0xF6 if matches!(modrm_reg, Some(0 | 1)) => 1,
+            0xF7 if matches!(modrm_reg, Some(0 | 1)) => z,
+            _ => 0,
+        },
+        Map::Secondary => match opcode {
+            0x0F | 0x70..=0x73 | 0xA4 | 0xAC | 0xBA | 0xC2 | 0xC4..=0xC6 => 1,
+            0x80..=0x8F => rel,
+            _ => 0,
+        },
+        Map::Escape3A => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,608 @@
+//! A Rust port of MinHook's `hook.c`, used in place of the C library on Linux and with the `pure-rust` feature.
+//!
+//! The functions in this module mirror the C API exported by MinHook, so the rest of the crate can call them through
+//! the same `ffi` interface.
+
+#![allow(non_snake_case)]
+
+use crate::{
+    MH_STATUS,
+    buffer::Buffer,
+    disasm::Mode,
+    os,
+    trampoline::{self, JMP_REL_SHORT_SIZE, JMP_REL_SIZE},
+};
+use std::{
+    ffi::{CStr, c_void},
+    slice,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+/// Hook information.
+struct HookEntry {
+    /// Address of the target function.
+    target: usize,
+    /// Address of the detour or relay function.
+    detour: usize,
+    /// Address of the trampoline function.
+    trampoline: usize,
+    /// Original prologue of the target function.
+    backup: [u8; 8],
+    /// Uses the hot patch area.
+    patch_above: bool,
+    /// Enabled.
+    is_enabled: bool,
+    /// Queued for enabling/disabling when != is_enabled.
+    queue_enable: bool,
+    /// Instruction boundaries of the target function and the trampoline function.
+    ips: Vec<(u8, u8)>,
+}
+
+impl HookEntry {
+    /// Returns the address and size of the code that is patched when the hook is enabled.
+    fn patch_range(&self) -> (usize, usize) {
+        if self.patch_above {
+            (
+                self.target - JMP_REL_SIZE,
+                JMP_REL_SIZE + JMP_REL_SHORT_SIZE,
+            )
+        } else {
+            (self.target, JMP_REL_SIZE)
+        }
+    }
+
+    /// Maps an instruction pointer inside the trampoline back to the target function.
+    fn find_old_ip(&self, ip: usize) -> Option<usize> {
+        if self.patch_above && ip == self.target - JMP_REL_SIZE {
+            return Some(self.target);
+        }
+
+        if let Some(&(old, _)) = self
+            .ips
+            .iter()
+            .find(|&&(_, new)| ip == self.trampoline + new as usize)
+        {
+            return Some(self.target + old as usize);
+        }
+
+        // Check relay function.
+        if Mode::NATIVE == Mode::X64 && ip == self.detour {
+            return Some(self.target);
+        }
+
+        None
+    }
+
+    /// Maps an instruction pointer inside the patched prologue to the trampoline.
+    fn find_new_ip(&self, ip: usize) -> Option<usize> {
+        self.ips
+            .iter()
+            .find(|&&(old, _)| ip == self.target + old as usize)
+            .map(|&(_, new)| self.trampoline + new as usize)
+    }
+}
+
+/// The change that is about to be applied while other threads are frozen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Disable,
+    Enable,
+    ApplyQueued,
+}
+
+struct Engine {
+    hooks: Vec<HookEntry>,
+    buffer: Buffer,
+}
+
+impl Engine {
+    fn find_hook_entry(&self, target: usize) -> Option<usize> {
+        self.hooks.iter().position(|hook| hook.target == target)
+    }
+
+    /// Suspends all other threads and moves their instruction pointers out of the code that is about to change, unless
+    /// freezing threads is disabled.
+    fn freeze(&self, pos: Option<usize>, action: Action) -> Option<os::FrozenThreads> {
+        if !FREEZE_THREADS.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let threads = os::FrozenThreads::freeze();
+        threads.move_ips(|ip| self.process_thread_ip(pos, action, ip));
+        Some(threads)
+    }
+
+    fn process_thread_ip(&self, pos: Option<usize>, action: Action, ip: usize) -> Option<usize> {
+        let hooks = match pos {
+            Some(pos) => &self.hooks[pos..=pos],
+            None => &self.hooks[..],
+        };
+
+        hooks.iter().find_map(|hook| {
+            let enable = match action {
+                Action::Disable => false,
+                Action::Enable => true,
+                Action::ApplyQueued => hook.queue_enable,
+            };
+            if hook.is_enabled == enable {
+                return None;
+            }
+
+            if enable {
+                hook.find_new_ip(ip)
+            } else {
+                hook.find_old_ip(ip)
+            }
+        })
+    }
+
+    fn enable_all_hooks_ll(&mut self, enable: bool) -> MH_STATUS {
+        if self.hooks.iter().all(|hook| hook.is_enabled == enable) {
+            return MH_STATUS::MH_OK;
+        }
+
+        let action = if enable {
+            Action::Enable
+        } else {
+            Action::Disable
+        };
+        let _threads = self.freeze(None, action);
+
+        for hook in &mut self.hooks {
+            if hook.is_enabled != enable {
+                let status = enable_hook_ll(hook, enable);
+                if status != MH_STATUS::MH_OK {
+                    return status;
+                }
+            }
+        }
+
+        MH_STATUS::MH_OK
+    }
+}
+
+/// The engine state. `None` while MinHook is not initialized.
+static ENGINE: Mutex<Option<Engine>> = Mutex::new(None);
+
+/// Whether other threads are frozen while hooks are enabled or disabled.
+static FREEZE_THREADS: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether other threads are frozen while hooks are enabled or disabled.
+pub(crate) fn set_freeze_threads(enabled: bool) {
+    FREEZE_THREADS.store(enabled, Ordering::Relaxed);
+}
+
+fn lock() -> MutexGuard<'static, Option<Engine>> {
+    ENGINE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the memory slot the trampoline for `target` would be placed in, as far as it can be told without allocating
+/// it.
+pub(crate) fn trampoline_placement(target: usize) -> Option<usize> {
+    match lock().as_ref() {
+        Some(engine) => engine.buffer.placement(target),
+        None => Buffer::default().placement(target),
+    }
+}
+
+/// Runs `f` with the initialized engine, or reports `MH_ERROR_NOT_INITIALIZED`.
+fn with_engine(f: impl FnOnce(&mut Engine) -> MH_STATUS) -> MH_STATUS {
+    match lock().as_mut() {
+        Some(engine) => f(engine),
+        None => MH_STATUS::MH_ERROR_NOT_INITIALIZED,
+    }
+}
+
+fn enable_hook_ll(hook: &mut HookEntry, enable: bool) -> MH_STATUS {
+    let (patch_target, patch_size) = hook.patch_range();
+
+    let Some(guard) = os::ProtectGuard::new(patch_target, patch_size) else {
+        return MH_STATUS::MH_ERROR_MEMORY_PROTECT;
+    };
+
+    if enable {
+        let mut jmp = [0u8; JMP_REL_SIZE];
+        jmp[0] = 0xE9;
+        let operand = hook.detour.wrapping_sub(patch_target + JMP_REL_SIZE) as u32;
+        jmp[1..].copy_from_slice(&operand.to_le_bytes());
+
+        unsafe { write_code(patch_target, &jmp) };
+
+        if hook.patch_above {
+            let short_jmp = [0xEB, (0 - (JMP_REL_SHORT_SIZE + JMP_REL_SIZE) as i8) as u8];
+            unsafe { write_code(hook.target, &short_jmp) };
+        }
+    } else if hook.patch_above {
+        // Restore the entry point before the jump above it, which is not reachable anymore afterwards.
+        unsafe {
+            write_code(hook.target, &hook.backup[JMP_REL_SIZE..patch_size]);
+            write_code(patch_target, &hook.backup[..JMP_REL_SIZE]);
+        }
+    } else {
+        unsafe { write_code(patch_target, &hook.backup[..patch_size]) };
+    }
+
+    drop(guard);
+
+    // Just-in-case measure.
+    os::flush_instruction_cache(patch_target, patch_size);
+
+    hook.is_enabled = enable;
+    hook.queue_enable = enable;
+
+    MH_STATUS::MH_OK
+}
+
+/// Returns `true` if [`write_code`] can replace `len` bytes at `address` without other threads ever executing a
+/// partially written instruction.
+fn can_write_code(address: usize, len: usize) -> bool {
+    let head_len = 8 - address % 8;
+    len <= head_len || head_len >= 2 || can_store_code_wide(address, len)
+}
+
+/// Writes `code` over executable memory that other threads may be running, which must have been checked with
+/// [`can_write_code`].
+///
+/// Each aligned quadword is replaced with a single atomic store. If the code spans several quadwords, threads
+/// entering at `address` are parked on a `JMP $` while the tail is written, and the head is published last. If the
+/// head is a single byte, which is too short for the `JMP $`, the code is written with a single 16-byte store instead.
+unsafe fn write_code(address: usize, code: &[u8]) {
+    let head_len = (8 - address % 8).min(code.len());
+    let (head, tail) = code.split_at(head_len);
+
+    if !tail.is_empty() && head_len == 1 {
+        debug_assert!(can_store_code_wide(address, code.len()));
+        unsafe { store_code_wide(address, code) };
+        return;
+    }
+
+    if !tail.is_empty() {
+        unsafe { store_code(address, &[0xEB, 0xFE]) };
+    }
+
+    for (index, chunk) in tail.chunks(8).enumerate() {
+        unsafe { store_code(address + head_len + index * 8, chunk) };
+    }
+
+    unsafe { store_code(address, head) };
+}
+
+/// Replaces bytes within a single aligned quadword.
+unsafe fn store_code(address: usize, code: &[u8]) {
+    let offset = address % 8;
+    let quadword = unsafe { &*((address - offset) as *const AtomicU64) };
+
+    let mut value = quadword.load(Ordering::Acquire).to_le_bytes();
+    value[offset..offset + code.len()].copy_from_slice(code);
+    quadword.store(u64::from_le_bytes(value), Ordering::Release);
+}
+
+/// Returns `true` if `len` bytes at `address` lie within an aligned 16-byte block that can be replaced atomically.
+fn can_store_code_wide(address: usize, len: usize) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        address % 16 + len <= 16 && std::arch::is_x86_feature_detected!("cmpxchg16b")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (address, len);
+        false
+    }
+}
+
+/// Replaces bytes within a single aligned 16-byte block with `CMPXCHG16B`.
+#[cfg(target_arch = "x86_64")]
+unsafe fn store_code_wide(address: usize, code: &[u8]) {
+    let offset = address % 16;
+    let block = address - offset;
+
+    let (mut low, mut high) = unsafe {
+        (
+            (block as *const u64).read(),
+            (block as *const u64).add(1).read(),
+        )
+    };
+    loop {
+        let mut value = [0u8; 16];
+        value[..8].copy_from_slice(&low.to_le_bytes());
+        value[8..].copy_from_slice(&high.to_le_bytes());
+        value[offset..offset + code.len()].copy_from_slice(code);
+        let new_low = u64::from_le_bytes(value[..8].try_into().unwrap());
+        let new_high = u64::from_le_bytes(value[8..].try_into().unwrap());
+
+        let (expected_low, expected_high) = (low, high);
+        // RBX is reserved by LLVM, so the low half of the new value is swapped into it around the instruction.
+        unsafe {
+            std::arch::asm!(
+                "xchg {new_low}, rbx",
+                "lock cmpxchg16b xmmword ptr [{block}]",
+                "mov rbx, {new_low}",
+                block = in(reg) block,
+                new_low = inout(reg) new_low => _,
+                inout("rax") low,
+                inout("rdx") high,
+                in("rcx") new_high,
+                options(nostack),
+            );
+        }
+
+        // On failure, RDX:RAX holds the current value of the block.
+        if (low, high) == (expected_low, expected_high) {
+            return;
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn store_code_wide(_address: usize, _code: &[u8]) {
+    unreachable!("16-byte stores are only used on x86-64");
+}
+
+fn create_trampoline(
+    engine: &mut Engine,
+    target: usize,
+    detour: usize,
+) -> Result<HookEntry, MH_STATUS> {
+    let function = trampoline::Target::read(target).ok_or(MH_STATUS::MH_ERROR_NOT_EXECUTABLE)?;
+
+    let slot = engine
+        .buffer
+        .allocate(target)
+        .ok_or(MH_STATUS::MH_ERROR_MEMORY_ALLOC)?;
+
+    let ct = trampoline::create(&function, slot, detour, Mode::NATIVE);
+
+    let ct = match ct {
+        Ok(ct) => ct,
+        Err(_) => {
+            engine.buffer.free(slot);
+            return Err(MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION);
+        }
+    };
+
+    // Other threads must never run a partially written jump.
+    let writable = if ct.patch_above {
+        can_write_code(target - JMP_REL_SIZE, JMP_REL_SIZE)
+            && can_write_code(target, JMP_REL_SHORT_SIZE)
+    } else {
+        can_write_code(target, JMP_REL_SIZE)
+    };
+    if !writable {
+        engine.buffer.free(slot);
+        return Err(MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION);
+    }
+
+    unsafe { std::ptr::copy_nonoverlapping(ct.code.as_ptr(), slot as *mut u8, ct.code.len()) };
+
+    // Back up the target function.
+    let mut backup = [0u8; 8];
+    if ct.patch_above {
+        let above = function
+            .above
+            .expect("the long jump is only placed above executable padding");
+        backup[..JMP_REL_SIZE].copy_from_slice(&above);
+        backup[JMP_REL_SIZE..JMP_REL_SIZE + JMP_REL_SHORT_SIZE]
+            .copy_from_slice(&function.code[..JMP_REL_SHORT_SIZE]);
+    } else {
+        backup[..JMP_REL_SIZE].copy_from_slice(&function.code[..JMP_REL_SIZE]);
+    }
+
+    Ok(HookEntry {
+        target,
+        detour: ct.relay.map_or(detour, |relay| slot + relay),
+        trampoline: slot,
+        backup,
+        patch_above: ct.patch_above,
+        is_enabled: false,
+        queue_enable: false,
+        ips: ct.ips,
+    })
+}
+
+/// Initializes the engine.
+pub unsafe fn MH_Initialize() -> MH_STATUS {
+    let mut engine = lock();
+    if engine.is_some() {
+        return MH_STATUS::MH_ERROR_ALREADY_INITIALIZED;
+    }
+
+    *engine = Some(Engine {
+        hooks: Vec::new(),
+        buffer: Buffer::default(),
+    });
+
+    MH_STATUS::MH_OK
+}
+
+/// Disables all hooks and releases the engine.
+pub unsafe fn MH_Uninitialize() -> MH_STATUS {
+    let mut engine = lock();
+    let Some(state) = engine.as_mut() else {
+        return MH_STATUS::MH_ERROR_NOT_INITIALIZED;
+    };
+
+    let status = state.enable_all_hooks_ll(false);
+    if status == MH_STATUS::MH_OK {
+        // Dropping the engine frees the memory blocks of all trampolines.
+        *engine = None;
+    }
+
+    status
+}
+
+/// Creates a hook for the specified target function, in disabled state.
+pub unsafe fn MH_CreateHook(
+    pTarget: *mut c_void,
+    pDetour: *mut c_void,
+    ppOriginal: *mut *mut c_void,
+) -> MH_STATUS {
+    with_engine(|engine| {
+        let (target, detour) = (pTarget as usize, pDetour as usize);
+
+        if !os::is_executable(target) || !os::is_executable(detour) {
+            return MH_STATUS::MH_ERROR_NOT_EXECUTABLE;
+        }
+
+        if engine.find_hook_entry(target).is_some() {
+            return MH_STATUS::MH_ERROR_ALREADY_CREATED;
+        }
+
+        match create_trampoline(engine, target, detour) {
+            Ok(hook) => {
+                if !ppOriginal.is_null() {
+                    unsafe { *ppOriginal = hook.trampoline as *mut c_void };
+                }
+                engine.hooks.push(hook);
+                MH_STATUS::MH_OK
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+/// Creates a hook for the specified API function, in disabled state, and returns the target function.
+pub unsafe fn MH_CreateHookApiEx(
+    pszModule: *const u8,
+    pszProcName: *const u8,
+    pDetour: *mut c_void,
+    ppOriginal: *mut *mut c_void,
+    ppTarget: *mut *mut c_void,
+) -> MH_STATUS {
+    // The module name is a NUL-terminated UTF-16 string, like the one passed to GetModuleHandleW.
+    let module = unsafe {
+        let module = pszModule as *const u16;
+        let len = (0..).take_while(|&i| *module.add(i) != 0).count();
+        String::from_utf16_lossy(slice::from_raw_parts(module, len))
+    };
+    let proc_name = unsafe { CStr::from_ptr(pszProcName.cast()) };
+
+    let target = match os::find_symbol(&module, proc_name) {
+        Ok(target) => target as *mut c_void,
+        Err(status) => return status,
+    };
+
+    if !ppTarget.is_null() {
+        unsafe { *ppTarget = target };
+    }
+
+    unsafe { MH_CreateHook(target, pDetour, ppOriginal) }
+}
+
+/// Removes an already created hook.
+pub unsafe fn MH_RemoveHook(pTarget: *mut c_void) -> MH_STATUS {
+    with_engine(|engine| {
+        let Some(pos) = engine.find_hook_entry(pTarget as usize) else {
+            return MH_STATUS::MH_ERROR_NOT_CREATED;
+        };
+
+        if engine.hooks[pos].is_enabled {
+            let _threads = engine.freeze(Some(pos), Action::Disable);
+            let status = enable_hook_ll(&mut engine.hooks[pos], false);
+            if status != MH_STATUS::MH_OK {
+                return status;
+            }
+        }
+
+        let hook = engine.hooks.remove(pos);
+        engine.buffer.free(hook.trampoline);
+
+        MH_STATUS::MH_OK
+    })
+}
+
+fn enable_hook(target: *mut c_void, enable: bool) -> MH_STATUS {
+    with_engine(|engine| {
+        if target.is_null() {
+            return engine.enable_all_hooks_ll(enable);
+        }
+
+        let Some(pos) = engine.find_hook_entry(target as usize) else {
+            return MH_STATUS::MH_ERROR_NOT_CREATED;
+        };
+
+        if engine.hooks[pos].is_enabled == enable {
+            return if enable {
+                MH_STATUS::MH_ERROR_ENABLED
+            } else {
+                MH_STATUS::MH_ERROR_DISABLED
+            };
+        }
+
+        let action = if enable {
+            Action::Enable
+        } else {
+            Action::Disable
+        };
+        let _threads = engine.freeze(Some(pos), action);
+        enable_hook_ll(&mut engine.hooks[pos], enable)
+    })
+}
+
+/// Enables an already created hook.
+pub unsafe fn MH_EnableHook(pTarget: *mut c_void) -> MH_STATUS {
+    enable_hook(pTarget, true)
+}
+
+/// Disables an already created hook.
+pub unsafe fn MH_DisableHook(pTarget: *mut c_void) -> MH_STATUS {
+    enable_hook(pTarget, false)
+}
+
+fn queue_hook(target: *mut c_void, queue_enable: bool) -> MH_STATUS {
+    with_engine(|engine| {
+        if target.is_null() {
+            for hook in &mut engine.hooks {
+                hook.queue_enable = queue_enable;
+            }
+            return MH_STATUS::MH_OK;
+        }
+
+        match engine.find_hook_entry(target as usize) {
+            Some(pos) => {
+                engine.hooks[pos].queue_enable = queue_enable;
+                MH_STATUS::MH_OK
+            }
+            None => MH_STATUS::MH_ERROR_NOT_CREATED,
+        }
+    })
+}
+
+/// Queues to enable an already created hook.
+pub unsafe fn MH_QueueEnableHook(pTarget: *mut c_void) -> MH_STATUS {
+    queue_hook(pTarget, true)
+}
+
+/// Queues to disable an already created hook.
+pub unsafe fn MH_QueueDisableHook(pTarget: *mut c_void) -> MH_STATUS {
+    queue_hook(pTarget, false)
+}
+
+/// Applies all queued changes in one go.
+pub unsafe fn MH_ApplyQueued() -> MH_STATUS {
+    with_engine(|engine| {
+        if engine
+            .hooks
+            .iter()
+            .all(|hook| hook.is_enabled == hook.queue_enable)
+        {
+            return MH_STATUS::MH_OK;
+        }
+
+        let _threads = engine.freeze(None, Action::ApplyQueued);
+
+        for hook in &mut engine.hooks {
+            if hook.is_enabled != hook.queue_enable {
+                let enable = hook.queue_enable;
+                let status = enable_hook_ll(hook, enable);
+                if status != MH_STATUS::MH_OK {
+                    return status;
+                }
+            }
+        }
+
+        MH_STATUS::MH_OK
+    })
+}
@@ -0,0 +1,230 @@
+//! Hooks that call closures when a function is entered and when it returns, without replacing the function.
+//!
+//! The function is detoured to a thunk of the mid-function hook entry, which calls the enter closure with the
+//! registers at the first instruction. It then replaces the return address on the stack with a second thunk and
+//! resumes at the trampoline, so the function runs unchanged and returns into the second thunk. That one calls the
+//! leave closure with the registers after the return and resumes at the original return address.
+//!
+//! The replaced return addresses are kept on a stack per thread, so recursive and nested calls return in order. The
+//! stack has a fixed size and is never borrowed across a call, so the hooked function can be called while pushing,
+//! from the enter closure or an allocator, without reallocating or borrowing it twice.
+
+use crate::{
+    Context, ErrorKind, Hook, HookError, HookGuard, HookTarget, MH_STATUS, Operation,
+    mid::{self, MidState},
+    thunk::{BoundThunk, ThunkHook},
+};
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    fmt, process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The number of calls to hooked functions a thread can be in at once. Deeper calls run without the leave closure.
+const MAX_DEPTH: usize = 256;
+
+/// The return addresses replaced on a thread, innermost call last.
+struct Returns {
+    addresses: [Cell<usize>; MAX_DEPTH],
+    len: Cell<usize>,
+}
+
+impl Returns {
+    /// Returns `false` if the stack is full.
+    fn push(&self, address: usize) -> bool {
+        let len = self.len.get();
+        let Some(slot) = self.addresses.get(len) else {
+            return false;
+        };
+
+        slot.set(address);
+        self.len.set(len + 1);
+        true
+    }
+
+    fn pop(&self) -> Option<usize> {
+        let len = self.len.get().checked_sub(1)?;
+        self.len.set(len);
+        Some(self.addresses[len].get())
+    }
+}
+
+thread_local! {
+    static RETURNS: Returns = const {
+        Returns {
+            addresses: [const { Cell::new(0) }; MAX_DEPTH],
+            len: Cell::new(0),
+        }
+    };
+}
+
+/// A hook that calls a closure when a function is entered and another when it returns, around the original function.
+///
+/// The enter closure receives the registers at the first instruction of the function, so it can read and change the
+/// arguments passed in registers, and those on the stack above the return address. The leave closure receives the
+/// registers after the function returned, with the return value in `rax` on x64 and in `eax`, and `edx` for 64-bit
+/// values, on x86. Changing it replaces the value seen by the caller. Floating point return values are not part of
+/// the [`Context`].
+///
+/// The return address of the function is replaced while it runs, so backtraces through it end at the hook, and the
+/// function must not be unwound through or left with `longjmp`. The enter closure must not move the stack pointer.
+/// A thread can be in 256 calls to hooked functions at once, and deeper calls run without the leave closure.
+///
+/// Dropping it removes the hook and releases both closures, which is why no call may be in progress then.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{EnterLeaveHook, HookError};
+///
+/// # #[cfg(target_arch = "x86_64")]
+/// fn main() -> Result<(), HookError> {
+///     let hook = unsafe {
+///         EnterLeaveHook::new(
+///             square as *mut _,
+///             Box::new(|context| println!("square({})", context.rdi as i32)),
+///             Box::new(|context| context.rax += 1),
+///         )?
+///     };
+///     unsafe { hook.enable()? };
+///
+///     assert_eq!(square(3), 10);
+///
+///     Ok(())
+/// }
+/// # #[cfg(not(target_arch = "x86_64"))]
+/// # fn main() {}
+///
+/// #[inline(never)]
+/// extern "sysv64" fn square(x: i32) -> i32 {
+///     std::hint::black_box(x) * std::hint::black_box(x)
+/// }
+/// ```
+pub struct EnterLeaveHook {
+    /// The thunks of the enter and the leave closure.
+    hook: ThunkHook<*mut c_void, (BoundThunk<MidState>, BoundThunk<MidState>)>,
+}
+
+impl EnterLeaveHook {
+    /// Creates a hook on `target` that calls `on_enter` when the function is entered and `on_leave` when it returns.
+    /// The hook is created disabled.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`](crate::MinHook::create_hook). The hook must not be dropped while a call to the
+    /// function is in progress on any thread, because the call returns into the thunk of the leave closure.
+    pub unsafe fn new(
+        target: *mut c_void,
+        on_enter: Box<dyn Fn(&mut Context) + Send + Sync>,
+        on_leave: Box<dyn Fn(&mut Context) + Send + Sync>,
+    ) -> Result<Self, HookError> {
+        let alloc_error = || {
+            HookError::new(
+                Operation::CreateHook,
+                Some(HookTarget::Address(target as usize)),
+                ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_ALLOC),
+            )
+        };
+
+        let leave = MidState {
+            callback: Box::new(move |context, _| {
+                on_leave(context);
+                // Unwinding out of the thunk is undefined, and there is no address left to return to.
+                RETURNS
+                    .try_with(Returns::pop)
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(|| {
+                        eprintln!("minhook: a function hooked by an EnterLeaveHook returned without entering");
+                        process::abort()
+                    })
+            }),
+            resume: AtomicUsize::new(0),
+        };
+        let leave_thunk = BoundThunk::new(leave, mid::mid_thunk).ok_or_else(alloc_error)?;
+        let leave_address = leave_thunk.address();
+
+        let enter = MidState {
+            callback: Box::new(move |context, trampoline| {
+                on_enter(context);
+
+                #[cfg(target_arch = "x86_64")]
+                let sp = context.rsp as *mut usize;
+                #[cfg(target_arch = "x86")]
+                let sp = context.esp as *mut usize;
+
+                // Without room on the stack of return addresses, the function runs without the leave closure.
+                let pushed = RETURNS
+                    .try_with(|returns| returns.push(unsafe { sp.read() }))
+                    .unwrap_or(false);
+                if pushed {
+                    unsafe { sp.write(leave_address) };
+                }
+                trampoline
+            }),
+            resume: AtomicUsize::new(0),
+        };
+        let enter_thunk = BoundThunk::new(enter, mid::mid_thunk).ok_or_else(alloc_error)?;
+
+        let guard = unsafe { HookGuard::new(target, enter_thunk.address() as *mut c_void)? };
+        enter_thunk
+            .state()
+            .resume
+            .store(guard.original() as usize, Ordering::Release);
+
+        Ok(Self {
+            hook: ThunkHook::new(guard, (enter_thunk, leave_thunk)),
+        })
+    }
+
+    /// Returns the hooked function.
+    pub fn target(&self) -> *mut c_void {
+        self.hook.guard().target()
+    }
+
+    /// Returns the trampoline, which calls the original function without the closures.
+    pub fn original(&self) -> *mut c_void {
+        self.hook.guard().original()
+    }
+
+    /// Enables the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::enable_hook`](crate::MinHook::enable_hook).
+    pub unsafe fn enable(&self) -> Result<(), HookError> {
+        unsafe { self.hook.guard().enable() }
+    }
+
+    /// Disables the hook. Calls already in progress still run the leave closure when they return.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::disable_hook`](crate::MinHook::disable_hook).
+    pub unsafe fn disable(&self) -> Result<(), HookError> {
+        unsafe { self.hook.guard().disable() }
+    }
+
+    /// Releases the hook without removing it. The closures and their thunks stay alive for the rest of the process.
+    pub fn leak(self) -> Hook<*mut c_void> {
+        self.hook.leak()
+    }
+}
+
+impl fmt::Debug for EnterLeaveHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EnterLeaveHook")
+            .field("target", &self.target())
+            .field("original", &self.original())
+            .field(
+                "enter_thunk",
+                &(self.hook.thunks().0.address() as *const c_void),
+            )
+            .field(
+                "leave_thunk",
+                &(self.hook.thunks().1.address() as *const c_void),
+            )
+            .finish()
+    }
+}
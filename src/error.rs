@@ -0,0 +1,226 @@
+//! The error type returned by the hooking API.
+
+use crate::MH_STATUS;
+use std::fmt;
+
+/// An error returned by a hooking operation, together with the operation and the target it was applied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookError {
+    operation: Option<Operation>,
+    target: Option<HookTarget>,
+    kind: ErrorKind,
+}
+
+/// The operation that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    Initialize,
+    Uninitialize,
+    CreateHook,
+    CreateHookApi,
+    EnableHook,
+    DisableHook,
+    RemoveHook,
+    QueueEnableHook,
+    QueueDisableHook,
+    ApplyQueued,
+    Analyze,
+    InspectTrampoline,
+    CreateImportHook,
+    RemoveImportHook,
+    CreateVTableHook,
+    RemoveVTableHook,
+    CreateDeferredHook,
+    FindSignature,
+}
+
+/// The function an operation was applied to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HookTarget {
+    /// All created hooks.
+    All,
+    /// A function at an address.
+    Address(usize),
+    /// A function of a module by name, exported by the module or imported by it for import hooks.
+    Symbol { module: String, symbol: String },
+    /// The code of a module searched for a byte pattern.
+    Signature { module: String, pattern: String },
+}
+
+/// The reason an operation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The hooking engine returned an error status.
+    Status(MH_STATUS),
+    /// The hooking engine returned a status code that is not a known [`MH_STATUS`].
+    Unrecognized(i32),
+    /// A name contains a NUL byte at the given position.
+    InteriorNul {
+        argument: &'static str,
+        position: usize,
+    },
+    /// A pointer argument is null.
+    NullPointer { argument: &'static str },
+    /// A byte pattern is empty or has an invalid byte at the given position.
+    InvalidPattern { position: usize },
+}
+
+impl HookError {
+    pub(crate) fn new(operation: Operation, target: Option<HookTarget>, kind: ErrorKind) -> Self {
+        Self {
+            operation: Some(operation),
+            target,
+            kind,
+        }
+    }
+
+    /// Returns the operation that failed, if known.
+    pub fn operation(&self) -> Option<Operation> {
+        self.operation
+    }
+
+    /// Returns the function the operation was applied to, if known.
+    pub fn target(&self) -> Option<&HookTarget> {
+        self.target.as_ref()
+    }
+
+    /// Returns the reason the operation failed.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the status code that best describes the error. Invalid input is reported with the status MinHook
+    /// returns for the same input, or would have returned if the input could be passed to it. Unrecognized status codes
+    /// are reported as `MH_UNKNOWN`.
+    pub fn status(&self) -> MH_STATUS {
+        match self.kind {
+            ErrorKind::Status(status) => status,
+            ErrorKind::InteriorNul {
+                argument: "module_name",
+                ..
+            } => MH_STATUS::MH_ERROR_MODULE_NOT_FOUND,
+            ErrorKind::InteriorNul { .. } | ErrorKind::InvalidPattern { .. } => {
+                MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND
+            }
+            ErrorKind::NullPointer { .. } => MH_STATUS::MH_ERROR_NOT_EXECUTABLE,
+            ErrorKind::Unrecognized(_) => MH_STATUS::MH_UNKNOWN,
+        }
+    }
+}
+
+impl From<ErrorKind> for HookError {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            operation: None,
+            target: None,
+            kind,
+        }
+    }
+}
+
+impl From<MH_STATUS> for HookError {
+    fn from(status: MH_STATUS) -> Self {
+        ErrorKind::Status(status).into()
+    }
+}
+
+impl From<HookError> for MH_STATUS {
+    fn from(error: HookError) -> Self {
+        error.status()
+    }
+}
+
+impl PartialEq<MH_STATUS> for HookError {
+    fn eq(&self, status: &MH_STATUS) -> bool {
+        self.kind == ErrorKind::Status(*status)
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operation = match self {
+            Operation::Initialize => "initialize MinHook",
+            Operation::Uninitialize => "uninitialize MinHook",
+            Operation::CreateHook | Operation::CreateHookApi => "create hook",
+            Operation::EnableHook => "enable hook",
+            Operation::DisableHook => "disable hook",
+            Operation::RemoveHook => "remove hook",
+            Operation::QueueEnableHook => "queue enabling hook",
+            Operation::QueueDisableHook => "queue disabling hook",
+            Operation::ApplyQueued => "apply queued hooks",
+            Operation::Analyze => "analyze function",
+            Operation::InspectTrampoline => "inspect trampoline",
+            Operation::CreateImportHook => "create import hook",
+            Operation::RemoveImportHook => "remove import hook",
+            Operation::CreateVTableHook => "create vtable hook",
+            Operation::RemoveVTableHook => "remove vtable hook",
+            Operation::CreateDeferredHook => "create deferred hook",
+            Operation::FindSignature => "find signature",
+        };
+
+        write!(f, "{operation}")
+    }
+}
+
+impl fmt::Display for HookTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HookTarget::All => write!(f, "all hooks"),
+            HookTarget::Address(address) => write!(f, "{address:#x}"),
+            HookTarget::Symbol { module, symbol } => write!(f, "{module}!{symbol}"),
+            HookTarget::Signature { module, pattern } if module.is_empty() => {
+                write!(f, "\"{pattern}\" in the main program")
+            }
+            HookTarget::Signature { module, pattern } => write!(f, "\"{pattern}\" in {module}"),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Status(status) => write!(f, "{status}"),
+            ErrorKind::InteriorNul { argument, position } => {
+                write!(
+                    f,
+                    "`{argument}` contains a NUL byte at position {position}."
+                )
+            }
+            ErrorKind::NullPointer { argument } => write!(f, "`{argument}` is a null pointer."),
+            ErrorKind::InvalidPattern { position } => {
+                write!(f, "The pattern is invalid at position {position}.")
+            }
+            ErrorKind::Unrecognized(code) => write!(f, "Unrecognized status code {code}."),
+        }
+    }
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(operation) = self.operation else {
+            return write!(f, "{}", self.kind);
+        };
+
+        match (operation, &self.target) {
+            (_, None) => write!(f, "Failed to {operation}: {}", self.kind),
+            (Operation::EnableHook, Some(HookTarget::All)) => {
+                write!(f, "Failed to enable all hooks: {}", self.kind)
+            }
+            (Operation::DisableHook, Some(HookTarget::All)) => {
+                write!(f, "Failed to disable all hooks: {}", self.kind)
+            }
+            (Operation::QueueEnableHook, Some(HookTarget::All)) => {
+                write!(f, "Failed to queue enabling all hooks: {}", self.kind)
+            }
+            (Operation::QueueDisableHook, Some(HookTarget::All)) => {
+                write!(f, "Failed to queue disabling all hooks: {}", self.kind)
+            }
+            (_, Some(target)) => write!(f, "Failed to {operation} for {target}: {}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
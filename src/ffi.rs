@@ -1,14 +1,24 @@
-use crate::MH_STATUS;
+#[cfg(not(rust_engine))]
 use std::ffi::c_void;
 
+// The Rust port of the engine replaces the bundled C library on Linux and with the `pure-rust` feature.
+#[cfg(rust_engine)]
+pub use crate::engine::{
+    MH_ApplyQueued, MH_CreateHook, MH_CreateHookApiEx, MH_DisableHook, MH_EnableHook,
+    MH_Initialize, MH_QueueDisableHook, MH_QueueEnableHook, MH_RemoveHook, MH_Uninitialize,
+};
+
+// The C functions return the raw status code, which is checked with `MH_STATUS::try_from` before use, since a value
+// that is not a variant of `MH_STATUS` cannot be returned as one.
+#[cfg(not(rust_engine))]
 unsafe extern "system" {
     /// Initializes the MinHook library. You must call this function in the
     /// beginning of your program.
-    pub fn MH_Initialize() -> MH_STATUS;
+    pub fn MH_Initialize() -> i32;
 
     /// Uninitialize the MinHook library. You must call this function EXACTLY
     /// ONCE at the end of your program.
-    pub fn MH_Uninitialize() -> MH_STATUS;
+    pub fn MH_Uninitialize() -> i32;
 
     /// Creates a hook for the specified target function, in disabled state.
     ///
@@ -21,22 +31,7 @@ unsafe extern "system" {
         pTarget: *mut c_void,
         pDetour: *mut c_void,
         ppOriginal: *mut *mut c_void,
-    ) -> MH_STATUS;
-
-    /// Creates a hook for the specified API function, in disabled state.
-    ///
-    /// # Arguments
-    ///
-    /// * `pszModule` \[in\] - A pointer to the loaded module name which contains the target function.
-    /// * `pszProcName` \[in\] - A pointer to the target function name, which will be overridden by the detour function.
-    /// * `pDetour` \[in\] - A pointer to the detour function, which will override the target function.
-    /// * `ppOriginal` \[out\] - A pointer to the trampoline function, which will be used to call the original target function. This parameter can be NULL.
-    pub fn MH_CreateHookApi(
-        pszModule: *const u8,
-        pszProcName: *const u8,
-        pDetour: *mut c_void,
-        ppOriginal: *mut *mut c_void,
-    ) -> MH_STATUS;
+    ) -> i32;
 
     /// Creates a hook for the specified API function, in disabled state.
     ///
@@ -53,43 +48,43 @@ unsafe extern "system" {
         pDetour: *mut c_void,
         ppOriginal: *mut *mut c_void,
         ppTarget: *mut *mut c_void,
-    ) -> MH_STATUS;
+    ) -> i32;
 
     /// Removes an already created hook.
     ///
     /// # Arguments
     ///
     /// * `pTarget` \[in\] - A pointer to the target function.
-    pub fn MH_RemoveHook(pTarget: *mut c_void) -> MH_STATUS;
+    pub fn MH_RemoveHook(pTarget: *mut c_void) -> i32;
 
     /// Enables an already created hook.
     ///
     /// # Arguments
     ///
     /// * `pTarget` \[in\] - A pointer to the target function.
-    pub fn MH_EnableHook(pTarget: *mut c_void) -> MH_STATUS;
+    pub fn MH_EnableHook(pTarget: *mut c_void) -> i32;
 
     /// Disables an already created hook.
     ///
     /// # Arguments
     ///
     /// * `pTarget` \[in\] - A pointer to the target function.
-    pub fn MH_DisableHook(pTarget: *mut c_void) -> MH_STATUS;
+    pub fn MH_DisableHook(pTarget: *mut c_void) -> i32;
 
     /// Queues to enable an already created hook.
     ///
     /// # Arguments
     ///
     /// * `pTarget` \[in\] - A pointer to the target function.
-    pub fn MH_QueueEnableHook(pTarget: *mut c_void) -> MH_STATUS;
+    pub fn MH_QueueEnableHook(pTarget: *mut c_void) -> i32;
 
     /// Queues to disable an already created hook.
     ///
     /// # Arguments
     ///
     /// * `pTarget` \[in\] - A pointer to the target function.
-    pub fn MH_QueueDisableHook(pTarget: *mut c_void) -> MH_STATUS;
+    pub fn MH_QueueDisableHook(pTarget: *mut c_void) -> i32;
 
     /// Applies all queued changes in one go.
-    pub fn MH_ApplyQueued() -> MH_STATUS;
+    pub fn MH_ApplyQueued() -> i32;
 }
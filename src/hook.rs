@@ -0,0 +1,303 @@
+//! Hooks that are typed over the function pointer type of the target function.
+
+use crate::{HookError, MinHook, TrampolineInfo};
+use std::{ffi::c_void, fmt, mem::ManuallyDrop, ops::Deref};
+use tracing::error;
+
+/// A function pointer type that can be hooked.
+///
+/// This trait is implemented for `fn` pointers with up to 12 arguments in the `Rust`, `C` and `system` ABIs (and
+/// `stdcall`, `fastcall` and `thiscall` on x86), both safe and `unsafe`, as well as for `*mut c_void` to keep a hook
+/// untyped. Function pointers with higher-ranked lifetimes, such as `fn(&str)`, are not covered.
+///
+/// # Safety
+///
+/// Implementors must be pointer-sized, and `from_ptr(to_ptr(f))` must return `f`.
+pub unsafe trait FnPtr: Copy + 'static {
+    /// Returns the address of the function.
+    fn to_ptr(self) -> *mut c_void;
+
+    /// Creates a function pointer from an address.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a function with the signature and calling convention of `Self`.
+    unsafe fn from_ptr(ptr: *mut c_void) -> Self;
+}
+
+unsafe impl FnPtr for *mut c_void {
+    fn to_ptr(self) -> *mut c_void {
+        self
+    }
+
+    unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+        ptr
+    }
+}
+
+macro_rules! impl_fn_ptr {
+    ($abi:literal; $($arg:ident),*) => {
+        unsafe impl<Ret: 'static, $($arg: 'static),*> FnPtr for extern $abi fn($($arg),*) -> Ret {
+            fn to_ptr(self) -> *mut c_void {
+                self as *mut c_void
+            }
+
+            unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+                unsafe { std::mem::transmute::<*mut c_void, Self>(ptr) }
+            }
+        }
+
+        unsafe impl<Ret: 'static, $($arg: 'static),*> FnPtr for unsafe extern $abi fn($($arg),*) -> Ret {
+            fn to_ptr(self) -> *mut c_void {
+                self as *mut c_void
+            }
+
+            unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+                unsafe { std::mem::transmute::<*mut c_void, Self>(ptr) }
+            }
+        }
+    };
+}
+
+macro_rules! impl_fn_ptrs {
+    ($($arg:ident),*) => {
+        impl_fn_ptr!("Rust"; $($arg),*);
+        impl_fn_ptr!("C"; $($arg),*);
+        impl_fn_ptr!("system"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_fn_ptr!("stdcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_fn_ptr!("fastcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_fn_ptr!("thiscall"; $($arg),*);
+    };
+}
+
+impl_fn_ptrs!();
+impl_fn_ptrs!(A);
+impl_fn_ptrs!(A, B);
+impl_fn_ptrs!(A, B, C);
+impl_fn_ptrs!(A, B, C, D);
+impl_fn_ptrs!(A, B, C, D, E);
+impl_fn_ptrs!(A, B, C, D, E, F);
+impl_fn_ptrs!(A, B, C, D, E, F, G);
+impl_fn_ptrs!(A, B, C, D, E, F, G, H);
+impl_fn_ptrs!(A, B, C, D, E, F, G, H, I);
+impl_fn_ptrs!(A, B, C, D, E, F, G, H, I, J);
+impl_fn_ptrs!(A, B, C, D, E, F, G, H, I, J, K);
+impl_fn_ptrs!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// A hook from a target function to a detour function of the same type `F`.
+///
+/// Because the target and the detour share a single type, mismatched signatures are rejected at compile time, and the
+/// original function is returned with the correct type.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{Hook, HookError};
+///
+/// fn main() -> Result<(), HookError> {
+///     let hook = unsafe { Hook::<fn(i32) -> i32>::new(double, triple)? };
+///     unsafe { hook.enable()? };
+///
+///     assert_eq!(double(2), 6);
+///     assert_eq!(hook.original()(2), 4);
+///
+///     unsafe { hook.remove() }
+/// }
+///
+/// fn double(x: i32) -> i32 {
+///     x * 2
+/// }
+///
+/// fn triple(x: i32) -> i32 {
+///     x * 3
+/// }
+/// ```
+pub struct Hook<F: FnPtr> {
+    target: F,
+    detour: F,
+    original: F,
+}
+
+impl<F: FnPtr> Hook<F> {
+    /// Creates a hook for the target function and detours it to the detour function. The hook is created disabled.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`].
+    pub unsafe fn new(target: F, detour: F) -> Result<Self, HookError> {
+        let original = unsafe { MinHook::create_hook(target.to_ptr(), detour.to_ptr())? };
+
+        Ok(Self {
+            target,
+            detour,
+            original: unsafe { F::from_ptr(original) },
+        })
+    }
+
+    /// Creates a hook for an exported function of a loaded module and detours it to the detour function. The hook is
+    /// created disabled.
+    ///
+    /// # Safety
+    ///
+    /// The exported function must have the signature and calling convention of `F`. See also
+    /// [`MinHook::create_hook_api_ex`].
+    pub unsafe fn with_api<T: AsRef<str>>(
+        module_name: T,
+        proc_name: T,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        let (original, target) =
+            unsafe { MinHook::create_hook_api_ex(module_name, proc_name, detour.to_ptr())? };
+
+        Ok(Self {
+            target: unsafe { F::from_ptr(target) },
+            detour,
+            original: unsafe { F::from_ptr(original) },
+        })
+    }
+
+    /// Returns the hooked function.
+    pub fn target(&self) -> F {
+        self.target
+    }
+
+    /// Returns the detour function.
+    pub fn detour(&self) -> F {
+        self.detour
+    }
+
+    /// Returns the trampoline that calls the original, unhooked target function.
+    pub fn original(&self) -> F {
+        self.original
+    }
+
+    /// Returns the code of the trampoline. See [`MinHook::inspect_trampoline`].
+    pub fn inspect_trampoline(&self) -> Result<TrampolineInfo, HookError> {
+        MinHook::inspect_trampoline(self.target.to_ptr())
+    }
+
+    /// Enables the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::enable_hook`].
+    pub unsafe fn enable(&self) -> Result<(), HookError> {
+        unsafe { MinHook::enable_hook(self.target.to_ptr()) }
+    }
+
+    /// Disables the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::disable_hook`].
+    pub unsafe fn disable(&self) -> Result<(), HookError> {
+        unsafe { MinHook::disable_hook(self.target.to_ptr()) }
+    }
+
+    /// Removes the hook. The function returned by [`Hook::original`] must not be called afterwards.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::remove_hook`].
+    pub unsafe fn remove(self) -> Result<(), HookError> {
+        unsafe { MinHook::remove_hook(self.target.to_ptr()) }
+    }
+
+    /// Wraps the hook in a [`HookGuard`] that removes it when dropped.
+    pub fn into_guard(self) -> HookGuard<F> {
+        HookGuard {
+            hook: ManuallyDrop::new(self),
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hook")
+            .field("target", &self.target.to_ptr())
+            .field("detour", &self.detour.to_ptr())
+            .field("original", &self.original.to_ptr())
+            .finish()
+    }
+}
+
+/// A hook that is disabled and removed when the guard is dropped, including during unwinding.
+///
+/// Errors while removing the hook cannot be returned from `Drop`, so they are reported through `tracing` instead. The
+/// methods of [`Hook`] are available through `Deref`.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, MinHook};
+///
+/// fn main() -> Result<(), HookError> {
+///     {
+///         let guard = unsafe { MinHook::create_hook_guard(return_0 as _, return_1 as _)? };
+///         unsafe { guard.enable()? };
+///
+///         assert_eq!(return_0(), 1);
+///     }
+///
+///     // The hook was removed at the end of the scope.
+///     assert_eq!(return_0(), 0);
+///
+///     Ok(())
+/// }
+///
+/// fn return_0() -> i32 {
+///     0
+/// }
+///
+/// fn return_1() -> i32 {
+///     1
+/// }
+/// ```
+pub struct HookGuard<F: FnPtr = *mut c_void> {
+    hook: ManuallyDrop<Hook<F>>,
+}
+
+impl<F: FnPtr> HookGuard<F> {
+    /// Creates a hook for the target function and detours it to the detour function. The hook is created disabled.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`].
+    pub unsafe fn new(target: F, detour: F) -> Result<Self, HookError> {
+        unsafe { Hook::new(target, detour) }.map(Hook::into_guard)
+    }
+
+    /// Releases the hook without removing it, leaving it in its current state.
+    pub fn leak(self) -> Hook<F> {
+        let mut guard = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(&mut guard.hook) }
+    }
+}
+
+impl<F: FnPtr> Deref for HookGuard<F> {
+    type Target = Hook<F>;
+
+    fn deref(&self) -> &Hook<F> {
+        &self.hook
+    }
+}
+
+impl<F: FnPtr> Drop for HookGuard<F> {
+    fn drop(&mut self) {
+        // Removing a hook disables it first if it is enabled.
+        let hook = unsafe { ManuallyDrop::take(&mut self.hook) };
+        let target = hook.target().to_ptr();
+        if let Err(status) = unsafe { hook.remove() } {
+            error!("Could not remove the hook for {:?}: {:?}", target, status);
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for HookGuard<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("HookGuard").field(&*self.hook).finish()
+    }
+}
@@ -0,0 +1,117 @@
+//! Hooks on the import table of a module, which redirect the calls that module makes to an imported function without
+//! patching the function itself.
+//!
+//! On Linux, the slots are the GOT entries filled by the `JUMP_SLOT` and `GLOB_DAT` relocations of the module, on
+//! Windows, the entries of its import address table. Every slot holding the function is replaced by the detour, and
+//! the previous values are written back when the hook is removed.
+
+use crate::{ErrorKind, HookError, HookTarget, MH_STATUS, Operation, os};
+use std::{
+    ffi::{CString, c_void},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+use tracing::debug;
+
+/// A created import hook.
+struct ImportHook {
+    module: usize,
+    symbol: CString,
+    /// The addresses of the replaced slots and their previous values.
+    slots: Vec<(usize, usize)>,
+}
+
+static IMPORTS: Mutex<Vec<ImportHook>> = Mutex::new(Vec::new());
+
+fn lock() -> MutexGuard<'static, Vec<ImportHook>> {
+    IMPORTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn error(operation: Operation, module_name: &str, symbol: &str, kind: ErrorKind) -> HookError {
+    HookError::new(
+        operation,
+        Some(HookTarget::Symbol {
+            module: module_name.to_owned(),
+            symbol: symbol.to_owned(),
+        }),
+        kind,
+    )
+}
+
+/// Finds the import slots of `symbol` in `module_name`, validating the names.
+fn find(module_name: &str, symbol: &str) -> Result<(CString, os::Import), ErrorKind> {
+    if let Some(position) = module_name.find('\0') {
+        return Err(ErrorKind::InteriorNul {
+            argument: "module_name",
+            position,
+        });
+    }
+    let symbol = CString::new(symbol).map_err(|e| ErrorKind::InteriorNul {
+        argument: "symbol",
+        position: e.nul_position(),
+    })?;
+
+    let import = os::find_import(module_name, &symbol).map_err(ErrorKind::Status)?;
+    Ok((symbol, import))
+}
+
+pub(crate) unsafe fn create(
+    module_name: &str,
+    symbol: &str,
+    detour: *mut c_void,
+) -> Result<*mut c_void, HookError> {
+    let error = |kind| error(Operation::CreateImportHook, module_name, symbol, kind);
+    if detour.is_null() {
+        return Err(error(ErrorKind::NullPointer { argument: "detour" }));
+    }
+
+    let mut imports = lock();
+    let (symbol, import) = find(module_name, symbol).map_err(error)?;
+    if imports
+        .iter()
+        .any(|hook| hook.module == import.module && hook.symbol == symbol)
+    {
+        return Err(error(ErrorKind::Status(
+            MH_STATUS::MH_ERROR_ALREADY_CREATED,
+        )));
+    }
+
+    let mut slots = Vec::with_capacity(import.slots.len());
+    for slot in import.slots {
+        let previous = unsafe { *(slot as *const usize) };
+        if !unsafe { os::write_pointer(slot, detour as usize) } {
+            for &(slot, previous) in &slots {
+                unsafe { os::write_pointer(slot, previous) };
+            }
+            return Err(error(ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_PROTECT)));
+        }
+        slots.push((slot, previous));
+    }
+    debug!("created import hook with {} slots", slots.len());
+
+    imports.push(ImportHook {
+        module: import.module,
+        symbol,
+        slots,
+    });
+    Ok(import.function as *mut c_void)
+}
+
+pub(crate) unsafe fn remove(module_name: &str, symbol: &str) -> Result<(), HookError> {
+    let error = |kind| error(Operation::RemoveImportHook, module_name, symbol, kind);
+
+    let mut imports = lock();
+    let (symbol, import) = find(module_name, symbol).map_err(error)?;
+    let index = imports
+        .iter()
+        .position(|hook| hook.module == import.module && hook.symbol == symbol)
+        .ok_or(error(ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_CREATED)))?;
+
+    for &(slot, previous) in &imports[index].slots {
+        if !unsafe { os::write_pointer(slot, previous) } {
+            return Err(error(ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_PROTECT)));
+        }
+    }
+
+    imports.remove(index);
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! The code of trampolines, for debugging hooks.
+
+use crate::{
+    disasm::{self, Map, Mode},
+    trampoline::{self, Target, Trampoline},
+};
+use std::{ffi::c_void, fmt};
+
+/// The code of a trampoline, as returned by [`MinHook::inspect_trampoline`](crate::MinHook::inspect_trampoline) and
+/// [`Analysis::trampoline`](crate::Analysis::trampoline).
+///
+/// The trampoline starts with the instructions relocated from the start of the target function, followed by a jump
+/// to the rest of the target function. On x64, it ends with a relay function that jumps to the detour, because the
+/// detour may be out of reach of the jump written over the target function.
+///
+/// The [`Display`](fmt::Display) implementation prints one line per instruction, with its offset, bytes and the
+/// instruction of the target function it was relocated from.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, MinHook};
+///
+/// fn main() -> Result<(), HookError> {
+///     unsafe { MinHook::create_hook(return_0 as _, return_1 as _)? };
+///
+///     let trampoline = MinHook::inspect_trampoline(return_0 as _)?;
+///     assert!(trampoline.relocated_len() >= 5);
+///     println!("{trampoline}");
+///
+///     unsafe { MinHook::remove_hook(return_0 as _)? };
+///     Ok(())
+/// }
+///
+/// fn return_0() -> i32 {
+///     std::hint::black_box(0)
+/// }
+///
+/// fn return_1() -> i32 {
+///     1
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrampolineInfo {
+    target: usize,
+    address: usize,
+    code: Vec<u8>,
+    pub(crate) relocated_len: usize,
+    pub(crate) patch_above: bool,
+    relay: Option<usize>,
+    offsets: Vec<(usize, usize)>,
+}
+
+impl TrampolineInfo {
+    /// Describes a trampoline built for `target` at `address`.
+    pub(crate) fn new(target: &Target, address: usize, trampoline: Trampoline) -> Self {
+        Self {
+            target: target.address,
+            address,
+            code: trampoline.code,
+            relocated_len: trampoline.len,
+            patch_above: trampoline.patch_above,
+            relay: trampoline.relay,
+            offsets: trampoline
+                .ips
+                .iter()
+                .map(|&(old, new)| (old as usize, new as usize))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn set_code(&mut self, code: Vec<u8>) {
+        self.code = code;
+    }
+
+    /// Returns the target function.
+    pub fn target(&self) -> *mut c_void {
+        self.target as *mut c_void
+    }
+
+    /// Returns the address of the trampoline.
+    pub fn address(&self) -> *mut c_void {
+        self.address as *mut c_void
+    }
+
+    /// Returns the machine code of the trampoline, followed by the relay function on x64.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Returns the number of bytes at the start of the target function that were relocated into the trampoline.
+    pub fn relocated_len(&self) -> usize {
+        self.relocated_len
+    }
+
+    /// Returns the offset of the relay function within the code, which jumps to the detour on x64.
+    pub fn relay(&self) -> Option<usize> {
+        self.relay
+    }
+
+    /// Returns the offsets of the relocated instructions in the target function, paired with their offsets in the
+    /// trampoline. The last pair is the jump back to the rest of the target function, unless the relocated code ends
+    /// with a return or an unconditional jump.
+    pub fn offsets(&self) -> &[(usize, usize)] {
+        &self.offsets
+    }
+}
+
+impl fmt::Display for TrampolineInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "trampoline at {:#x} for {:#x}, {} bytes relocated",
+            self.address, self.target, self.relocated_len
+        )?;
+
+        let mut pos = 0;
+        while pos < self.code.len() {
+            let Ok(inst) = disasm::decode(&self.code[pos..], Mode::NATIVE) else {
+                write!(f, "\n  +{pos:#04x}  ")?;
+                write_bytes(f, &self.code[pos..])?;
+                return write!(f, "  (undecodable)");
+            };
+
+            let address = self.address + pos;
+            let mut len = inst.len;
+            let mut comment = String::new();
+
+            if let Some(&(old, _)) = self.offsets.iter().find(|&&(_, new)| new == pos) {
+                comment = format!("target+{old:#04x}");
+            } else if self.relay == Some(pos) {
+                comment = "relay".to_owned();
+            }
+
+            let name = instruction_name(&inst);
+            if inst.rip_relative {
+                let operand = (address + inst.len).wrapping_add(inst.disp as usize);
+                let inline = operand.wrapping_sub(self.address);
+
+                // The absolute jumps and calls written by MinHook keep their destination right after the instruction.
+                match (name, inline.checked_sub(pos + inst.len)) {
+                    (Some(name @ ("jmp" | "call")), Some(gap @ (0 | 2)))
+                        if inline + 8 <= self.code.len() =>
+                    {
+                        let mut destination = [0; 8];
+                        destination.copy_from_slice(&self.code[inline..inline + 8]);
+                        len += gap + 8;
+                        comment = join(
+                            comment,
+                            format!("{name} {:#x}", u64::from_le_bytes(destination)),
+                        );
+                    }
+                    _ => comment = join(comment, format!("[rip] {operand:#x}")),
+                }
+            } else if let Some(destination) = trampoline::relative_branch(address, &inst) {
+                comment = join(
+                    comment,
+                    format!("{} {destination:#x}", name.unwrap_or("branch")),
+                );
+            } else if name == Some("ret") {
+                comment = join(comment, "ret".to_owned());
+            }
+
+            let len = len.min(self.code.len() - pos);
+            write!(f, "\n  +{pos:#04x}  ")?;
+            write_bytes(f, &self.code[pos..pos + len])?;
+            let width = (len * 3).min(BYTES_WIDTH);
+            write!(f, "{:1$}  {comment}", "", BYTES_WIDTH - width)?;
+
+            pos += len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Width of the column of instruction bytes, enough for the absolute calls written by MinHook.
+const BYTES_WIDTH: usize = 48;
+
+fn write_bytes(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{byte:02x}")?;
+    }
+    write!(f, " ")
+}
+
+fn join(comment: String, annotation: String) -> String {
+    if comment.is_empty() {
+        annotation
+    } else {
+        format!("{comment}  {annotation}")
+    }
+}
+
+/// Returns the mnemonic of the control flow instructions MinHook relocates and generates.
+fn instruction_name(inst: &disasm::Instruction) -> Option<&'static str> {
+    const JCC: [&str; 16] = [
+        "jo", "jno", "jb", "jae", "je", "jne", "jbe", "ja", "js", "jns", "jp", "jnp", "jl", "jge",
+        "jle", "jg",
+    ];
+    const LOOP: [&str; 4] = ["loopne", "loope", "loop", "jcxz"];
+
+    match (inst.map, inst.opcode) {
+        (Map::Primary, 0x70..=0x7F) => Some(JCC[usize::from(inst.opcode & 0x0F)]),
+        (Map::Secondary, 0x80..=0x8F) => Some(JCC[usize::from(inst.opcode & 0x0F)]),
+        (Map::Primary, 0xE0..=0xE3) => Some(LOOP[usize::from(inst.opcode & 0x03)]),
+        (Map::Primary, 0xE8) => Some("call"),
+        (Map::Primary, 0xE9 | 0xEB) => Some("jmp"),
+        (Map::Primary, 0xC2 | 0xC3) => Some("ret"),
+        (Map::Primary, 0xFF) => match inst.modrm_reg() {
+            Some(2) => Some("call"),
+            Some(4) => Some("jmp"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -2,14 +2,17 @@
 //!
 //! This crate is a wrapper around the MinHook library. Most of the API is unsafe because it is not possible to guarantee safety of the hooks.
 //!
+//! On Windows, the bundled C library is used. On Linux x86-64, the same API is backed by a Rust port of the MinHook engine,
+//! which can also be used on Windows by enabling the `pure-rust` feature.
+//!
 //! # Example
 //!
 //! This example shows how to create a hook for a function, and also call the original function.
 //!
 //! ```rust
-//! use minhook::{MinHook, MH_STATUS};
+//! use minhook::{HookError, MinHook};
 //!
-//! fn main() -> Result<(), MH_STATUS> {
+//! fn main() -> Result<(), HookError> {
 //!     // Create a hook for the return_0 function, detouring it to return_1
 //!     let return_0_address = unsafe { MinHook::create_hook(return_0 as _, return_1 as _)? };
 //!
@@ -36,243 +39,621 @@
 //!     1
 //! }
 //! ```
+//!
+//! [`Hook`] wraps the same calls with a function pointer type, so the original function does not have to be
+//! transmuted and the target and detour signatures are checked by the compiler. [`HookGuard`] removes a hook when it
+//! goes out of scope, [`ClosureHook`] detours a function to a closure that can capture state, and [`ChainedHook`] stacks
+//! several detours on the same function. Hooks that live in statics can be declared with [`static_hook!`], and with the
+//! default `macros` feature, the `#[hook]` attribute turns a function into a detour for an exported function or any
+//! other target. [`MidHook`] hooks an instruction in the middle of a function and passes the registers to a closure,
+//! and [`EnterLeaveHook`] calls closures when a function is entered and when it returns, which can change the arguments
+//! and the return value.
+//!
+//! MinHook is initialized on first use. [`MinHook::init`] initializes it explicitly with a [`Config`], and
+//! [`MinHook::shutdown`] removes all hooks and uninitializes it, after which it can be initialized again.
+//! [`MinHook::hooks`] lists the created hooks and their state, and [`HookTransaction`] applies a batch of changes
+//! together or not at all. [`MinHook::analyze`] reports how a function would be hooked without patching it, and
+//! [`MinHook::inspect_trampoline`] shows the trampoline of a created hook. [`MinHook::create_import_hook`] redirects
+//! the calls of a single module by replacing an entry of its import table instead of patching the function, and
+//! [`VTableHook`] replaces an entry of a virtual table. [`DeferredHook`] hooks an exported function of a module that may
+//! not be loaded yet, and installs the hook whenever the module is loaded. [`Signature`] finds functions that are not
+//! exported by searching the code of a module for a byte pattern.
+//!
+//! The [`disasm`] module exposes the instruction length decoder used to build trampolines, and the [`process`] module
+//! lists the loaded modules and memory regions of the process.
 
+use disasm::Mode;
 use ffi::{
-    MH_ApplyQueued, MH_CreateHook, MH_CreateHookApi, MH_CreateHookApiEx, MH_DisableHook,
-    MH_EnableHook, MH_Initialize, MH_QueueDisableHook, MH_QueueEnableHook, MH_RemoveHook,
-    MH_Uninitialize,
+    MH_ApplyQueued, MH_CreateHook, MH_CreateHookApiEx, MH_DisableHook, MH_EnableHook,
+    MH_Initialize, MH_QueueDisableHook, MH_QueueEnableHook, MH_RemoveHook, MH_Uninitialize,
 };
+use registry::Registry;
 use std::{
     ffi::{CString, c_void},
     fmt,
     ptr::null_mut,
-    sync::Once,
+    slice,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
 };
-use tracing::debug;
-
+use tracing::{debug, warn};
+
+mod analysis;
+#[cfg_attr(not(rust_engine), allow(dead_code))]
+mod buffer;
+mod chain;
+mod closure;
+mod config;
+mod deferred;
+pub mod disasm;
+#[cfg(rust_engine)]
+mod engine;
+mod enter_leave;
+mod error;
 mod ffi;
+mod hook;
+mod import;
+mod inspect;
+mod mid;
+#[cfg_attr(not(rust_engine), allow(dead_code))]
+mod os;
+pub mod process;
+mod registry;
+mod signature;
+mod static_hook;
+mod thunk;
+mod trampoline;
+mod transaction;
+mod vtable;
+
+pub use analysis::{Analysis, Issue};
+pub use chain::ChainedHook;
+pub use closure::{ClosureDetour, ClosureHook};
+pub use config::Config;
+pub use deferred::DeferredHook;
+pub use enter_leave::EnterLeaveHook;
+pub use error::{ErrorKind, HookError, HookTarget, Operation};
+pub use hook::{FnPtr, Hook, HookGuard};
+pub use inspect::TrampolineInfo;
+pub use mid::{Context, MidHook};
+pub use registry::HookInfo;
+pub use signature::Signature;
+pub use static_hook::StaticHook;
+pub use transaction::HookTransaction;
+pub use vtable::VTableHook;
+
+#[cfg(feature = "macros")]
+pub use minhook_macros::hook;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::static_hook::HookCell;
+}
 
 const MH_ALL_HOOKS: *const i32 = std::ptr::null();
 
-static MINHOOK_INIT: Once = Once::new();
-static MINHOOK_UNINIT: Once = Once::new();
+/// The configuration MinHook was last initialized with, which also decides whether it is initialized on first use.
+static CONFIG: Mutex<Config> = Mutex::new(Config::new());
+
+/// Set while MinHook is initialized, so the hooking functions do not have to lock `CONFIG`.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
 /// A struct to access the MinHook API.
 pub struct MinHook {}
 
 impl MinHook {
-    // Initialize MinHook
-    fn initialize() {
-        MINHOOK_INIT.call_once(|| {
-            let status = unsafe { MH_Initialize() };
-            debug!("MH_Initialize: {:?}", status);
-
-            match status.ok() {
-                Ok(_) => (), // Initialization successful, do nothing
-                Err(MH_STATUS::MH_ERROR_ALREADY_INITIALIZED) => (), // Ignore if already initialized
-                Err(e) => panic!("Could not initialize MinHook, error: {e:?}"),
-            }
-        });
+    /// Initializes MinHook with the given configuration.
+    ///
+    /// Calling this function is optional, since the hooking functions initialize MinHook with the default configuration
+    /// when it is not initialized. MinHook can be initialized again after [`MinHook::shutdown`].
+    ///
+    /// Returns `MH_ERROR_ALREADY_INITIALIZED` if MinHook is already initialized, including when it was initialized on
+    /// first use.
+    pub fn init(config: Config) -> Result<(), HookError> {
+        let mut current = lock_config();
+        init_with(&mut current, config)
+            .map_err(|kind| HookError::new(Operation::Initialize, None, kind))
+    }
+
+    /// Disables and removes all hooks and uninitializes MinHook.
+    ///
+    /// Returns `MH_ERROR_NOT_INITIALIZED` if MinHook is not initialized.
+    ///
+    /// # Safety
+    ///
+    /// The trampolines of all hooks are freed, so no thread may be executing a trampoline or call an original function
+    /// obtained before, and hook handles such as [`Hook`] must not be used afterwards.
+    pub unsafe fn shutdown() -> Result<(), HookError> {
+        let _config = lock_config();
+        let mut registry = registry::lock();
+        let status = decode(unsafe { MH_Uninitialize() });
+        debug!("MH_Uninitialize: {:?}", status);
+        status.map_err(|kind| HookError::new(Operation::Uninitialize, None, kind))?;
+
+        registry.clear();
+        INITIALIZED.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Returns `true` if MinHook is initialized.
+    pub fn is_initialized() -> bool {
+        INITIALIZED.load(Ordering::SeqCst)
     }
 
-    /// Uninitializes MinHook. This function is only possible to call once. If you want to reinitialize MinHook, you need to restart the program.
+    /// Uninitializes MinHook, ignoring any error.
     ///
     /// # Safety
+    ///
+    /// See [`MinHook::shutdown`].
+    #[deprecated(
+        since = "0.9.0",
+        note = "use `MinHook::shutdown`, which reports errors and allows initializing MinHook again"
+    )]
     pub fn uninitialize() {
-        // Make sure we are initialized before we uninitialize
-        Self::initialize();
+        let _ = unsafe { Self::shutdown() };
+    }
 
-        MINHOOK_UNINIT.call_once(|| {
-            let status = unsafe { MH_Uninitialize() };
-            debug!("MH_Uninitialize: {:?}", status);
+    // Initializes MinHook on first use, unless automatic initialization is disabled. In that case the engine reports
+    // that it is not initialized.
+    fn initialize() -> Result<(), HookError> {
+        if INITIALIZED.load(Ordering::SeqCst) {
+            return Ok(());
+        }
 
-            status.ok().expect("Could not uninitialize MinHook");
-        });
+        let mut config = lock_config();
+        if INITIALIZED.load(Ordering::SeqCst) || !config.auto_initialize {
+            return Ok(());
+        }
+
+        let new = *config;
+        init_with(&mut config, new)
+            .map_err(|kind| HookError::new(Operation::Initialize, None, kind))
     }
 
     /// Creates a hook for the target function and detours it to the detour function. This function returns the original function pointer.
     ///
+    /// Returns `MH_ERROR_NOT_EXECUTABLE` before anything is changed if the target or the detour does not lie within
+    /// executable memory, as checked by [`process::is_executable`].
+    ///
     /// # Safety
     pub unsafe fn create_hook(
         target: *mut c_void,
         detour: *mut c_void,
-    ) -> Result<*mut c_void, MH_STATUS> {
-        Self::initialize();
+    ) -> Result<*mut c_void, HookError> {
+        Self::initialize()?;
 
-        let mut pp_original: *mut c_void = null_mut();
-        let status = unsafe { MH_CreateHook(target, detour, &mut pp_original) };
-        debug!("MH_CreateHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(pp_original),
-            _ => Err(status),
-        }
+        unsafe { create_hook(&mut registry::lock(), target, detour) }
+    }
+
+    /// Creates a hook for the target function and detours it to the detour function. This function returns a guard that removes the hook when dropped.
+    ///
+    /// # Safety
+    pub unsafe fn create_hook_guard(
+        target: *mut c_void,
+        detour: *mut c_void,
+    ) -> Result<HookGuard, HookError> {
+        unsafe { HookGuard::new(target, detour) }
     }
 
     /// Creates a hook for the targeted API function and detours it to the detour function. This function returns the original function pointer.
     ///
+    /// On Windows, the module is looked up with `GetModuleHandleW` and the function with `GetProcAddress`. On Linux, the
+    /// module is a loaded shared object such as `libc.so.6`, or the empty name for the main program, and the function
+    /// is looked up by the dynamic loader with `dlsym`. That also finds functions of the dependencies of the module,
+    /// and for the main program those of every library in the global scope. A version can be named as in
+    /// `malloc@GLIBC_2.2.5`, or `malloc@@GLIBC_2.2.5` to only match it if it is the default version, in which case the
+    /// function is looked up in the dynamic symbol table of the module.
+    ///
     /// # Safety
     pub unsafe fn create_hook_api<T: AsRef<str>>(
         module_name: T,
         proc_name: T,
         detour: *mut c_void,
-    ) -> Result<*mut c_void, MH_STATUS> {
-        Self::initialize();
-
-        let mut module_name = module_name.as_ref().encode_utf16().collect::<Vec<_>>();
-        module_name.push(0);
-
-        let proc_name = CString::new(proc_name.as_ref()).unwrap();
-        let mut pp_original: *mut c_void = null_mut();
-        let status = unsafe {
-            MH_CreateHookApi(
-                module_name.as_ptr() as *const _,
-                proc_name.as_ptr() as *const _,
-                detour,
-                &mut pp_original,
-            )
-        };
-        debug!("MH_CreateHookApi: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(pp_original),
-            _ => Err(status),
-        }
+    ) -> Result<*mut c_void, HookError> {
+        unsafe { Self::create_hook_api_ex(module_name, proc_name, detour) }
+            .map(|(original, _)| original)
     }
 
     /// Extended function for creating a hook for the targeted API function and detours it to the detour function. This function returns the original function pointer as well as a pointer to the target function.
+    ///
+    /// The function is looked up as for [`MinHook::create_hook_api`].
+    ///
     /// # Safety
     pub unsafe fn create_hook_api_ex<T: AsRef<str>>(
         module_name: T,
         proc_name: T,
         detour: *mut c_void,
-    ) -> Result<(*mut c_void, *mut c_void), MH_STATUS> {
-        Self::initialize();
+    ) -> Result<(*mut c_void, *mut c_void), HookError> {
+        Self::initialize()?;
 
-        let mut module_name = module_name.as_ref().encode_utf16().collect::<Vec<_>>();
-        module_name.push(0);
+        let (module_name, proc_name) = (module_name.as_ref(), proc_name.as_ref());
+        let error = |kind| api_error(module_name, proc_name, kind);
+        let (module_name_w, proc_name_c) =
+            encode_api_names(module_name, proc_name, detour).map_err(error)?;
 
-        let proc_name = CString::new(proc_name.as_ref()).unwrap();
+        let mut registry = registry::lock();
         let mut pp_original: *mut c_void = null_mut();
         let mut pp_target: *mut c_void = null_mut();
-        let status = unsafe {
+        let status = decode(unsafe {
             MH_CreateHookApiEx(
-                module_name.as_ptr() as *const _,
-                proc_name.as_ptr() as *const _,
+                module_name_w.as_ptr() as *const _,
+                proc_name_c.as_ptr() as *const _,
                 detour,
                 &mut pp_original,
                 &mut pp_target,
             )
-        };
+        });
         debug!("MH_CreateHookApiEx: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok((pp_original, pp_target)),
-            _ => Err(status),
-        }
+        status.map_err(error)?;
+
+        registry.insert(pp_target as usize, detour as usize, pp_original as usize);
+        Ok((pp_original, pp_target))
+    }
+
+    /// Creates an import hook, which replaces `symbol` in the import table of the loaded module `module_name` with the
+    /// detour function. Only the calls made by that module are detoured, and the imported function itself is not
+    /// patched. The empty module name stands for the main program. This function returns the imported function.
+    ///
+    /// On Linux, the GOT slots of the module that are filled by `JUMP_SLOT` and `GLOB_DAT` relocations for the symbol
+    /// are replaced, and the module can be given by its path or file name, such as `libfoo.so`. On Windows, the slots of
+    /// the import address table that hold the function imported by name are replaced. Import hooks take effect
+    /// immediately and do not require MinHook to be initialized.
+    ///
+    /// Returns `MH_ERROR_MODULE_NOT_FOUND` if the module is not loaded, `MH_ERROR_FUNCTION_NOT_FOUND` if it does not
+    /// import the symbol, and `MH_ERROR_ALREADY_CREATED` if an import hook already replaces it.
+    ///
+    /// # Safety
+    ///
+    /// The detour must have the same signature as the imported function, and other threads of the module may call it
+    /// as soon as this function writes the slots. Calls that read the slot before it is written still reach the
+    /// imported function.
+    pub unsafe fn create_import_hook<T: AsRef<str>>(
+        module_name: T,
+        symbol: T,
+        detour: *mut c_void,
+    ) -> Result<*mut c_void, HookError> {
+        unsafe { import::create(module_name.as_ref(), symbol.as_ref(), detour) }
+    }
+
+    /// Removes an import hook created by [`MinHook::create_import_hook`], writing the previous values back to the
+    /// import slots.
+    ///
+    /// Returns `MH_ERROR_NOT_CREATED` if no import hook replaces `symbol` in the module.
+    ///
+    /// # Safety
+    ///
+    /// Calls to the detour that are already in progress are not waited for.
+    pub unsafe fn remove_import_hook<T: AsRef<str>>(
+        module_name: T,
+        symbol: T,
+    ) -> Result<(), HookError> {
+        unsafe { import::remove(module_name.as_ref(), symbol.as_ref()) }
     }
 
     /// Enables a hook for the target function.
     ///
     /// # Safety
-    pub unsafe fn enable_hook(target: *mut c_void) -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_EnableHook(target) };
-        debug!("MH_EnableHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
-        }
+    pub unsafe fn enable_hook(target: *mut c_void) -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { change_hook(&mut registry::lock(), Operation::EnableHook, target) }
     }
 
     /// Enables all hooks.
     ///
     /// # Safety
-    pub unsafe fn enable_all_hooks() -> Result<(), MH_STATUS> {
+    pub unsafe fn enable_all_hooks() -> Result<(), HookError> {
         unsafe { Self::enable_hook(MH_ALL_HOOKS as *mut _) }
     }
 
     /// Disables a hook for the target function.
     ///
     /// # Safety
-    pub unsafe fn disable_hook(target: *mut c_void) -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_DisableHook(target) };
-        debug!("MH_DisableHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
-        }
+    pub unsafe fn disable_hook(target: *mut c_void) -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { change_hook(&mut registry::lock(), Operation::DisableHook, target) }
     }
 
     /// Disables all hooks.
     ///
     /// # Safety
-    pub unsafe fn disable_all_hooks() -> Result<(), MH_STATUS> {
+    pub unsafe fn disable_all_hooks() -> Result<(), HookError> {
         unsafe { Self::disable_hook(MH_ALL_HOOKS as *mut _) }
     }
 
     /// Removes a hook for the target function.
     ///
     /// # Safety
-    pub unsafe fn remove_hook(target: *mut c_void) -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_RemoveHook(target) };
-        debug!("MH_RemoveHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
-        }
+    pub unsafe fn remove_hook(target: *mut c_void) -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { change_hook(&mut registry::lock(), Operation::RemoveHook, target) }
     }
 
     /// Queues a hook for enabling.
     ///
     /// # Safety
-    pub unsafe fn queue_enable_hook(target: *mut c_void) -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_QueueEnableHook(target) };
-        debug!("MH_QueueEnableHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
-        }
+    pub unsafe fn queue_enable_hook(target: *mut c_void) -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { change_hook(&mut registry::lock(), Operation::QueueEnableHook, target) }
     }
 
     /// Queues a hook for disabling.
     ///
     /// # Safety
-    pub unsafe fn queue_disable_hook(target: *mut c_void) -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_QueueDisableHook(target) };
-        debug!("MH_QueueDisableHook: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
-        }
+    pub unsafe fn queue_disable_hook(target: *mut c_void) -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { change_hook(&mut registry::lock(), Operation::QueueDisableHook, target) }
     }
 
     /// Applies all queued hooks.
     ///
     /// # Safety
-    pub unsafe fn apply_queued() -> Result<(), MH_STATUS> {
-        Self::initialize();
-
-        let status = unsafe { MH_ApplyQueued() };
-        debug!("MH_ApplyQueued: {:?}", status);
-        match status {
-            MH_STATUS::MH_OK => Ok(()),
-            _ => Err(status),
+    pub unsafe fn apply_queued() -> Result<(), HookError> {
+        Self::initialize()?;
+
+        unsafe { apply_queued(&mut registry::lock()) }
+    }
+
+    /// Returns all hooks created through this crate, in the order they were created.
+    ///
+    /// The list reflects the calls that succeeded. If enabling or disabling all hooks or applying queued changes fails
+    /// partway, the hooks changed before the failure are still listed with their previous state.
+    pub fn hooks() -> Vec<HookInfo> {
+        registry::lock().hooks().to_vec()
+    }
+
+    /// Returns `true` if a hook has been created for the target function.
+    pub fn is_created(target: *mut c_void) -> bool {
+        registry::lock().get(target as usize).is_some()
+    }
+
+    /// Returns `true` if the hook for the target function is enabled.
+    pub fn is_enabled(target: *mut c_void) -> bool {
+        registry::lock()
+            .get(target as usize)
+            .is_some_and(HookInfo::is_enabled)
+    }
+
+    /// Returns the code of the trampoline of the hook for the target function, with the offsets of the instructions
+    /// relocated into it and its relay function.
+    ///
+    /// The offsets are worked out by building the trampoline again from the code the target function had when the hook
+    /// was created, while the code is read from the trampoline itself.
+    ///
+    /// Returns `MH_ERROR_NOT_CREATED` if no hook has been created for the target.
+    pub fn inspect_trampoline(target: *mut c_void) -> Result<TrampolineInfo, HookError> {
+        let error = |status| {
+            HookError::new(
+                Operation::InspectTrampoline,
+                Some(HookTarget::Address(target as usize)),
+                ErrorKind::Status(status),
+            )
+        };
+
+        let registry = registry::lock();
+        let (Some(hook), Some(prologue)) = (
+            registry.get(target as usize),
+            registry.prologue(target as usize),
+        ) else {
+            return Err(error(MH_STATUS::MH_ERROR_NOT_CREATED));
+        };
+
+        let address = hook.trampoline() as usize;
+        let built = trampoline::create(prologue, address, hook.detour() as usize, Mode::NATIVE)
+            .map_err(|_| error(MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION))?;
+        let mut info = TrampolineInfo::new(prologue, address, built);
+
+        // The trampoline is read back, so the code is the one that runs even if it was not built the same way.
+        let code = unsafe { slice::from_raw_parts(address as *const u8, info.code().len()) };
+        if code != info.code() {
+            warn!("The trampoline at {address:#x} differs from the one built for {target:?}");
+            info.set_code(code.to_vec());
+        }
+        Ok(info)
+    }
+
+    /// Works out how the target function would be hooked, without patching it or allocating a trampoline. The
+    /// analysis reports the instructions that would be relocated, the trampoline that would be built and the reasons
+    /// the function cannot be hooked, if any.
+    ///
+    /// Returns `MH_ERROR_NOT_EXECUTABLE` if the target is not executable, and `MH_ERROR_ALREADY_CREATED` if a hook has
+    /// already been created for it, since the code of a hooked function may already be patched.
+    pub fn analyze(target: *mut c_void) -> Result<Analysis, HookError> {
+        let error = |kind| {
+            HookError::new(
+                Operation::Analyze,
+                Some(HookTarget::Address(target as usize)),
+                kind,
+            )
+        };
+        if target.is_null() {
+            return Err(error(ErrorKind::NullPointer { argument: "target" }));
+        }
+
+        // Holding the registry keeps hooks from being created for the target while its code is read.
+        let registry = registry::lock();
+        if registry.get(target as usize).is_some() {
+            return Err(error(ErrorKind::Status(
+                MH_STATUS::MH_ERROR_ALREADY_CREATED,
+            )));
         }
+
+        let function = trampoline::Target::read(target as usize)
+            .ok_or(error(ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_EXECUTABLE)))?;
+        Ok(Analysis::new(&function))
+    }
+}
+
+/// Creates a hook and records it. The caller holds the registry lock.
+pub(crate) unsafe fn create_hook(
+    registry: &mut Registry,
+    target: *mut c_void,
+    detour: *mut c_void,
+) -> Result<*mut c_void, HookError> {
+    let error = |kind| {
+        HookError::new(
+            Operation::CreateHook,
+            Some(HookTarget::Address(target as usize)),
+            kind,
+        )
+    };
+    if target.is_null() {
+        return Err(error(ErrorKind::NullPointer { argument: "target" }));
+    }
+    if detour.is_null() {
+        return Err(error(ErrorKind::NullPointer { argument: "detour" }));
+    }
+    // The engine reports that it is not initialized before anything else.
+    if !INITIALIZED.load(Ordering::SeqCst) {
+        return Err(error(ErrorKind::Status(
+            MH_STATUS::MH_ERROR_NOT_INITIALIZED,
+        )));
+    }
+    // Checked before the engine is called, so no state is changed for a target that cannot be hooked. Each check looks
+    // up a single memory region.
+    if !process::is_executable(target) || !process::is_executable(detour) {
+        return Err(error(ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_EXECUTABLE)));
+    }
+
+    let mut pp_original: *mut c_void = null_mut();
+    let status = decode(unsafe { MH_CreateHook(target, detour, &mut pp_original) });
+    debug!("MH_CreateHook: {:?}", status);
+    status.map_err(error)?;
+
+    registry.insert(target as usize, detour as usize, pp_original as usize);
+    Ok(pp_original)
+}
+
+/// Enables, disables, removes or queues the hook for `target`, or all hooks if `target` is null, and records the
+/// change. The caller holds the registry lock.
+pub(crate) unsafe fn change_hook(
+    registry: &mut Registry,
+    operation: Operation,
+    target: *mut c_void,
+) -> Result<(), HookError> {
+    let status = match operation {
+        Operation::EnableHook => decode(unsafe { MH_EnableHook(target) }),
+        Operation::DisableHook => decode(unsafe { MH_DisableHook(target) }),
+        Operation::RemoveHook => decode(unsafe { MH_RemoveHook(target) }),
+        Operation::QueueEnableHook => decode(unsafe { MH_QueueEnableHook(target) }),
+        Operation::QueueDisableHook => decode(unsafe { MH_QueueDisableHook(target) }),
+        _ => unreachable!("{operation:?} does not change a created hook"),
+    };
+    debug!("{:?}: {:?}", operation, status);
+    check(status, operation, target)?;
+
+    let target = target as usize;
+    match operation {
+        Operation::EnableHook => registry.set_enabled(target, true),
+        Operation::DisableHook => registry.set_enabled(target, false),
+        Operation::RemoveHook => registry.remove(target),
+        Operation::QueueEnableHook => registry.queue(target, true),
+        _ => registry.queue(target, false),
+    }
+    Ok(())
+}
+
+/// Applies all queued changes and records them. The caller holds the registry lock.
+pub(crate) unsafe fn apply_queued(registry: &mut Registry) -> Result<(), HookError> {
+    let status = decode(unsafe { MH_ApplyQueued() });
+    debug!("MH_ApplyQueued: {:?}", status);
+    status.map_err(|kind| HookError::new(Operation::ApplyQueued, None, kind))?;
+
+    registry.apply_queued();
+    Ok(())
+}
+
+/// Encodes the names passed to `MH_CreateHookApiEx`, rejecting input they cannot represent.
+fn encode_api_names(
+    module_name: &str,
+    proc_name: &str,
+    detour: *mut c_void,
+) -> Result<(Vec<u16>, CString), ErrorKind> {
+    if let Some(position) = module_name.find('\0') {
+        return Err(ErrorKind::InteriorNul {
+            argument: "module_name",
+            position,
+        });
+    }
+    if detour.is_null() {
+        return Err(ErrorKind::NullPointer { argument: "detour" });
+    }
+
+    let mut module_name = module_name.encode_utf16().collect::<Vec<_>>();
+    module_name.push(0);
+
+    let proc_name = CString::new(proc_name).map_err(|e| ErrorKind::InteriorNul {
+        argument: "proc_name",
+        position: e.nul_position(),
+    })?;
+
+    Ok((module_name, proc_name))
+}
+
+fn api_error(module_name: &str, proc_name: &str, kind: ErrorKind) -> HookError {
+    HookError::new(
+        Operation::CreateHookApi,
+        Some(HookTarget::Symbol {
+            module: module_name.to_owned(),
+            symbol: proc_name.to_owned(),
+        }),
+        kind,
+    )
+}
+
+fn lock_config() -> MutexGuard<'static, Config> {
+    CONFIG.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Initializes the engine and records the configuration. The caller holds the lock on `CONFIG`.
+fn init_with(current: &mut Config, config: Config) -> Result<(), ErrorKind> {
+    let status = decode(unsafe { MH_Initialize() });
+    debug!("MH_Initialize: {:?}", status);
+    status?;
+
+    #[cfg(rust_engine)]
+    engine::set_freeze_threads(config.freeze_threads);
+    *current = config;
+    INITIALIZED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Decodes a status code returned by the engine, which may be a raw code from the C library.
+fn decode(status: impl Into<i32>) -> Result<(), ErrorKind> {
+    match MH_STATUS::try_from(status.into()) {
+        Ok(MH_STATUS::MH_OK) => Ok(()),
+        Ok(status) => Err(ErrorKind::Status(status)),
+        Err(code) => Err(ErrorKind::Unrecognized(code)),
     }
 }
 
+/// Converts the result of an operation on `target`, where a null `target` stands for all hooks.
+fn check(
+    status: Result<(), ErrorKind>,
+    operation: Operation,
+    target: *mut c_void,
+) -> Result<(), HookError> {
+    status.map_err(|kind| {
+        let target = if target.is_null() {
+            HookTarget::All
+        } else {
+            HookTarget::Address(target as usize)
+        };
+        HookError::new(operation, Some(target), kind)
+    })
+}
+
 /// MinHook status codes.
 #[allow(non_camel_case_types)]
 #[must_use]
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MH_STATUS {
     /// Unknown error. Should not be returned.
     MH_UNKNOWN = -1,
@@ -316,6 +697,39 @@ impl MH_STATUS {
     }
 }
 
+impl TryFrom<i32> for MH_STATUS {
+    type Error = i32;
+
+    /// Converts a raw status code, returning the code as the error if it is not a known status.
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        let status = match code {
+            -1 => MH_STATUS::MH_UNKNOWN,
+            0 => MH_STATUS::MH_OK,
+            1 => MH_STATUS::MH_ERROR_ALREADY_INITIALIZED,
+            2 => MH_STATUS::MH_ERROR_NOT_INITIALIZED,
+            3 => MH_STATUS::MH_ERROR_ALREADY_CREATED,
+            4 => MH_STATUS::MH_ERROR_NOT_CREATED,
+            5 => MH_STATUS::MH_ERROR_ENABLED,
+            6 => MH_STATUS::MH_ERROR_DISABLED,
+            7 => MH_STATUS::MH_ERROR_NOT_EXECUTABLE,
+            8 => MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION,
+            9 => MH_STATUS::MH_ERROR_MEMORY_ALLOC,
+            10 => MH_STATUS::MH_ERROR_MEMORY_PROTECT,
+            11 => MH_STATUS::MH_ERROR_MODULE_NOT_FOUND,
+            12 => MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND,
+            _ => return Err(code),
+        };
+
+        Ok(status)
+    }
+}
+
+impl From<MH_STATUS> for i32 {
+    fn from(status: MH_STATUS) -> Self {
+        status as i32
+    }
+}
+
 impl fmt::Display for MH_STATUS {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let message = match self {
@@ -0,0 +1,389 @@
+//! Hooks on an instruction in the middle of a function, which call a closure with the registers at that instruction.
+//!
+//! The hook jumps to a thunk that reserves stack space below the red zone, pushes a pointer to the hook state and
+//! jumps to a shared entry. The entry saves the registers into a [`Context`] on the stack, together with the x87 and
+//! SSE state, and calls the closure. It then restores the registers and resumes at the address returned by the state,
+//! which for a mid-function hook is the trampoline that runs the relocated instructions and jumps back to the
+//! function.
+//!
+//! To let the closure move the stack pointer, the address to resume at is written just below the red zone of the new
+//! stack pointer. The entry switches to it with `pop rsp` and returns to that address, releasing the red
+//! zone in the same instruction.
+
+use crate::{
+    ErrorKind, Hook, HookError, HookGuard, HookTarget, MH_STATUS, Operation,
+    thunk::{BoundThunk, Thunk, ThunkHook},
+};
+use std::{
+    arch::global_asm,
+    ffi::c_void,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The registers at the hooked instruction, which are restored from it when the function resumes.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct Context {
+    pub rflags: u64,
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rax: u64,
+    /// The stack pointer, which may be moved up, or down by at most 256 bytes.
+    pub rsp: u64,
+}
+
+/// The registers at the hooked instruction, which are restored from it when the function resumes.
+#[cfg(target_arch = "x86")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct Context {
+    pub eflags: u32,
+    pub edi: u32,
+    pub esi: u32,
+    pub ebp: u32,
+    pub ebx: u32,
+    pub edx: u32,
+    pub ecx: u32,
+    pub eax: u32,
+    /// The stack pointer, which may be moved up, or down by at most 256 bytes.
+    pub esp: u32,
+}
+
+/// Distance between the stack pointer of the function and the slot holding the address to resume at, which
+/// `ret` pops before skipping the red zone of 128 bytes on x64.
+const RESUME_SLOT: usize = if cfg!(target_arch = "x86_64") { 136 } else { 4 };
+
+/// Space the thunk reserves below the stack pointer of the function. The saved registers lie below it, so the resume
+/// slot of a stack pointer moved down by up to 256 bytes does not overlap them.
+const RESERVED: usize = RESUME_SLOT + 256;
+
+// Entered with the hook state pushed by the thunk `RESERVED` bytes below the stack pointer of the function.
+#[cfg(target_arch = "x86_64")]
+global_asm!(
+    ".text",
+    ".globl minhook_mid_entry",
+    ".p2align 4",
+    "minhook_mid_entry:",
+    // Store the stack pointer of the function above the other registers.
+    "push rax",
+    "lea rax, [rsp + {reserved} + 16]",
+    "xchg [rsp], rax",
+    "push rax",
+    "push rcx",
+    "push rdx",
+    "push rbx",
+    "push rbp",
+    "push rsi",
+    "push rdi",
+    "push r8",
+    "push r9",
+    "push r10",
+    "push r11",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "pushfq",
+    "cld",
+    // The context is passed in rdi and the state in rsi on System V, and in rcx and rdx on Windows.
+    "mov rbx, rsp",
+    "mov rdi, rsp",
+    "mov rcx, rsp",
+    "mov rsi, [rsp + {context_size}]",
+    "mov rdx, [rsp + {context_size}]",
+    // Space for the x87 and SSE state and the shadow space of the Windows x64 ABI, keeping the stack 16-byte aligned.
+    "and rsp, -16",
+    "sub rsp, 0x220",
+    "fxsave64 [rsp + 0x20]",
+    "call {dispatch}",
+    "fxrstor64 [rsp + 0x20]",
+    "mov rsp, rbx",
+    "popfq",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop r11",
+    "pop r10",
+    "pop r9",
+    "pop r8",
+    "pop rdi",
+    "pop rsi",
+    "pop rbp",
+    "pop rbx",
+    "pop rdx",
+    "pop rcx",
+    "pop rax",
+    // Switch to the resume slot below the red zone and return to the trampoline.
+    "pop rsp",
+    "ret 128",
+    reserved = const RESERVED,
+    context_size = const size_of::<Context>(),
+    dispatch = sym dispatch,
+);
+
+// Entered with the hook state pushed by the thunk `RESERVED` bytes below the stack pointer of the function.
+#[cfg(target_arch = "x86")]
+global_asm!(
+    ".text",
+    ".globl _minhook_mid_entry",
+    ".p2align 4",
+    "_minhook_mid_entry:",
+    // Store the stack pointer of the function above the other registers.
+    "push eax",
+    "lea eax, [esp + {reserved} + 8]",
+    "xchg [esp], eax",
+    "push eax",
+    "push ecx",
+    "push edx",
+    "push ebx",
+    "push ebp",
+    "push esi",
+    "push edi",
+    "pushfd",
+    "cld",
+    "mov esi, esp",
+    "mov eax, [esp + {context_size}]",
+    // Space for the x87 and SSE state and the arguments, keeping the stack 16-byte aligned.
+    "and esp, -16",
+    "sub esp, 0x210",
+    "fxsave [esp + 0x10]",
+    "mov [esp + 4], eax",
+    "mov [esp], esi",
+    "call {dispatch}",
+    "fxrstor [esp + 0x10]",
+    "mov esp, esi",
+    "popfd",
+    "pop edi",
+    "pop esi",
+    "pop ebp",
+    "pop ebx",
+    "pop edx",
+    "pop ecx",
+    "pop eax",
+    // Switch to the resume slot and return to the trampoline.
+    "pop esp",
+    "ret",
+    reserved = const RESERVED,
+    context_size = const size_of::<Context>(),
+    dispatch = sym dispatch,
+);
+
+unsafe extern "C" {
+    fn minhook_mid_entry();
+}
+
+/// Called with the saved registers and the `resume` address of the state, and returns the address to resume at.
+pub(crate) type Callback = Box<dyn Fn(&mut Context, usize) -> usize + Send + Sync>;
+
+/// State shared between a thunk and the entry.
+pub(crate) struct MidState {
+    pub(crate) callback: Callback,
+    /// The address to resume at, usually the trampoline of the hook.
+    pub(crate) resume: AtomicUsize,
+}
+
+/// Called by the entry with the saved registers.
+extern "C" fn dispatch(context: &mut Context, state: &MidState) {
+    let resume = (state.callback)(context, state.resume.load(Ordering::Acquire));
+
+    #[cfg(target_arch = "x86_64")]
+    let sp = &mut context.rsp;
+    #[cfg(target_arch = "x86")]
+    let sp = &mut context.esp;
+
+    let slot = *sp as usize - RESUME_SLOT;
+    unsafe { *(slot as *mut usize) = resume };
+    *sp = slot as _;
+}
+
+/// Generates the thunk that enters the shared entry with `state`.
+pub(crate) fn mid_thunk(state: *const MidState) -> Option<Thunk> {
+    let entry = minhook_mid_entry as *const () as usize;
+    let reserved = (RESERVED as u32).wrapping_neg().to_le_bytes();
+
+    Thunk::new(|address| {
+        if cfg!(target_arch = "x86_64") {
+            [
+                // lea rsp, [rsp - RESERVED]
+                &[0x48, 0x8D, 0xA4, 0x24][..],
+                &reserved,
+                // push [rip+14]
+                &[0xFF, 0x35, 0x0E, 0x00, 0x00, 0x00],
+                // jmp [rip+0]
+                &[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00],
+                &(entry as u64).to_le_bytes(),
+                &(state as u64).to_le_bytes(),
+            ]
+            .concat()
+        } else {
+            [
+                // lea esp, [esp - RESERVED]
+                &[0x8D, 0xA4, 0x24][..],
+                &reserved,
+                // push state
+                &[0x68],
+                &(state as u32).to_le_bytes(),
+                // jmp entry
+                &[0xE9],
+                &(entry.wrapping_sub(address + 17) as u32).to_le_bytes(),
+            ]
+            .concat()
+        }
+    })
+}
+
+/// A hook on an instruction in the middle of a function, which calls a closure with the registers at that
+/// instruction before the instruction runs.
+///
+/// The closure can change the registers, including the flags and the stack pointer, and the function resumes with
+/// the changed registers at the hooked instruction. The x87 and SSE registers are preserved across the closure.
+///
+/// The hooked instruction and the ones following it are relocated into a trampoline like the prologue of a hooked
+/// function, so the hook cannot be placed where the following bytes are the target of a branch. Use
+/// [`MinHook::analyze`](crate::MinHook::analyze) to check the instruction first.
+///
+/// The hook is removed when it is dropped, before the closure is released.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, MidHook};
+/// use std::arch::global_asm;
+///
+/// # #[cfg(target_arch = "x86_64")]
+/// global_asm!(
+///     ".globl add_ten",
+///     "add_ten:",
+///     "xor eax, eax",
+///     ".globl add_ten_add",
+///     "add_ten_add:",
+///     "add eax, 10",
+///     "ret",
+///     "int3",
+///     "int3",
+/// );
+///
+/// # #[cfg(target_arch = "x86_64")]
+/// unsafe extern "C" {
+///     fn add_ten() -> i32;
+///     fn add_ten_add();
+/// }
+///
+/// # #[cfg(target_arch = "x86_64")]
+/// fn main() -> Result<(), HookError> {
+///     let hook = unsafe {
+///         MidHook::new(
+///             add_ten_add as *mut _,
+///             Box::new(|context| context.rax = 100),
+///         )?
+///     };
+///     unsafe { hook.enable()? };
+///
+///     // The closure runs before `add eax, 10`.
+///     assert_eq!(unsafe { add_ten() }, 110);
+///
+///     Ok(())
+/// }
+/// # #[cfg(not(target_arch = "x86_64"))]
+/// # fn main() {}
+/// ```
+pub struct MidHook {
+    hook: ThunkHook<*mut c_void, BoundThunk<MidState>>,
+}
+
+impl MidHook {
+    /// Creates a hook on the instruction at `address` that calls `callback`. The hook is created disabled.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`](crate::MinHook::create_hook). `address` must be the start of an instruction, and
+    /// the hook must not be dropped while another thread may still be executing the closure.
+    pub unsafe fn new(
+        address: *mut c_void,
+        callback: Box<dyn Fn(&mut Context) + Send + Sync>,
+    ) -> Result<Self, HookError> {
+        let state = MidState {
+            callback: Box::new(move |context, trampoline| {
+                callback(context);
+                trampoline
+            }),
+            resume: AtomicUsize::new(0),
+        };
+
+        let thunk = BoundThunk::new(state, mid_thunk).ok_or_else(|| {
+            HookError::new(
+                Operation::CreateHook,
+                Some(HookTarget::Address(address as usize)),
+                ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_ALLOC),
+            )
+        })?;
+
+        let guard = unsafe { HookGuard::new(address, thunk.address() as *mut c_void)? };
+        thunk
+            .state()
+            .resume
+            .store(guard.original() as usize, Ordering::Release);
+
+        Ok(Self {
+            hook: ThunkHook::new(guard, thunk),
+        })
+    }
+
+    /// Returns the hooked instruction.
+    pub fn address(&self) -> *mut c_void {
+        self.hook.guard().target()
+    }
+
+    /// Returns the trampoline, which runs the relocated instructions and jumps back to the function.
+    pub fn trampoline(&self) -> *mut c_void {
+        self.hook.guard().original()
+    }
+
+    /// Enables the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::enable_hook`](crate::MinHook::enable_hook).
+    pub unsafe fn enable(&self) -> Result<(), HookError> {
+        unsafe { self.hook.guard().enable() }
+    }
+
+    /// Disables the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::disable_hook`](crate::MinHook::disable_hook).
+    pub unsafe fn disable(&self) -> Result<(), HookError> {
+        unsafe { self.hook.guard().disable() }
+    }
+
+    /// Releases the hook without removing it. The closure and its thunk stay alive for the rest of the process.
+    pub fn leak(self) -> Hook<*mut c_void> {
+        self.hook.leak()
+    }
+}
+
+impl fmt::Debug for MidHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MidHook")
+            .field("address", &self.address())
+            .field("trampoline", &self.trampoline())
+            .field("thunk", &(self.hook.thunks().address() as *const c_void))
+            .finish()
+    }
+}
@@ -0,0 +1,1105 @@
+//! Linux implementation of the operating system primitives, based on `/proc/self/maps`, `mmap` and `mprotect`, and on
+//! signals to suspend threads.
+
+use super::{Import, LoadedModule};
+use crate::MH_STATUS;
+use std::{
+    cell::Cell,
+    env,
+    ffi::{CStr, CString, c_int, c_void},
+    fs, mem,
+    path::{Path, PathBuf},
+    ptr::{null, null_mut},
+    slice,
+    sync::{
+        OnceLock,
+        atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// The lowest and highest addresses that memory can be mapped at.
+const APPLICATION_ADDRESS_RANGE: (usize, usize) = (0x1_0000, 0x7FFF_FFFF_0000);
+
+/// A mapping listed in `/proc/self/maps`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Region {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    protection: i32,
+}
+
+impl Region {
+    pub(crate) fn mapped(&self) -> bool {
+        true
+    }
+
+    pub(crate) fn readable(&self) -> bool {
+        self.protection & libc::PROT_READ != 0
+    }
+
+    pub(crate) fn writable(&self) -> bool {
+        self.protection & libc::PROT_WRITE != 0
+    }
+
+    pub(crate) fn executable(&self) -> bool {
+        self.protection & libc::PROT_EXEC != 0
+    }
+
+    /// Parses the address range and the permissions at the start of a line of `/proc/self/maps`.
+    fn parse(line: &[u8]) -> Option<Self> {
+        let mut fields = line.split(|&byte| byte == b' ');
+        let range = std::str::from_utf8(fields.next()?).ok()?;
+        let (start, end) = range.split_once('-')?;
+        let permissions = fields.next()?;
+
+        let mut protection = libc::PROT_NONE;
+        for (flag, prot) in [
+            (b'r', libc::PROT_READ),
+            (b'w', libc::PROT_WRITE),
+            (b'x', libc::PROT_EXEC),
+        ] {
+            if permissions.contains(&flag) {
+                protection |= prot;
+            }
+        }
+
+        Some(Self {
+            start: usize::from_str_radix(start, 16).ok()?,
+            end: usize::from_str_radix(end, 16).ok()?,
+            protection,
+        })
+    }
+}
+
+/// Calls `f` with each mapping of the current process in order of address, until it returns `false`.
+///
+/// `/proc/self/maps` is read into a buffer on the stack, so this does not allocate and can be called while other
+/// threads are suspended, one of which may hold the lock of the allocator.
+fn for_each_region(mut f: impl FnMut(Region) -> bool) {
+    let fd = unsafe {
+        libc::open(
+            c"/proc/self/maps".as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return;
+    }
+
+    let mut buffer = [0u8; 4096];
+    let mut len = 0;
+    // Set while skipping the rest of a line that did not fit into the buffer.
+    let mut skipping = false;
+    'read: loop {
+        let read = unsafe { libc::read(fd, buffer[len..].as_mut_ptr().cast(), buffer.len() - len) };
+        if read < 0 && unsafe { *libc::__errno_location() } == libc::EINTR {
+            continue;
+        }
+        if read <= 0 {
+            break;
+        }
+        len += read as usize;
+
+        let mut start = 0;
+        while let Some(newline) = buffer[start..len].iter().position(|&byte| byte == b'\n') {
+            let line = &buffer[start..start + newline];
+            start += newline + 1;
+            if mem::take(&mut skipping) {
+                continue;
+            }
+            if !Region::parse(line).is_none_or(&mut f) {
+                break 'read;
+            }
+        }
+
+        // The line is longer than the buffer because of its path, which is not needed.
+        if start == 0 && len == buffer.len() {
+            if !skipping && !Region::parse(&buffer).is_none_or(&mut f) {
+                break;
+            }
+            skipping = true;
+            len = 0;
+            continue;
+        }
+
+        buffer.copy_within(start..len, 0);
+        len -= start;
+    }
+
+    unsafe { libc::close(fd) };
+}
+
+/// Returns all mappings of the current process, sorted by address.
+pub(crate) fn regions() -> Vec<Region> {
+    let mut regions = Vec::new();
+    for_each_region(|region| {
+        regions.push(region);
+        true
+    });
+    regions
+}
+
+/// Returns the mapping containing `address`.
+pub(crate) fn query(address: usize) -> Option<Region> {
+    let mut found = None;
+    for_each_region(|region| {
+        if (region.start..region.end).contains(&address) {
+            found = Some(region);
+        }
+        found.is_none() && region.start <= address
+    });
+    found
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+pub(crate) fn application_address_range() -> (usize, usize) {
+    APPLICATION_ADDRESS_RANGE
+}
+
+/// Finds the closest unmapped range of `size` bytes below `address`.
+pub(crate) fn find_prev_free_region(address: usize, min_addr: usize, size: usize) -> Option<usize> {
+    let regions = regions();
+    let granularity = page_size();
+
+    // Round down to the allocation granularity.
+    let mut try_addr = (address - address % granularity).checked_sub(size)?;
+
+    while try_addr >= min_addr {
+        match regions
+            .iter()
+            .find(|region| region.start < try_addr + size && try_addr < region.end)
+        {
+            None => return Some(try_addr),
+            Some(region) => {
+                try_addr = region.start.checked_sub(size)?;
+                try_addr -= try_addr % granularity;
+            }
+        }
+    }
+
+    None
+}
+
+/// Finds the closest unmapped range of `size` bytes above `address`.
+pub(crate) fn find_next_free_region(address: usize, max_addr: usize, size: usize) -> Option<usize> {
+    let regions = regions();
+    let granularity = page_size();
+
+    // Round down to the allocation granularity.
+    let mut try_addr = address - address % granularity + granularity;
+
+    while try_addr <= max_addr {
+        match regions
+            .iter()
+            .find(|region| region.start < try_addr + size && try_addr < region.end)
+        {
+            None => return Some(try_addr),
+            Some(region) => try_addr = region.end.next_multiple_of(granularity),
+        }
+    }
+
+    None
+}
+
+/// Maps `size` bytes of readable, writable and executable memory, at exactly `address` if given.
+pub(crate) unsafe fn allocate(address: Option<usize>, size: usize) -> Option<usize> {
+    let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+    if address.is_some() {
+        flags |= libc::MAP_FIXED_NOREPLACE;
+    }
+
+    let mapping = unsafe {
+        libc::mmap(
+            address.map_or(null_mut(), |address| address as *mut c_void),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            flags,
+            -1,
+            0,
+        )
+    };
+    if mapping == libc::MAP_FAILED {
+        return None;
+    }
+
+    // Kernels older than 4.17 treat MAP_FIXED_NOREPLACE as a hint.
+    if address.is_some_and(|address| address != mapping as usize) {
+        unsafe { libc::munmap(mapping, size) };
+        return None;
+    }
+
+    Some(mapping as usize)
+}
+
+/// Unmaps memory returned by [`allocate`].
+pub(crate) unsafe fn free(address: usize, size: usize) {
+    unsafe { libc::munmap(address as *mut c_void, size) };
+}
+
+/// The most mappings a range made writable by `ProtectGuard` may span.
+const MAX_PROTECTED_REGIONS: usize = 4;
+
+/// Makes a range of code writable, restoring the original protection when dropped. It does not allocate, so code can
+/// be patched while other threads are suspended.
+pub(crate) struct ProtectGuard {
+    regions: [(usize, usize, i32); MAX_PROTECTED_REGIONS],
+    len: usize,
+}
+
+impl ProtectGuard {
+    pub(crate) fn new(address: usize, size: usize) -> Option<Self> {
+        let page_size = page_size();
+        let start = address - address % page_size;
+        let end = (address + size).next_multiple_of(page_size);
+
+        let mut guard = Self {
+            regions: [(0, 0, 0); MAX_PROTECTED_REGIONS],
+            len: 0,
+        };
+        let mut overflow = false;
+        for_each_region(|region| {
+            if region.start < end && start < region.end {
+                if guard.len == MAX_PROTECTED_REGIONS {
+                    overflow = true;
+                    return false;
+                }
+                guard.regions[guard.len] = (
+                    region.start.max(start),
+                    region.end.min(end),
+                    region.protection,
+                );
+                guard.len += 1;
+            }
+            region.end < end
+        });
+
+        // Every page in the range has to be mapped.
+        if overflow
+            || guard.regions[..guard.len]
+                .iter()
+                .map(|(start, end, _)| end - start)
+                .sum::<usize>()
+                != end - start
+        {
+            return None;
+        }
+
+        let status = unsafe {
+            libc::mprotect(
+                start as *mut c_void,
+                end - start,
+                libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            )
+        };
+        (status == 0).then_some(guard)
+    }
+}
+
+impl Drop for ProtectGuard {
+    fn drop(&mut self) {
+        for &(start, end, protection) in &self.regions[..self.len] {
+            unsafe { libc::mprotect(start as *mut c_void, end - start, protection) };
+        }
+    }
+}
+
+/// Instruction and data caches are coherent on x86, so there is nothing to flush.
+pub(crate) fn flush_instruction_cache(_address: usize, _size: usize) {}
+
+/// Looks up an exported function of an already loaded shared object.
+///
+/// The symbol may name a version, as in `malloc@GLIBC_2.2.5`, or `malloc@@GLIBC_2.2.5` for the default version. Such
+/// symbols are looked up in the dynamic symbol table of the module itself, where the `@@` form only matches the default
+/// version, not a hidden one of the same name.
+///
+/// Other symbols are looked up with `dlsym` on the handle of the module, which also searches its dependencies. The
+/// empty name for the main program opens the handle with `dlopen(NULL)`, which searches the global scope, i.e. the main
+/// program and every library loaded with it or with `RTLD_GLOBAL`. Only if the loader does not know the module by that
+/// name is the dynamic symbol table of the module searched instead.
+pub(crate) fn find_symbol(module: &str, symbol: &CStr) -> Result<usize, MH_STATUS> {
+    let symbol = symbol
+        .to_str()
+        .map_err(|_| MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND)?;
+    let (name, version) = match symbol.split_once('@') {
+        Some((name, version)) => match version.strip_prefix('@') {
+            Some(version) => (name, Some((version, true))),
+            None => (name, Some((version, false))),
+        },
+        None => (symbol, None),
+    };
+    let name = CString::new(name).map_err(|_| MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND)?;
+
+    if version.is_none() {
+        if let Some(handle) = Handle::open(module) {
+            return handle
+                .symbol(&name, None)
+                .ok_or(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+        }
+    }
+
+    let module = modules()
+        .into_iter()
+        .find(|candidate| candidate.matches(module))
+        .ok_or(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND)?;
+    let (address, indirect) = module
+        .find_symbol(name.to_bytes(), version)
+        .ok_or(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND)?;
+
+    // The address of an indirect function is its resolver, so the loader has to pick the implementation.
+    if indirect {
+        return Handle::open(&module.path)
+            .and_then(|handle| handle.symbol(&name, version.map(|(version, _)| version)))
+            .ok_or(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+    }
+
+    Ok(address)
+}
+
+/// Returns the address ranges of the executable segments of a loaded module. The empty name stands for the main
+/// program.
+pub(crate) fn code_ranges(module: &str) -> Result<Vec<(usize, usize)>, MH_STATUS> {
+    modules()
+        .into_iter()
+        .find(|candidate| candidate.matches(module))
+        .map(|module| module.code)
+        .ok_or(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND)
+}
+
+/// Returns the handle of a loaded module, which is the same as the one returned by `dlopen`.
+pub(crate) fn module_handle(module: &str) -> Option<usize> {
+    Handle::open(module).map(|handle| handle.0 as usize)
+}
+
+thread_local! {
+    /// Set while this thread looks up a module through the dynamic loader, whose functions may be hooked.
+    static LOOKUP: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns `true` if this thread is looking up a module, so calls to the dynamic loader come from MinHook itself.
+pub(crate) fn is_looking_up() -> bool {
+    LOOKUP.get()
+}
+
+/// Calls the dynamic loader to look up a module.
+fn lookup<T>(f: impl FnOnce() -> T) -> T {
+    let previous = LOOKUP.replace(true);
+    let result = f();
+    LOOKUP.set(previous);
+    result
+}
+
+/// A reference to a module that was already loaded, released when dropped.
+struct Handle(*mut c_void);
+
+impl Handle {
+    /// Opens a module by the name it was loaded with, without loading it. The empty name stands for the main program.
+    fn open(module: &str) -> Option<Self> {
+        let handle = if module.is_empty() {
+            lookup(|| unsafe { libc::dlopen(null_mut(), libc::RTLD_LAZY) })
+        } else {
+            let module = CString::new(module).ok()?;
+            lookup(|| unsafe { libc::dlopen(module.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD) })
+        };
+        (!handle.is_null()).then(|| Self(handle))
+    }
+
+    /// Looks up a symbol in the module and its dependencies. The module was already loaded before, so the symbol stays
+    /// valid after the handle is released.
+    fn symbol(&self, name: &CStr, version: Option<&str>) -> Option<usize> {
+        let address = match version {
+            #[cfg(target_env = "gnu")]
+            Some(version) => {
+                let version = CString::new(version).ok()?;
+                unsafe { libc::dlvsym(self.0, name.as_ptr(), version.as_ptr()) }
+            }
+            _ => unsafe { libc::dlsym(self.0, name.as_ptr()) },
+        };
+        (!address.is_null()).then_some(address as usize)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        lookup(|| unsafe { libc::dlclose(self.0) });
+    }
+}
+
+// Tags of the entries of the dynamic section.
+const DT_NULL: isize = 0;
+const DT_PLTRELSZ: isize = 2;
+const DT_HASH: isize = 4;
+const DT_STRTAB: isize = 5;
+const DT_SYMTAB: isize = 6;
+const DT_RELA: isize = 7;
+const DT_RELASZ: isize = 8;
+const DT_RELAENT: isize = 9;
+const DT_SYMENT: isize = 11;
+const DT_REL: isize = 17;
+const DT_RELSZ: isize = 18;
+const DT_RELENT: isize = 19;
+const DT_PLTREL: isize = 20;
+const DT_JMPREL: isize = 23;
+const DT_GNU_HASH: isize = 0x6FFF_FEF5;
+const DT_VERSYM: isize = 0x6FFF_FFF0;
+const DT_VERDEF: isize = 0x6FFF_FFFC;
+const DT_VERDEFNUM: isize = 0x6FFF_FFFD;
+
+// Relocations that fill a slot with the address of a symbol. The types have the same values on x86 and x64.
+const R_GLOB_DAT: usize = 6;
+const R_JUMP_SLOT: usize = 7;
+
+// Types of symbols that can be hooked.
+const STT_FUNC: u8 = 2;
+const STT_GNU_IFUNC: u8 = 10;
+
+/// Size of `Elf32_Sym` or `Elf64_Sym`.
+const SYMBOL_SIZE: usize = if cfg!(target_pointer_width = "64") {
+    24
+} else {
+    16
+};
+
+#[cfg(target_pointer_width = "64")]
+type Phdr = libc::Elf64_Phdr;
+#[cfg(target_pointer_width = "32")]
+type Phdr = libc::Elf32_Phdr;
+
+/// An entry of the dynamic section, `Elf32_Dyn` or `Elf64_Dyn`.
+#[repr(C)]
+struct Dyn {
+    tag: isize,
+    value: usize,
+}
+
+/// The entries of a dynamic section.
+struct Dynamic(Vec<(isize, usize)>);
+
+impl Dynamic {
+    fn get(&self, tag: isize) -> Option<usize> {
+        self.0
+            .iter()
+            .find(|&&(entry, _)| entry == tag)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// A loaded ELF module, as reported by `dl_iterate_phdr`.
+struct Module {
+    base: usize,
+    path: String,
+    segments: Vec<(usize, usize)>,
+    /// The segments mapped executable.
+    code: Vec<(usize, usize)>,
+    dynamic: Option<usize>,
+}
+
+impl Module {
+    /// Returns `true` if the module is loaded from `name`, given as a path or a file name. The empty name stands for
+    /// the main program.
+    fn matches(&self, name: &str) -> bool {
+        self.path == name || (!name.is_empty() && Path::new(&self.path).ends_with(name))
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        self.segments
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&address))
+    }
+
+    /// Returns the entries of the dynamic section.
+    fn dynamic(&self) -> Dynamic {
+        let mut entries = Vec::new();
+        let Some(mut entry) = self.dynamic.map(|dynamic| dynamic as *const Dyn) else {
+            return Dynamic(entries);
+        };
+
+        loop {
+            let Dyn { tag, value } = unsafe { entry.read() };
+            if tag == DT_NULL {
+                return Dynamic(entries);
+            }
+            entries.push((tag, value));
+            entry = entry.wrapping_add(1);
+        }
+    }
+
+    /// Looks up a function defined by the module in its dynamic symbol table, with the given version or the default
+    /// one. A version that is marked as the default one only matches if it is not hidden. Returns its address, and
+    /// whether it is an indirect function whose address is the resolver.
+    fn find_symbol(&self, name: &[u8], version: Option<(&str, bool)>) -> Option<(usize, bool)> {
+        let dynamic = self.dynamic();
+        let symbols = self.pointer(dynamic.get(DT_SYMTAB)?);
+        let strings = self.pointer(dynamic.get(DT_STRTAB)?);
+        let symbol_size = dynamic.get(DT_SYMENT).unwrap_or(SYMBOL_SIZE);
+        let versions = dynamic
+            .get(DT_VERSYM)
+            .map(|versions| self.pointer(versions));
+        let string =
+            |offset: u32| unsafe { CStr::from_ptr((strings + offset as usize) as *const _) };
+
+        let version = match version {
+            Some((version, default)) => Some((
+                self.version_index(&dynamic, strings, version.as_bytes())?,
+                default,
+            )),
+            None => None,
+        };
+
+        // The first symbol is always the undefined symbol.
+        for index in 1..self.symbol_count(&dynamic)? {
+            let symbol = symbols + index * symbol_size;
+
+            #[cfg(target_pointer_width = "64")]
+            let (name_offset, info, section, value) = unsafe {
+                (
+                    *(symbol as *const u32),
+                    *((symbol + 4) as *const u8),
+                    *((symbol + 6) as *const u16),
+                    *((symbol + 8) as *const u64) as usize,
+                )
+            };
+            #[cfg(target_pointer_width = "32")]
+            let (name_offset, info, section, value) = unsafe {
+                (
+                    *(symbol as *const u32),
+                    *((symbol + 12) as *const u8),
+                    *((symbol + 14) as *const u16),
+                    *((symbol + 4) as *const u32) as usize,
+                )
+            };
+
+            let kind = info & 0xF;
+            if section == 0 || (kind != STT_FUNC && kind != STT_GNU_IFUNC) {
+                continue;
+            }
+            if string(name_offset).to_bytes() != name {
+                continue;
+            }
+
+            // The highest bit of the version index marks versions other than the default one.
+            let index = versions.map(|versions| unsafe { *((versions + index * 2) as *const u16) });
+            let matches = match (version, index) {
+                (Some((version, default)), Some(index)) => {
+                    index & 0x7FFF == version && (!default || index & 0x8000 == 0)
+                }
+                (Some(_), None) => false,
+                (None, index) => index.is_none_or(|index| index & 0x8000 == 0),
+            };
+            if matches {
+                return Some((self.base + value, kind == STT_GNU_IFUNC));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the index of a version defined by the module.
+    fn version_index(&self, dynamic: &Dynamic, strings: usize, name: &[u8]) -> Option<u16> {
+        let mut definition = self.pointer(dynamic.get(DT_VERDEF)?);
+
+        // Each `Elf_Verdef` is followed by `Elf_Verdaux` entries, the first of which names the version.
+        for _ in 0..dynamic.get(DT_VERDEFNUM)? {
+            let (index, aux, next) = unsafe {
+                (
+                    *((definition + 4) as *const u16),
+                    *((definition + 12) as *const u32),
+                    *((definition + 16) as *const u32),
+                )
+            };
+            let version = unsafe { *((definition + aux as usize) as *const u32) };
+            let version = unsafe { CStr::from_ptr((strings + version as usize) as *const _) };
+            if version.to_bytes() == name {
+                return Some(index);
+            }
+            definition += next as usize;
+        }
+
+        None
+    }
+
+    /// Returns the number of entries in the dynamic symbol table, which is only recorded by the hash tables.
+    fn symbol_count(&self, dynamic: &Dynamic) -> Option<usize> {
+        if let Some(hash) = dynamic.get(DT_HASH) {
+            // The number of chains equals the number of symbols.
+            return Some(unsafe { *((self.pointer(hash) + 4) as *const u32) } as usize);
+        }
+
+        // The GNU hash table starts with the number of buckets, the index of the first hashed symbol and the size of
+        // the bloom filter, followed by the bloom filter, the buckets and the chains. The chain of the last bucket
+        // ends with the last symbol, marked by the lowest bit of its hash.
+        let hash = self.pointer(dynamic.get(DT_GNU_HASH)?);
+        let word = |index: usize| unsafe { *((hash as *const u32).add(index)) } as usize;
+        let (bucket_count, offset, bloom_size) = (word(0), word(1), word(2));
+        let buckets = 4 + bloom_size * size_of::<usize>() / 4;
+        let chains = buckets + bucket_count;
+
+        let Some(mut last) = (0..bucket_count).map(|bucket| word(buckets + bucket)).max() else {
+            return Some(offset);
+        };
+        if last < offset {
+            return Some(offset);
+        }
+        while word(chains + last - offset) & 1 == 0 {
+            last += 1;
+        }
+        Some(last + 1)
+    }
+
+    /// Returns the address a pointer of the dynamic section refers to. The dynamic loader of glibc relocates them in
+    /// place, other loaders leave them relative to the base address.
+    fn pointer(&self, value: usize) -> usize {
+        if value < self.base {
+            value + self.base
+        } else {
+            value
+        }
+    }
+}
+
+/// Returns the loaded modules, starting with the main program.
+fn modules() -> Vec<Module> {
+    unsafe extern "C" fn callback(
+        info: *mut libc::dl_phdr_info,
+        _size: usize,
+        modules: *mut c_void,
+    ) -> c_int {
+        let (info, modules) = unsafe { (&*info, &mut *(modules as *mut Vec<Module>)) };
+
+        let base = info.dlpi_addr as usize;
+        let path = if info.dlpi_name.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(info.dlpi_name) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        let headers: &[Phdr] = match info.dlpi_phnum {
+            0 => &[],
+            count => unsafe { slice::from_raw_parts(info.dlpi_phdr, count.into()) },
+        };
+
+        let mut module = Module {
+            base,
+            path,
+            segments: Vec::new(),
+            code: Vec::new(),
+            dynamic: None,
+        };
+        for header in headers {
+            let (start, end) = (
+                base + header.p_vaddr as usize,
+                base + (header.p_vaddr + header.p_memsz) as usize,
+            );
+            match header.p_type {
+                libc::PT_LOAD => {
+                    module.segments.push((start, end));
+                    if header.p_flags & libc::PF_X != 0 {
+                        module.code.push((start, end));
+                    }
+                }
+                libc::PT_DYNAMIC => module.dynamic = Some(start),
+                _ => {}
+            }
+        }
+
+        modules.push(module);
+        0
+    }
+
+    let mut modules = Vec::new();
+    unsafe {
+        libc::dl_iterate_phdr(
+            Some(callback),
+            &mut modules as *mut Vec<Module> as *mut c_void,
+        )
+    };
+    modules
+}
+
+/// Returns the loaded modules, starting with the main program, whose path is that of the executable.
+pub(crate) fn loaded_modules() -> Vec<LoadedModule> {
+    modules()
+        .into_iter()
+        .filter_map(|module| {
+            let start = module.segments.iter().map(|&(start, _)| start).min()?;
+            let end = module.segments.iter().map(|&(_, end)| end).max()?;
+            let path = match module.path.is_empty() {
+                true => env::current_exe().unwrap_or_default(),
+                false => PathBuf::from(module.path),
+            };
+
+            Some(LoadedModule {
+                base: start,
+                size: end - start,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Finds the GOT slots through which a loaded module calls `symbol`, which are filled by its `JUMP_SLOT` and
+/// `GLOB_DAT` relocations.
+pub(crate) fn find_import(module: &str, symbol: &CStr) -> Result<Import, MH_STATUS> {
+    let module = modules()
+        .into_iter()
+        .find(|candidate| candidate.matches(module))
+        .ok_or(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND)?;
+
+    let dynamic = module.dynamic();
+    let get = |tag| dynamic.get(tag);
+
+    let (Some(symbols), Some(strings)) = (get(DT_SYMTAB), get(DT_STRTAB)) else {
+        return Err(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+    };
+    let (symbols, strings) = (module.pointer(symbols), module.pointer(strings));
+    let symbol_size = get(DT_SYMENT).unwrap_or(SYMBOL_SIZE);
+
+    // Both relocation formats start with the offset and the info, so only the entry size differs.
+    let mut tables = Vec::new();
+    if let (Some(table), Some(size)) = (get(DT_JMPREL), get(DT_PLTRELSZ)) {
+        let entry_size = if get(DT_PLTREL) == Some(DT_RELA as usize) {
+            get(DT_RELAENT).unwrap_or(3 * size_of::<usize>())
+        } else {
+            get(DT_RELENT).unwrap_or(2 * size_of::<usize>())
+        };
+        tables.push((table, size, entry_size));
+    }
+    if let (Some(table), Some(size)) = (get(DT_RELA), get(DT_RELASZ)) {
+        tables.push((
+            table,
+            size,
+            get(DT_RELAENT).unwrap_or(3 * size_of::<usize>()),
+        ));
+    }
+    if let (Some(table), Some(size)) = (get(DT_REL), get(DT_RELSZ)) {
+        tables.push((
+            table,
+            size,
+            get(DT_RELENT).unwrap_or(2 * size_of::<usize>()),
+        ));
+    }
+
+    let mut slots = Vec::new();
+    for (table, size, entry_size) in tables {
+        let table = module.pointer(table);
+        for entry in (table..table + size).step_by(entry_size.max(1)) {
+            let (offset, info) = unsafe {
+                (
+                    *(entry as *const usize),
+                    *((entry + size_of::<usize>()) as *const usize),
+                )
+            };
+
+            #[cfg(target_pointer_width = "64")]
+            let (index, kind) = (info >> 32, info & 0xFFFF_FFFF);
+            #[cfg(target_pointer_width = "32")]
+            let (index, kind) = (info >> 8, info & 0xFF);
+
+            if index == 0 || (kind != R_GLOB_DAT && kind != R_JUMP_SLOT) {
+                continue;
+            }
+
+            let name = unsafe { *((symbols + index * symbol_size) as *const u32) };
+            let name = unsafe { CStr::from_ptr((strings + name as usize) as *const _) };
+            if name == symbol {
+                slots.push(module.base + offset);
+            }
+        }
+    }
+
+    // The table of lazily bound relocations may be part of the other tables.
+    slots.sort_unstable();
+    slots.dedup();
+
+    let &first = slots
+        .first()
+        .ok_or(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND)?;
+    let mut function = unsafe { *(first as *const usize) };
+
+    // A lazily bound slot points back into the PLT of the module until the first call resolves it.
+    if module.contains(function) {
+        let resolved = unsafe { libc::dlsym(libc::RTLD_DEFAULT, symbol.as_ptr()) };
+        if !resolved.is_null() {
+            function = resolved as usize;
+        }
+    }
+
+    Ok(Import {
+        module: module.dynamic.unwrap_or(module.base),
+        slots,
+        function,
+    })
+}
+
+/// The most threads that can be suspended at once. Threads beyond it keep running while code is patched.
+const MAX_FROZEN_THREADS: usize = 1024;
+
+/// How long to wait for the other threads to stop. Threads that do not run in time keep running while code is patched.
+const FREEZE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A thread to suspend, and the state of its suspension.
+struct FrozenSlot {
+    /// The thread, or `0` once it is known to have exited.
+    tid: AtomicI32,
+    /// `0` if unused, `waiting(generation)` until the thread stops, then the address of its `ucontext_t`.
+    state: AtomicUsize,
+}
+
+static FROZEN: [FrozenSlot; MAX_FROZEN_THREADS] = [const {
+    FrozenSlot {
+        tid: AtomicI32::new(0),
+        state: AtomicUsize::new(0),
+    }
+}; MAX_FROZEN_THREADS];
+
+/// The number of slots in use by the current freeze.
+static FROZEN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Incremented to resume the suspended threads, which wait on it with a futex.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// The state of a slot whose thread has not stopped yet. It is odd, unlike the address of a context, and tied to the
+/// generation, so a signal handled after its freeze ended does not stop the thread.
+fn waiting(generation: u32) -> usize {
+    ((generation as usize) << 1) | 1
+}
+
+/// The signal that suspends other threads. Real-time signals are not used by the Rust standard library, and glibc
+/// reserves the lowest ones.
+fn suspend_signal() -> c_int {
+    libc::SIGRTMAX() - 1
+}
+
+fn gettid() -> libc::pid_t {
+    unsafe { libc::syscall(libc::SYS_gettid) as libc::pid_t }
+}
+
+fn tgkill(tid: libc::pid_t, signal: c_int) -> bool {
+    unsafe { libc::syscall(libc::SYS_tgkill, libc::getpid(), tid, signal) == 0 }
+}
+
+/// Parks the thread that received the suspend signal until the freeze ends, after publishing its context so the
+/// instruction pointer can be moved. Only async-signal-safe calls are made.
+unsafe extern "C" fn suspend_handler(
+    _signal: c_int,
+    _info: *mut libc::siginfo_t,
+    context: *mut c_void,
+) {
+    let errno = unsafe { *libc::__errno_location() };
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let tid = gettid();
+
+    let count = FROZEN_COUNT.load(Ordering::SeqCst).min(MAX_FROZEN_THREADS);
+    let parked = FROZEN[..count].iter().any(|slot| {
+        slot.tid.load(Ordering::SeqCst) == tid
+            && slot
+                .state
+                .compare_exchange(
+                    waiting(generation),
+                    context as usize,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+    });
+
+    if parked {
+        while GENERATION.load(Ordering::SeqCst) == generation {
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    GENERATION.as_ptr(),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    generation,
+                    null::<libc::timespec>(),
+                )
+            };
+        }
+    }
+
+    unsafe { *libc::__errno_location() = errno };
+}
+
+/// Installs the handler of the suspend signal, once for the process. A handler the application installed for the
+/// signal is kept, in which case no threads are suspended.
+fn install_suspend_handler() -> bool {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    *INSTALLED.get_or_init(|| unsafe {
+        let mut previous: libc::sigaction = mem::zeroed();
+        if libc::sigaction(suspend_signal(), null(), &mut previous) != 0 {
+            return false;
+        }
+        if previous.sa_sigaction != libc::SIG_DFL {
+            warn!(
+                "Signal {} already has a handler, so other threads are not suspended while code is patched",
+                suspend_signal()
+            );
+            return false;
+        }
+
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = suspend_handler as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART | libc::SA_ONSTACK;
+        libc::sigfillset(&mut action.sa_mask);
+        libc::sigaction(suspend_signal(), &action, null_mut()) == 0
+    })
+}
+
+/// Returns `true` if the thread blocks the suspend signal, according to the signal mask in its status.
+fn blocks_suspend_signal(tid: libc::pid_t) -> bool {
+    let Ok(status) = fs::read_to_string(format!("/proc/self/task/{tid}/status")) else {
+        return false;
+    };
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("SigBlk:"))
+        .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        .is_some_and(|mask| mask & (1 << (suspend_signal() - 1)) != 0)
+}
+
+/// Returns the other threads of the process, except those that block the suspend signal and would never stop.
+fn other_threads() -> Vec<libc::pid_t> {
+    let own = gettid();
+    let threads = fs::read_dir("/proc/self/task")
+        .map(|tasks| {
+            tasks
+                .filter_map(|task| task.ok()?.file_name().to_str()?.parse().ok())
+                .filter(|&tid| tid != own)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    threads
+        .into_iter()
+        .filter(|&tid| {
+            let blocks = blocks_suspend_signal(tid);
+            if blocks {
+                warn!("Thread {} blocks the suspend signal, so it keeps running while code is patched", tid);
+            }
+            !blocks
+        })
+        .collect()
+}
+
+/// All other threads of the process, suspended until dropped.
+///
+/// Each thread is sent a real-time signal whose handler waits until the threads are resumed. Nothing is allocated
+/// while they are suspended, since one of them may hold the lock of the allocator, so the threads that did not stop in
+/// time are only reported once they are resumed.
+pub(crate) struct FrozenThreads {
+    count: usize,
+}
+
+impl FrozenThreads {
+    pub(crate) fn freeze() -> Self {
+        if !install_suspend_handler() {
+            return Self { count: 0 };
+        }
+
+        let threads = other_threads();
+        let count = threads.len().min(MAX_FROZEN_THREADS);
+        let generation = GENERATION.load(Ordering::SeqCst);
+        for (slot, &tid) in FROZEN.iter().zip(&threads[..count]) {
+            slot.tid.store(tid, Ordering::SeqCst);
+            slot.state.store(waiting(generation), Ordering::SeqCst);
+        }
+        FROZEN_COUNT.store(count, Ordering::SeqCst);
+        drop(threads);
+
+        let slots = &FROZEN[..count];
+        for slot in slots {
+            if !tgkill(slot.tid.load(Ordering::SeqCst), suspend_signal()) {
+                slot.tid.store(0, Ordering::SeqCst);
+                slot.state.store(0, Ordering::SeqCst);
+            }
+        }
+
+        let deadline = Instant::now() + FREEZE_TIMEOUT;
+        loop {
+            let mut pending = false;
+            for slot in slots {
+                if slot.state.load(Ordering::SeqCst) != waiting(generation) {
+                    continue;
+                }
+
+                // Threads that exited never handle the signal, and the others are given up on after the timeout.
+                let exited = !tgkill(slot.tid.load(Ordering::SeqCst), 0);
+                if exited {
+                    slot.tid.store(0, Ordering::SeqCst);
+                }
+                if exited || Instant::now() >= deadline {
+                    let _ = slot.state.compare_exchange(
+                        waiting(generation),
+                        0,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    );
+                } else {
+                    pending = true;
+                }
+            }
+
+            if !pending {
+                break;
+            }
+            thread::yield_now();
+        }
+
+        Self { count }
+    }
+
+    /// Moves the instruction pointer of each suspended thread to the address returned by `f`, if any.
+    pub(crate) fn move_ips(&self, mut f: impl FnMut(usize) -> Option<usize>) {
+        for slot in &FROZEN[..self.count] {
+            let state = slot.state.load(Ordering::SeqCst);
+            if state == 0 || state & 1 != 0 {
+                continue;
+            }
+
+            // The thread waits in the signal handler, so its context stays valid until it is resumed.
+            let context = unsafe { &mut *(state as *mut libc::ucontext_t) };
+            #[cfg(target_arch = "x86_64")]
+            let ip = &mut context.uc_mcontext.gregs[libc::REG_RIP as usize];
+            #[cfg(target_arch = "x86")]
+            let ip = &mut context.uc_mcontext.gregs[libc::REG_EIP as usize];
+
+            if let Some(new_ip) = f(*ip as usize) {
+                *ip = new_ip as _;
+            }
+        }
+    }
+}
+
+impl Drop for FrozenThreads {
+    fn drop(&mut self) {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                GENERATION.as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                i32::MAX,
+            )
+        };
+
+        // A signal handled from now on belongs to an old generation, so the slots are no longer read.
+        for slot in &FROZEN[..self.count] {
+            let tid = slot.tid.swap(0, Ordering::SeqCst);
+            if slot.state.swap(0, Ordering::SeqCst) == 0 && tid != 0 {
+                warn!(
+                    "Thread {} did not stop in time, so it kept running while code was patched",
+                    tid
+                );
+            }
+        }
+        FROZEN_COUNT.store(0, Ordering::SeqCst);
+    }
+}
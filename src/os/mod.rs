@@ -0,0 +1,51 @@
+//! Operating system primitives used by the hooking engine.
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(windows)]
+mod windows;
+
+#[cfg(target_os = "linux")]
+pub(crate) use linux::*;
+#[cfg(windows)]
+pub(crate) use windows::*;
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Returns `true` if `address` points into mapped, executable memory.
+pub(crate) fn is_executable(address: usize) -> bool {
+    query(address).is_some_and(|region| region.executable())
+}
+
+/// Atomically writes a pointer to memory that may be read-only, such as a relocated table. Returns `false` if the
+/// memory cannot be made writable.
+pub(crate) unsafe fn write_pointer(address: usize, value: usize) -> bool {
+    let Some(_protect) = ProtectGuard::new(address, size_of::<usize>()) else {
+        return false;
+    };
+    unsafe { AtomicUsize::from_ptr(address as *mut usize).store(value, Ordering::SeqCst) };
+    true
+}
+
+/// The slots of the import table of a module that hold one imported function, as found by `find_import`.
+pub(crate) struct Import {
+    /// Identifies the module, so hooks on the same module under different names are recognized.
+    pub(crate) module: usize,
+    /// The addresses of the slots.
+    pub(crate) slots: Vec<usize>,
+    /// The imported function.
+    pub(crate) function: usize,
+}
+
+/// A loaded module, as listed by `loaded_modules`.
+pub(crate) struct LoadedModule {
+    /// The lowest address the module is mapped at.
+    pub(crate) base: usize,
+    /// The number of bytes from the base to the end of the highest mapping of the module.
+    pub(crate) size: usize,
+    pub(crate) path: PathBuf,
+}
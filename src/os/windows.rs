@@ -0,0 +1,546 @@
+//! Windows implementation of the operating system primitives, mirroring the Win32 calls made by MinHook's C sources.
+
+use super::{Import, LoadedModule};
+use crate::MH_STATUS;
+use std::{
+    ffi::{CStr, OsString, c_char},
+    iter, mem,
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+    ptr::null,
+};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE},
+    System::{
+        Diagnostics::{
+            Debug::{CONTEXT, FlushInstructionCache, GetThreadContext, SetThreadContext},
+            ToolHelp::{
+                CreateToolhelp32Snapshot, MODULEENTRY32W, Module32FirstW, Module32NextW,
+                TH32CS_SNAPMODULE, TH32CS_SNAPTHREAD, THREADENTRY32, Thread32First, Thread32Next,
+            },
+        },
+        LibraryLoader::{GetModuleHandleW, GetProcAddress},
+        Memory::{
+            MEM_COMMIT, MEM_FREE, MEM_RELEASE, MEM_RESERVE, MEMORY_BASIC_INFORMATION, PAGE_EXECUTE,
+            PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD,
+            PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY, VirtualAlloc, VirtualFree,
+            VirtualProtect, VirtualQuery,
+        },
+        SystemInformation::{GetSystemInfo, SYSTEM_INFO},
+        Threading::{
+            GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId, OpenThread, ResumeThread,
+            SuspendThread, THREAD_GET_CONTEXT, THREAD_QUERY_INFORMATION, THREAD_SET_CONTEXT,
+            THREAD_SUSPEND_RESUME,
+        },
+    },
+};
+
+#[cfg(target_arch = "x86_64")]
+use windows_sys::Win32::System::Diagnostics::Debug::CONTEXT_CONTROL_AMD64 as CONTEXT_CONTROL;
+#[cfg(target_arch = "x86")]
+use windows_sys::Win32::System::Diagnostics::Debug::CONTEXT_CONTROL_X86 as CONTEXT_CONTROL;
+
+/// The characteristic of a section that is mapped executable.
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+const PAGE_EXECUTE_FLAGS: u32 =
+    PAGE_EXECUTE | PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+
+const PAGE_READ_FLAGS: u32 = PAGE_READONLY
+    | PAGE_READWRITE
+    | PAGE_WRITECOPY
+    | PAGE_EXECUTE_READ
+    | PAGE_EXECUTE_READWRITE
+    | PAGE_EXECUTE_WRITECOPY;
+
+const PAGE_WRITE_FLAGS: u32 =
+    PAGE_READWRITE | PAGE_WRITECOPY | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY;
+
+const THREAD_ACCESS: u32 =
+    THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT | THREAD_QUERY_INFORMATION | THREAD_SET_CONTEXT;
+
+/// A range of pages with the same state and protection, as reported by `VirtualQuery`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Region {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    state: u32,
+    protection: u32,
+}
+
+impl Region {
+    pub(crate) fn mapped(&self) -> bool {
+        self.state == MEM_COMMIT
+    }
+
+    /// Returns `true` if the pages are committed with one of `flags`, and are not guard pages.
+    fn allows(&self, flags: u32) -> bool {
+        self.mapped() && self.protection & PAGE_GUARD == 0 && self.protection & flags != 0
+    }
+
+    pub(crate) fn readable(&self) -> bool {
+        self.allows(PAGE_READ_FLAGS)
+    }
+
+    pub(crate) fn writable(&self) -> bool {
+        self.allows(PAGE_WRITE_FLAGS)
+    }
+
+    pub(crate) fn executable(&self) -> bool {
+        self.state == MEM_COMMIT && self.protection & PAGE_EXECUTE_FLAGS != 0
+    }
+}
+
+/// Returns the range of pages containing `address`.
+pub(crate) fn query(address: usize) -> Option<Region> {
+    let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+    let size = unsafe {
+        VirtualQuery(
+            address as *const _,
+            &mut mbi,
+            mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+    if size == 0 {
+        return None;
+    }
+
+    Some(Region {
+        start: mbi.BaseAddress as usize,
+        end: mbi.BaseAddress as usize + mbi.RegionSize,
+        state: mbi.State,
+        protection: mbi.Protect,
+    })
+}
+
+/// Returns all ranges of pages between the lowest and highest application addresses, sorted by address.
+pub(crate) fn regions() -> Vec<Region> {
+    let (mut address, max_address) = application_address_range();
+    let mut regions = Vec::new();
+
+    while address < max_address {
+        let Some(region) = query(address) else {
+            break;
+        };
+        address = region.end;
+        regions.push(region);
+    }
+
+    regions
+}
+
+fn system_info() -> SYSTEM_INFO {
+    let mut si: SYSTEM_INFO = unsafe { mem::zeroed() };
+    unsafe { GetSystemInfo(&mut si) };
+    si
+}
+
+pub(crate) fn application_address_range() -> (usize, usize) {
+    let si = system_info();
+    (
+        si.lpMinimumApplicationAddress as usize,
+        si.lpMaximumApplicationAddress as usize,
+    )
+}
+
+/// Finds the closest free allocation below `address`. Free regions on Windows always span at least the allocation
+/// granularity, so `_size` does not need to be checked.
+pub(crate) fn find_prev_free_region(
+    address: usize,
+    min_addr: usize,
+    _size: usize,
+) -> Option<usize> {
+    let granularity = system_info().dwAllocationGranularity as usize;
+
+    // Round down to the allocation granularity.
+    let mut try_addr = address - address % granularity;
+
+    // Start from the previous allocation granularity multiply.
+    try_addr = try_addr.checked_sub(granularity)?;
+
+    while try_addr >= min_addr {
+        let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+        if unsafe {
+            VirtualQuery(
+                try_addr as *const _,
+                &mut mbi,
+                mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        } == 0
+        {
+            break;
+        }
+
+        if mbi.State == MEM_FREE {
+            return Some(try_addr);
+        }
+
+        try_addr = (mbi.AllocationBase as usize).checked_sub(granularity)?;
+    }
+
+    None
+}
+
+/// Finds the closest free allocation above `address`.
+pub(crate) fn find_next_free_region(
+    address: usize,
+    max_addr: usize,
+    _size: usize,
+) -> Option<usize> {
+    let granularity = system_info().dwAllocationGranularity as usize;
+
+    // Round down to the allocation granularity.
+    let mut try_addr = address - address % granularity;
+
+    // Start from the next allocation granularity multiply.
+    try_addr += granularity;
+
+    while try_addr <= max_addr {
+        let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+        if unsafe {
+            VirtualQuery(
+                try_addr as *const _,
+                &mut mbi,
+                mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        } == 0
+        {
+            break;
+        }
+
+        if mbi.State == MEM_FREE {
+            return Some(try_addr);
+        }
+
+        // Round up to the next allocation granularity.
+        try_addr = (mbi.BaseAddress as usize + mbi.RegionSize).next_multiple_of(granularity);
+    }
+
+    None
+}
+
+/// Allocates `size` bytes of readable, writable and executable memory, at exactly `address` if given.
+pub(crate) unsafe fn allocate(address: Option<usize>, size: usize) -> Option<usize> {
+    let allocation = unsafe {
+        VirtualAlloc(
+            address.map_or(null(), |address| address as *const _),
+            size,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_EXECUTE_READWRITE,
+        )
+    };
+
+    (!allocation.is_null()).then_some(allocation as usize)
+}
+
+/// Releases memory returned by [`allocate`].
+pub(crate) unsafe fn free(address: usize, _size: usize) {
+    unsafe { VirtualFree(address as *mut _, 0, MEM_RELEASE) };
+}
+
+/// Makes a range of code writable, restoring the original protection when dropped.
+pub(crate) struct ProtectGuard {
+    address: usize,
+    size: usize,
+    protection: u32,
+}
+
+impl ProtectGuard {
+    pub(crate) fn new(address: usize, size: usize) -> Option<Self> {
+        let mut protection = 0;
+        let status = unsafe {
+            VirtualProtect(
+                address as *const _,
+                size,
+                PAGE_EXECUTE_READWRITE,
+                &mut protection,
+            )
+        };
+
+        (status != 0).then_some(Self {
+            address,
+            size,
+            protection,
+        })
+    }
+}
+
+impl Drop for ProtectGuard {
+    fn drop(&mut self) {
+        let mut protection = 0;
+        unsafe {
+            VirtualProtect(
+                self.address as *const _,
+                self.size,
+                self.protection,
+                &mut protection,
+            )
+        };
+    }
+}
+
+pub(crate) fn flush_instruction_cache(address: usize, size: usize) {
+    unsafe { FlushInstructionCache(GetCurrentProcess(), address as *const _, size) };
+}
+
+/// Looks up an exported function of an already loaded module.
+pub(crate) fn find_symbol(module: &str, symbol: &CStr) -> Result<usize, MH_STATUS> {
+    let module = module
+        .encode_utf16()
+        .chain(iter::once(0))
+        .collect::<Vec<_>>();
+
+    let handle = unsafe { GetModuleHandleW(module.as_ptr()) };
+    if handle.is_null() {
+        return Err(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND);
+    }
+
+    match unsafe { GetProcAddress(handle, symbol.as_ptr().cast()) } {
+        Some(function) => Ok(function as usize),
+        None => Err(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND),
+    }
+}
+
+/// Returns the handle of a loaded module, which is its base address.
+pub(crate) fn module_handle(module: &str) -> Option<usize> {
+    let module = module
+        .encode_utf16()
+        .chain(iter::once(0))
+        .collect::<Vec<_>>();
+    let handle = unsafe { GetModuleHandleW(module.as_ptr()) };
+    (!handle.is_null()).then_some(handle as usize)
+}
+
+/// Looking up a module does not call the hooked loader functions on Windows.
+pub(crate) fn is_looking_up() -> bool {
+    false
+}
+
+/// Returns the address ranges of the executable sections of a loaded module. The empty name stands for the main
+/// program.
+pub(crate) fn code_ranges(module: &str) -> Result<Vec<(usize, usize)>, MH_STATUS> {
+    let base = module_base(module).ok_or(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND)?;
+    let read_u16 = |offset: usize| unsafe { ((base + offset) as *const u16).read_unaligned() };
+    let read_u32 = |offset: usize| unsafe { ((base + offset) as *const u32).read_unaligned() };
+
+    // The section headers follow the optional header, whose size is stored in the file header.
+    let file_header = read_u32(0x3C) as usize + 4;
+    let count = read_u16(file_header + 2) as usize;
+    let sections = file_header + 20 + read_u16(file_header + 16) as usize;
+
+    Ok((0..count)
+        .map(|index| sections + index * 40)
+        .filter(|&section| read_u32(section + 36) & IMAGE_SCN_MEM_EXECUTE != 0)
+        .map(|section| {
+            let start = base + read_u32(section + 12) as usize;
+            (start, start + read_u32(section + 8) as usize)
+        })
+        .collect())
+}
+
+/// Finds the slots of the import address table of a loaded module that hold `symbol`, imported by name. The empty
+/// name stands for the main program.
+pub(crate) fn find_import(module: &str, symbol: &CStr) -> Result<Import, MH_STATUS> {
+    let base = module_base(module).ok_or(MH_STATUS::MH_ERROR_MODULE_NOT_FOUND)?;
+    let read_u32 = |offset: usize| unsafe { ((base + offset) as *const u32).read_unaligned() };
+    let read_usize = |offset: usize| unsafe { ((base + offset) as *const usize).read_unaligned() };
+
+    // The optional header follows the signature and the file header of the PE header, whose offset is stored in the
+    // DOS header. The data directories are at its end, and the import directory is the second one.
+    let optional_header = read_u32(0x3C) as usize + 24;
+    let directories = match unsafe { ((base + optional_header) as *const u16).read_unaligned() } {
+        0x20B => optional_header + 112,
+        0x10B => optional_header + 96,
+        _ => return Err(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND),
+    };
+    let mut descriptor = read_u32(directories + 8) as usize;
+
+    let mut slots = Vec::new();
+    // The import descriptors end with an empty one. Each lists the names of the imported functions and the slots
+    // they are bound to in the same order.
+    while descriptor != 0 && read_u32(descriptor + 12) != 0 {
+        let (names, addresses) = (
+            read_u32(descriptor) as usize,
+            read_u32(descriptor + 16) as usize,
+        );
+
+        // Modules bound by old linkers only keep the slots, which no longer contain the names.
+        if names != 0 {
+            for index in 0.. {
+                let name = read_usize(names + index * mem::size_of::<usize>());
+                if name == 0 {
+                    break;
+                }
+
+                // Functions imported by ordinal have the highest bit set, otherwise the name follows a 2-byte hint.
+                if name >> (usize::BITS - 1) != 0 {
+                    continue;
+                }
+                let name = unsafe { CStr::from_ptr((base + name + 2) as *const c_char) };
+                if name == symbol {
+                    slots.push(base + addresses + index * mem::size_of::<usize>());
+                }
+            }
+        }
+
+        descriptor += 20;
+    }
+
+    let &first = slots
+        .first()
+        .ok_or(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND)?;
+
+    Ok(Import {
+        module: base,
+        function: unsafe { *(first as *const usize) },
+        slots,
+    })
+}
+
+/// Returns the loaded modules, starting with the main program.
+pub(crate) fn loaded_modules() -> Vec<LoadedModule> {
+    let mut modules = Vec::new();
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return modules;
+    }
+
+    let mut me: MODULEENTRY32W = unsafe { mem::zeroed() };
+    me.dwSize = mem::size_of::<MODULEENTRY32W>() as u32;
+
+    if unsafe { Module32FirstW(snapshot, &mut me) } != 0 {
+        loop {
+            let len = me
+                .szExePath
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(me.szExePath.len());
+            modules.push(LoadedModule {
+                base: me.modBaseAddr as usize,
+                size: me.modBaseSize as usize,
+                path: PathBuf::from(OsString::from_wide(&me.szExePath[..len])),
+            });
+
+            if unsafe { Module32NextW(snapshot, &mut me) } == 0 {
+                break;
+            }
+        }
+    }
+
+    unsafe { CloseHandle(snapshot) };
+    modules
+}
+
+/// Returns the base address of a loaded module. The empty name stands for the main program.
+fn module_base(module: &str) -> Option<usize> {
+    let handle = if module.is_empty() {
+        unsafe { GetModuleHandleW(null()) }
+    } else {
+        let module = module
+            .encode_utf16()
+            .chain(iter::once(0))
+            .collect::<Vec<_>>();
+        unsafe { GetModuleHandleW(module.as_ptr()) }
+    };
+    (!handle.is_null()).then_some(handle as usize)
+}
+
+/// `GetThreadContext` requires a 16-byte aligned `CONTEXT` on x64.
+#[repr(C, align(16))]
+struct AlignedContext(CONTEXT);
+
+/// All other threads of the process, suspended until dropped.
+pub(crate) struct FrozenThreads {
+    threads: Vec<HANDLE>,
+}
+
+impl FrozenThreads {
+    pub(crate) fn freeze() -> Self {
+        let ids = enumerate_threads();
+        let mut threads = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let thread = unsafe { OpenThread(THREAD_ACCESS, 0, id) };
+            if !thread.is_null() {
+                threads.push(thread);
+            }
+        }
+
+        // A suspended thread may hold the lock of the process heap, so nothing is allocated or freed from here until
+        // the threads are resumed.
+        threads.retain(|&thread| {
+            if unsafe { SuspendThread(thread) } == u32::MAX {
+                unsafe { CloseHandle(thread) };
+                return false;
+            }
+            true
+        });
+
+        Self { threads }
+    }
+
+    /// Moves the instruction pointer of each suspended thread to the address returned by `f`, if any.
+    pub(crate) fn move_ips(&self, mut f: impl FnMut(usize) -> Option<usize>) {
+        for &thread in &self.threads {
+            let mut context: AlignedContext = unsafe { mem::zeroed() };
+            context.0.ContextFlags = CONTEXT_CONTROL;
+            if unsafe { GetThreadContext(thread, &mut context.0) } == 0 {
+                continue;
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            let ip = &mut context.0.Rip;
+            #[cfg(target_arch = "x86")]
+            let ip = &mut context.0.Eip;
+
+            if let Some(new_ip) = f(*ip as usize) {
+                *ip = new_ip as _;
+                unsafe { SetThreadContext(thread, &context.0) };
+            }
+        }
+    }
+}
+
+impl Drop for FrozenThreads {
+    fn drop(&mut self) {
+        for &thread in &self.threads {
+            unsafe {
+                ResumeThread(thread);
+                CloseHandle(thread);
+            }
+        }
+    }
+}
+
+fn enumerate_threads() -> Vec<u32> {
+    let mut threads = Vec::new();
+
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return threads;
+    }
+
+    let mut te: THREADENTRY32 = unsafe { mem::zeroed() };
+    te.dwSize = mem::size_of::<THREADENTRY32>() as u32;
+
+    if unsafe { Thread32First(snapshot, &mut te) } != 0 {
+        loop {
+            if te.dwSize as usize
+                >= mem::offset_of!(THREADENTRY32, th32OwnerProcessID) + mem::size_of::<u32>()
+                && te.th32OwnerProcessID == unsafe { GetCurrentProcessId() }
+                && te.th32ThreadID != unsafe { GetCurrentThreadId() }
+            {
+                threads.push(te.th32ThreadID);
+            }
+
+            te.dwSize = mem::size_of::<THREADENTRY32>() as u32;
+            if unsafe { Thread32Next(snapshot, &mut te) } == 0 {
+                break;
+            }
+        }
+    }
+
+    unsafe { CloseHandle(snapshot) };
+    threads
+}
@@ -0,0 +1,152 @@
+//! The loaded modules and the memory of the current process, to find out what can be hooked before creating a hook.
+//!
+//! On Linux, modules are listed with `dl_iterate_phdr` and memory regions are read from `/proc/self/maps`. On
+//! Windows, modules are listed with a ToolHelp snapshot and memory regions are queried with `VirtualQuery`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minhook::process;
+//!
+//! for module in process::modules() {
+//!     println!("{:p} {:#x} {}", module.base(), module.size(), module.path().display());
+//! }
+//!
+//! let address = return_0 as *const ();
+//! assert!(process::is_executable(address.cast()));
+//! assert!(process::module_containing(address.cast()).is_some());
+//!
+//! fn return_0() -> i32 {
+//!     0
+//! }
+//! ```
+
+use crate::os;
+use std::{
+    ffi::c_void,
+    path::{Path, PathBuf},
+};
+
+/// A module loaded into the process: the executable, or a shared library or DLL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Module {
+    base: usize,
+    size: usize,
+    path: PathBuf,
+}
+
+impl Module {
+    /// Returns the lowest address the module is mapped at. On Windows, this is its module handle.
+    pub fn base(&self) -> *mut c_void {
+        self.base as *mut c_void
+    }
+
+    /// Returns the number of bytes the module spans, from its base to the end of its highest mapping.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the path the module was loaded from. On Linux, modules without a file, such as the vDSO, only have a
+    /// name.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `true` if `address` lies within the module.
+    pub fn contains(&self, address: *const c_void) -> bool {
+        (self.base..self.base + self.size).contains(&(address as usize))
+    }
+}
+
+/// A range of mapped pages with the same protection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+    start: usize,
+    end: usize,
+    readable: bool,
+    writable: bool,
+    executable: bool,
+}
+
+impl Region {
+    fn new(region: &os::Region) -> Self {
+        Self {
+            start: region.start,
+            end: region.end,
+            readable: region.readable(),
+            writable: region.writable(),
+            executable: region.executable(),
+        }
+    }
+
+    /// Returns the first address of the region.
+    pub fn start(&self) -> *mut c_void {
+        self.start as *mut c_void
+    }
+
+    /// Returns the number of bytes in the region.
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if `address` lies within the region.
+    pub fn contains(&self, address: *const c_void) -> bool {
+        (self.start..self.end).contains(&(address as usize))
+    }
+
+    /// Returns `true` if the pages can be read.
+    pub fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    /// Returns `true` if the pages can be written without changing their protection.
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Returns `true` if the pages can be executed, so functions in them can be hooked.
+    pub fn is_executable(&self) -> bool {
+        self.executable
+    }
+}
+
+/// Returns the loaded modules, starting with the main program.
+pub fn modules() -> Vec<Module> {
+    os::loaded_modules()
+        .into_iter()
+        .map(|module| Module {
+            base: module.base,
+            size: module.size,
+            path: module.path,
+        })
+        .collect()
+}
+
+/// Returns the loaded module that `address` lies within, if any.
+pub fn module_containing(address: *const c_void) -> Option<Module> {
+    modules()
+        .into_iter()
+        .find(|module| module.contains(address))
+}
+
+/// Returns the mapped memory regions of the process, sorted by address.
+pub fn regions() -> Vec<Region> {
+    os::regions()
+        .iter()
+        .filter(|region| region.mapped())
+        .map(Region::new)
+        .collect()
+}
+
+/// Returns the mapped memory region that `address` lies within, if any.
+pub fn region(address: *const c_void) -> Option<Region> {
+    os::query(address as usize)
+        .filter(|region| region.mapped())
+        .map(|region| Region::new(&region))
+}
+
+/// Returns `true` if `address` lies within mapped, executable memory. [`MinHook::create_hook`](crate::MinHook::create_hook)
+/// checks this for the target and the detour before hooking.
+pub fn is_executable(address: *const c_void) -> bool {
+    os::is_executable(address as usize)
+}
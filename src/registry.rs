@@ -0,0 +1,139 @@
+//! The record of created hooks and their state, kept in sync with the calls made through [`MinHook`](crate::MinHook).
+
+use crate::trampoline::Target;
+use std::{
+    ffi::c_void,
+    fmt,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A hook created through [`MinHook`](crate::MinHook), as returned by [`MinHook::hooks`](crate::MinHook::hooks).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct HookInfo {
+    target: usize,
+    detour: usize,
+    trampoline: usize,
+    enabled: bool,
+    queue_enable: bool,
+}
+
+impl HookInfo {
+    /// Returns the target function.
+    pub fn target(&self) -> *mut c_void {
+        self.target as *mut c_void
+    }
+
+    /// Returns the detour function.
+    pub fn detour(&self) -> *mut c_void {
+        self.detour as *mut c_void
+    }
+
+    /// Returns the trampoline that calls the original function.
+    pub fn trampoline(&self) -> *mut c_void {
+        self.trampoline as *mut c_void
+    }
+
+    /// Returns `true` if the hook is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the state [`MinHook::apply_queued`](crate::MinHook::apply_queued) will change the hook to, or `None` if
+    /// no change is queued.
+    pub fn queued(&self) -> Option<bool> {
+        (self.queue_enable != self.enabled).then_some(self.queue_enable)
+    }
+}
+
+impl fmt::Debug for HookInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HookInfo")
+            .field("target", &self.target())
+            .field("detour", &self.detour())
+            .field("trampoline", &self.trampoline())
+            .field("enabled", &self.enabled)
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+/// The created hooks in the order they were created. It is locked while the engine is called, so the record changes in
+/// the same order as the engine state. A target of 0 stands for all hooks, like a null target does for the engine.
+pub(crate) struct Registry {
+    hooks: Vec<HookInfo>,
+    /// The original code of the target functions, which is read when the hooks are created and still disabled.
+    prologues: Vec<Target>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    hooks: Vec::new(),
+    prologues: Vec::new(),
+});
+
+pub(crate) fn lock() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Registry {
+    pub(crate) fn hooks(&self) -> &[HookInfo] {
+        &self.hooks
+    }
+
+    pub(crate) fn get(&self, target: usize) -> Option<&HookInfo> {
+        self.hooks.iter().find(|hook| hook.target == target)
+    }
+
+    /// Returns the original code of the target function of a hook.
+    pub(crate) fn prologue(&self, target: usize) -> Option<&Target> {
+        self.prologues
+            .iter()
+            .find(|prologue| prologue.address == target)
+    }
+
+    pub(crate) fn insert(&mut self, target: usize, detour: usize, trampoline: usize) {
+        self.prologues.extend(Target::read(target));
+        self.hooks.push(HookInfo {
+            target,
+            detour,
+            trampoline,
+            enabled: false,
+            queue_enable: false,
+        });
+    }
+
+    pub(crate) fn remove(&mut self, target: usize) {
+        self.hooks.retain(|hook| hook.target != target);
+        self.prologues.retain(|prologue| prologue.address != target);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.hooks.clear();
+        self.prologues.clear();
+    }
+
+    /// Records that hooks were enabled or disabled, which also settles their queued state.
+    pub(crate) fn set_enabled(&mut self, target: usize, enable: bool) {
+        for hook in self.matching(target) {
+            hook.enabled = enable;
+            hook.queue_enable = enable;
+        }
+    }
+
+    pub(crate) fn queue(&mut self, target: usize, enable: bool) {
+        for hook in self.matching(target) {
+            hook.queue_enable = enable;
+        }
+    }
+
+    pub(crate) fn apply_queued(&mut self) {
+        for hook in &mut self.hooks {
+            hook.enabled = hook.queue_enable;
+        }
+    }
+
+    fn matching(&mut self, target: usize) -> impl Iterator<Item = &mut HookInfo> {
+        self.hooks
+            .iter_mut()
+            .filter(move |hook| target == 0 || hook.target == target)
+    }
+}
@@ -0,0 +1,171 @@
+//! Byte-pattern scanning of the code of loaded modules, to find functions that are not exported.
+
+use crate::{ErrorKind, HookError, HookTarget, MH_STATUS, Operation, os};
+use std::{ffi::c_void, slice};
+
+/// A byte pattern with wildcards that locates code in a loaded module, such as a function to hook.
+///
+/// Patterns are written in the IDA style: bytes as two hexadecimal digits separated by whitespace, and `?` or `??`
+/// for a byte that can have any value, as in `"48 8B ?? ?? E8 ? ? ? ?"`. The address of a match can be moved by an
+/// [offset](Signature::offset), and then [resolved](Signature::rip_relative) through the relative operand of the
+/// instruction there, to reach the function called by a matched `call` for example.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use minhook::{HookError, MinHook, Signature};
+///
+/// fn main() -> Result<(), HookError> {
+///     // A call to the function to hook, following a known instruction.
+///     let target = Signature::new("48 8D 0D ? ? ? ? E8 ? ? ? ?")?
+///         .offset(7)
+///         .rip_relative(1, 5)
+///         .find("libgame.so")?;
+///
+///     let original = unsafe { MinHook::create_hook(target, detour as _)? };
+///     Ok(())
+/// }
+///
+/// extern "C" fn detour() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pattern: String,
+    bytes: Vec<Option<u8>>,
+    offset: isize,
+    rip_relative: Option<(usize, usize)>,
+}
+
+impl Signature {
+    /// Parses a pattern.
+    ///
+    /// Returns [`ErrorKind::InvalidPattern`] with the position of the first byte that is neither two hexadecimal
+    /// digits nor a wildcard, or position 0 if the pattern is empty.
+    pub fn new(pattern: &str) -> Result<Self, HookError> {
+        let mut bytes = Vec::new();
+        let mut rest = pattern;
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            let token = &rest[start..];
+            let end = token.find(char::is_whitespace).unwrap_or(token.len());
+            let position = pattern.len() - token.len();
+
+            bytes.push(match &token[..end] {
+                "?" | "??" => None,
+                byte if byte.len() == 2 && byte.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                    Some(u8::from_str_radix(byte, 16).unwrap())
+                }
+                _ => return Err(parse_error(position)),
+            });
+            rest = &token[end..];
+        }
+
+        if bytes.is_empty() {
+            return Err(parse_error(0));
+        }
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            bytes,
+            offset: 0,
+            rip_relative: None,
+        })
+    }
+
+    /// Sets the offset from the start of a match to the address that is returned or resolved. 0 by default.
+    pub const fn offset(mut self, offset: isize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Resolves the address, after the offset, as an instruction with a 32-bit relative operand: the operand at
+    /// `operand_offset` within the instruction is added to the end of the instruction, `len` bytes from its start.
+    /// This is `(1, 5)` for a `call` or `jmp` and, for example, `(3, 7)` for a RIP-relative `lea` with a REX prefix.
+    pub const fn rip_relative(mut self, operand_offset: usize, len: usize) -> Self {
+        self.rip_relative = Some((operand_offset, len));
+        self
+    }
+
+    /// Returns the pattern as it was written.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the offset of the first match in `data`, without applying the offset or resolving the operand.
+    pub fn scan(&self, data: &[u8]) -> Option<usize> {
+        data.windows(self.bytes.len()).position(|window| {
+            window
+                .iter()
+                .zip(&self.bytes)
+                .all(|(byte, pattern)| pattern.is_none_or(|pattern| pattern == *byte))
+        })
+    }
+
+    /// Searches the executable code of the module `module_name` for the first match, and returns its address after
+    /// applying the offset and resolving the operand, ready to be passed to
+    /// [`MinHook::create_hook`](crate::MinHook::create_hook). The module is looked up as for
+    /// [`MinHook::create_import_hook`](crate::MinHook::create_import_hook), so the empty name stands for the main
+    /// program.
+    ///
+    /// Returns `MH_ERROR_MODULE_NOT_FOUND` if the module is not loaded, and `MH_ERROR_FUNCTION_NOT_FOUND` if the
+    /// pattern does not match or the operand to resolve lies outside the code of the module.
+    pub fn find(&self, module_name: &str) -> Result<*mut c_void, HookError> {
+        let error = |status| {
+            HookError::new(
+                Operation::FindSignature,
+                Some(HookTarget::Signature {
+                    module: module_name.to_owned(),
+                    pattern: self.pattern.clone(),
+                }),
+                ErrorKind::Status(status),
+            )
+        };
+
+        let ranges = os::code_ranges(module_name).map_err(error)?;
+        for &(start, end) in &ranges {
+            // The code of a loaded module stays mapped readable while the module is loaded.
+            let code = unsafe { slice::from_raw_parts(start as *const u8, end - start) };
+            let Some(position) = self.scan(code) else {
+                continue;
+            };
+
+            let address = (start + position).wrapping_add_signed(self.offset);
+            return self
+                .resolve(address, &ranges)
+                .map(|address| address as *mut c_void)
+                .ok_or_else(|| error(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND));
+        }
+
+        Err(error(MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND))
+    }
+
+    /// Resolves the relative operand at `address`, if any, reading it only from the code of the module.
+    fn resolve(&self, address: usize, ranges: &[(usize, usize)]) -> Option<usize> {
+        let Some((operand_offset, len)) = self.rip_relative else {
+            return Some(address);
+        };
+
+        let operand = address.checked_add(operand_offset)?;
+        let operand_end = operand.checked_add(size_of::<i32>())?;
+        if !ranges
+            .iter()
+            .any(|&(start, end)| start <= operand && operand_end <= end)
+        {
+            return None;
+        }
+
+        let displacement = unsafe { (operand as *const i32).read_unaligned() };
+        Some(
+            address
+                .wrapping_add(len)
+                .wrapping_add_signed(displacement as isize),
+        )
+    }
+}
+
+fn parse_error(position: usize) -> HookError {
+    HookError::new(
+        Operation::FindSignature,
+        None,
+        ErrorKind::InvalidPattern { position },
+    )
+}
@@ -0,0 +1,353 @@
+//! Hooks stored in statics, declared with the [`static_hook!`](crate::static_hook) macro.
+
+use crate::{ErrorKind, FnPtr, Hook, HookError, HookTarget, MH_STATUS, MinHook, Operation};
+use std::{
+    ffi::c_void,
+    fmt,
+    ptr::null_mut,
+    sync::{
+        Mutex, OnceLock, PoisonError,
+        atomic::{AtomicPtr, Ordering},
+    },
+};
+
+/// Declares statics holding [`StaticHook`]s with the given function pointer types.
+///
+/// The function pointer type determines the signature and calling convention of both the target and the detour, as
+/// well as the arguments of `call_original`.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, static_hook};
+///
+/// static_hook! {
+///     static ADD_HOOK: extern "C" fn(i32, i32) -> i32;
+/// }
+///
+/// fn main() -> Result<(), HookError> {
+///     unsafe {
+///         ADD_HOOK.initialize(add, sub)?;
+///         ADD_HOOK.enable()?;
+///     }
+///
+///     assert_eq!(add(5, 3), 2);
+///     assert_eq!(ADD_HOOK.call_original(5, 3), 8);
+///
+///     Ok(())
+/// }
+///
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// extern "C" fn sub(a: i32, b: i32) -> i32 {
+///     a - b
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_hook {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::StaticHook<$ty> = $crate::StaticHook::new();
+        )*
+    };
+}
+
+/// A hook that can be stored in a static and initialized later, usually declared with
+/// [`static_hook!`](crate::static_hook).
+pub struct StaticHook<F: FnPtr> {
+    hook: OnceLock<Hook<F>>,
+}
+
+impl<F: FnPtr> StaticHook<F> {
+    /// Creates an uninitialized hook.
+    pub const fn new() -> Self {
+        Self {
+            hook: OnceLock::new(),
+        }
+    }
+
+    /// Creates the hook for the target function and detours it to the detour function. The hook is created disabled.
+    ///
+    /// Returns `MH_ERROR_ALREADY_CREATED` if the hook has already been initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`](crate::MinHook::create_hook).
+    pub unsafe fn initialize(&self, target: F, detour: F) -> Result<&Self, HookError> {
+        if self.hook.get().is_some() {
+            return Err(already_created(target));
+        }
+
+        let hook = unsafe { Hook::new(target, detour)? };
+        self.hook.set(hook).map_err(|_| already_created(target))?;
+        Ok(self)
+    }
+
+    /// Enables the hook.
+    ///
+    /// Returns `MH_ERROR_NOT_CREATED` if the hook has not been initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::enable_hook`](crate::MinHook::enable_hook).
+    pub unsafe fn enable(&self) -> Result<(), HookError> {
+        unsafe { self.hook(Operation::EnableHook)?.enable() }
+    }
+
+    /// Disables the hook.
+    ///
+    /// Returns `MH_ERROR_NOT_CREATED` if the hook has not been initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::disable_hook`](crate::MinHook::disable_hook).
+    pub unsafe fn disable(&self) -> Result<(), HookError> {
+        unsafe { self.hook(Operation::DisableHook)?.disable() }
+    }
+
+    /// Returns `true` if the hook has been initialized and is enabled, as reported by
+    /// [`MinHook::is_enabled`](crate::MinHook::is_enabled). This includes changes made through other functions, such as
+    /// [`MinHook::disable_all_hooks`](crate::MinHook::disable_all_hooks).
+    pub fn is_enabled(&self) -> bool {
+        self.hook
+            .get()
+            .is_some_and(|hook| MinHook::is_enabled(hook.target().to_ptr()))
+    }
+
+    /// Returns `true` if the hook has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.hook.get().is_some()
+    }
+
+    /// Returns the trampoline that calls the original function, if the hook has been initialized.
+    pub fn original(&self) -> Option<F> {
+        self.hook.get().map(Hook::original)
+    }
+
+    fn hook(&self, operation: Operation) -> Result<&Hook<F>, HookError> {
+        self.hook.get().ok_or_else(|| not_created(operation))
+    }
+
+    fn original_or_panic(&self) -> F {
+        self.original()
+            .expect("the static hook must be initialized before calling the original function")
+    }
+}
+
+impl<F: FnPtr> Default for StaticHook<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for StaticHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StaticHook")
+            .field("hook", &self.hook.get())
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+/// The storage behind the functions generated by the `#[hook]` attribute. Unlike [`StaticHook`], it can be installed
+/// again after being uninstalled.
+#[doc(hidden)]
+pub struct HookCell<F: FnPtr> {
+    hook: Mutex<Option<Hook<F>>>,
+    original: AtomicPtr<c_void>,
+}
+
+impl<F: FnPtr> HookCell<F> {
+    pub const fn new() -> Self {
+        Self {
+            hook: Mutex::new(None),
+            original: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    /// Creates and enables a hook for the target function.
+    ///
+    /// # Safety
+    ///
+    /// See [`Hook::new`].
+    pub unsafe fn install(&self, target: F, detour: F) -> Result<(), HookError> {
+        self.install_with(
+            || unsafe { Hook::new(target, detour) },
+            || already_created(target),
+        )
+    }
+
+    /// Creates and enables a hook for an exported function of a loaded module.
+    ///
+    /// # Safety
+    ///
+    /// See [`Hook::with_api`].
+    pub unsafe fn install_api(
+        &self,
+        module_name: &str,
+        proc_name: &str,
+        detour: F,
+    ) -> Result<(), HookError> {
+        self.install_with(
+            || unsafe { Hook::with_api(module_name, proc_name, detour) },
+            || {
+                HookError::new(
+                    Operation::CreateHookApi,
+                    Some(HookTarget::Symbol {
+                        module: module_name.to_owned(),
+                        symbol: proc_name.to_owned(),
+                    }),
+                    ErrorKind::Status(MH_STATUS::MH_ERROR_ALREADY_CREATED),
+                )
+            },
+        )
+    }
+
+    fn install_with(
+        &self,
+        create: impl FnOnce() -> Result<Hook<F>, HookError>,
+        already_created: impl FnOnce() -> HookError,
+    ) -> Result<(), HookError> {
+        let mut slot = self.hook.lock().unwrap_or_else(PoisonError::into_inner);
+        if slot.is_some() {
+            return Err(already_created());
+        }
+
+        let hook = create()?;
+        // The original function has to be available before the detour can be entered.
+        self.original
+            .store(hook.original().to_ptr(), Ordering::SeqCst);
+        if let Err(status) = unsafe { hook.enable() } {
+            let _ = unsafe { hook.remove() };
+            self.original.store(null_mut(), Ordering::SeqCst);
+            return Err(status);
+        }
+
+        *slot = Some(hook);
+        Ok(())
+    }
+
+    /// Disables and removes the hook.
+    ///
+    /// # Safety
+    ///
+    /// See [`Hook::remove`].
+    pub unsafe fn uninstall(&self) -> Result<(), HookError> {
+        let mut slot = self.hook.lock().unwrap_or_else(PoisonError::into_inner);
+        let target = slot
+            .as_ref()
+            .ok_or_else(|| not_created(Operation::RemoveHook))?
+            .target();
+        unsafe { MinHook::remove_hook(target.to_ptr())? };
+
+        *slot = None;
+        self.original.store(null_mut(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn is_installed(&self) -> bool {
+        self.hook
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Returns the trampoline that calls the original function.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hook is not installed.
+    pub fn original(&self) -> F {
+        let original = self.original.load(Ordering::SeqCst);
+        assert!(
+            !original.is_null(),
+            "the hook must be installed before calling the original function"
+        );
+        unsafe { F::from_ptr(original) }
+    }
+}
+
+impl<F: FnPtr> Default for HookCell<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn already_created<F: FnPtr>(target: F) -> HookError {
+    HookError::new(
+        Operation::CreateHook,
+        Some(HookTarget::Address(target.to_ptr() as usize)),
+        ErrorKind::Status(MH_STATUS::MH_ERROR_ALREADY_CREATED),
+    )
+}
+
+fn not_created(operation: Operation) -> HookError {
+    HookError::new(
+        operation,
+        None,
+        ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_CREATED),
+    )
+}
+
+macro_rules! impl_call_original {
+    ($abi:literal; $($arg:ident),*) => {
+        impl<Ret: 'static, $($arg: 'static),*> StaticHook<extern $abi fn($($arg),*) -> Ret> {
+            /// Calls the original function, bypassing the detour.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the hook has not been initialized.
+            #[allow(non_snake_case, clippy::too_many_arguments)]
+            pub fn call_original(&self, $($arg: $arg),*) -> Ret {
+                self.original_or_panic()($($arg),*)
+            }
+        }
+
+        impl<Ret: 'static, $($arg: 'static),*> StaticHook<unsafe extern $abi fn($($arg),*) -> Ret> {
+            /// Calls the original function, bypassing the detour.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the hook has not been initialized.
+            ///
+            /// # Safety
+            ///
+            /// The same requirements as calling the original function apply.
+            #[allow(non_snake_case, clippy::too_many_arguments)]
+            pub unsafe fn call_original(&self, $($arg: $arg),*) -> Ret {
+                unsafe { self.original_or_panic()($($arg),*) }
+            }
+        }
+    };
+}
+
+macro_rules! impl_call_originals {
+    ($($arg:ident),*) => {
+        impl_call_original!("Rust"; $($arg),*);
+        impl_call_original!("C"; $($arg),*);
+        impl_call_original!("system"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_call_original!("stdcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_call_original!("fastcall"; $($arg),*);
+        #[cfg(target_arch = "x86")]
+        impl_call_original!("thiscall"; $($arg),*);
+    };
+}
+
+impl_call_originals!();
+impl_call_originals!(A);
+impl_call_originals!(A, B);
+impl_call_originals!(A, B, C);
+impl_call_originals!(A, B, C, D);
+impl_call_originals!(A, B, C, D, E);
+impl_call_originals!(A, B, C, D, E, F);
+impl_call_originals!(A, B, C, D, E, F, G);
+impl_call_originals!(A, B, C, D, E, F, G, H);
+impl_call_originals!(A, B, C, D, E, F, G, H, I);
+impl_call_originals!(A, B, C, D, E, F, G, H, I, J);
+impl_call_originals!(A, B, C, D, E, F, G, H, I, J, K);
+impl_call_originals!(A, B, C, D, E, F, G, H, I, J, K, L);
@@ -0,0 +1,180 @@
+//! Executable memory for small pieces of code generated at runtime, such as the entry points of closure detours.
+
+use crate::{FnPtr, Hook, HookGuard, os};
+use std::{
+    mem,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// Size of each thunk slot.
+pub(crate) const THUNK_SIZE: usize = 64;
+
+/// Size of each memory block.
+const BLOCK_SIZE: usize = 0x1000;
+
+struct Block {
+    address: usize,
+    free: Vec<usize>,
+}
+
+static BLOCKS: Mutex<Vec<Block>> = Mutex::new(Vec::new());
+
+/// A slot of executable memory holding generated code, released when dropped.
+#[derive(Debug)]
+pub(crate) struct Thunk {
+    address: usize,
+}
+
+impl Thunk {
+    /// Allocates a slot and fills it with the code returned by `code`, which receives the address of the slot.
+    pub(crate) fn new(code: impl FnOnce(usize) -> Vec<u8>) -> Option<Self> {
+        let address = allocate()?;
+        let code = code(address);
+        assert!(code.len() <= THUNK_SIZE, "thunk does not fit into a slot");
+
+        unsafe { std::ptr::copy_nonoverlapping(code.as_ptr(), address as *mut u8, code.len()) };
+        os::flush_instruction_cache(address, code.len());
+
+        Some(Self { address })
+    }
+
+    /// Returns the address of the generated code.
+    pub(crate) fn address(&self) -> usize {
+        self.address
+    }
+}
+
+/// A thunk that jumps to a destination that can be changed while other threads are running it. The destination is
+/// stored in front of the code, so it is replaced with a single aligned write.
+#[derive(Debug)]
+pub(crate) struct JumpThunk {
+    thunk: Thunk,
+}
+
+impl JumpThunk {
+    pub(crate) fn new(destination: usize) -> Option<Self> {
+        let thunk = Thunk::new(|address| {
+            if cfg!(target_arch = "x86_64") {
+                [
+                    &(destination as u64).to_le_bytes()[..],
+                    // jmp [rip-14]
+                    &[0xFF, 0x25, 0xF2, 0xFF, 0xFF, 0xFF],
+                ]
+                .concat()
+            } else {
+                [
+                    &(destination as u32).to_le_bytes()[..],
+                    // jmp [address]
+                    &[0xFF, 0x25],
+                    &(address as u32).to_le_bytes(),
+                ]
+                .concat()
+            }
+        })?;
+
+        Some(Self { thunk })
+    }
+
+    /// Returns the address of the jump.
+    pub(crate) fn address(&self) -> usize {
+        self.thunk.address() + size_of::<usize>()
+    }
+
+    pub(crate) fn set_destination(&self, destination: usize) {
+        let slot = unsafe { AtomicUsize::from_ptr(self.thunk.address() as *mut usize) };
+        slot.store(destination, Ordering::SeqCst);
+    }
+}
+
+/// A thunk together with the state it passes to the code it enters, such as a closure. The state is boxed, so its
+/// address stays the same while the owner moves.
+pub(crate) struct BoundThunk<T> {
+    thunk: Thunk,
+    state: Box<T>,
+}
+
+impl<T> BoundThunk<T> {
+    /// Boxes `state` and builds the thunk with `thunk`, which receives the address of the state.
+    pub(crate) fn new(state: T, thunk: impl FnOnce(*const T) -> Option<Thunk>) -> Option<Self> {
+        let state = Box::new(state);
+        let thunk = thunk(&*state)?;
+        Some(Self { thunk, state })
+    }
+
+    /// Returns the address of the generated code.
+    pub(crate) fn address(&self) -> usize {
+        self.thunk.address()
+    }
+
+    pub(crate) fn state(&self) -> &T {
+        &self.state
+    }
+}
+
+/// A hook that is detoured to thunks, which are released together with their state after the hook is removed. Leaking
+/// it keeps the thunks alive for the rest of the process, since the hook may still enter them.
+pub(crate) struct ThunkHook<F: FnPtr, T> {
+    // Dropped first, so the hook is removed before the thunks are released.
+    guard: HookGuard<F>,
+    thunks: T,
+}
+
+impl<F: FnPtr, T> ThunkHook<F, T> {
+    pub(crate) fn new(guard: HookGuard<F>, thunks: T) -> Self {
+        Self { guard, thunks }
+    }
+
+    pub(crate) fn guard(&self) -> &HookGuard<F> {
+        &self.guard
+    }
+
+    pub(crate) fn thunks(&self) -> &T {
+        &self.thunks
+    }
+
+    /// Releases the hook without removing it, leaking the thunks and their state.
+    pub(crate) fn leak(self) -> Hook<F> {
+        mem::forget(self.thunks);
+        self.guard.leak()
+    }
+}
+
+impl Drop for Thunk {
+    fn drop(&mut self) {
+        let mut blocks = BLOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(index) = blocks
+            .iter()
+            .position(|block| (block.address..block.address + BLOCK_SIZE).contains(&self.address))
+        else {
+            return;
+        };
+
+        let block = &mut blocks[index];
+        block.free.push(self.address);
+
+        // Free the block if it is completely unused.
+        if block.free.len() == BLOCK_SIZE / THUNK_SIZE {
+            let block = blocks.swap_remove(index);
+            unsafe { os::free(block.address, BLOCK_SIZE) };
+        }
+    }
+}
+
+fn allocate() -> Option<usize> {
+    let mut blocks = BLOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(slot) = blocks.iter_mut().find_map(|block| block.free.pop()) {
+        return Some(slot);
+    }
+
+    let address = unsafe { os::allocate(None, BLOCK_SIZE) }?;
+    let mut free = (0..BLOCK_SIZE / THUNK_SIZE)
+        .rev()
+        .map(|slot| address + slot * THUNK_SIZE)
+        .collect::<Vec<_>>();
+    let slot = free.pop();
+    blocks.push(Block { address, free });
+    slot
+}
@@ -0,0 +1,344 @@
+//! Port of MinHook's `trampoline.c`: copies the prologue of a target function into a trampoline,
+//! relocating relative instructions so the trampoline can be called in place of the original.
+
+use crate::{
+    disasm::{self, Map, Mode},
+    os,
+};
+use std::slice;
+
+/// Size of the `JMP rel32` that is written over the target function.
+pub(crate) const JMP_REL_SIZE: usize = 5;
+
+/// Size of the `JMP rel8` that is written over the target function when the long jump is placed above it.
+pub(crate) const JMP_REL_SHORT_SIZE: usize = 2;
+
+/// Maximum number of instructions that can be relocated into a trampoline.
+const MAX_IPS: usize = 8;
+
+/// Number of bytes of the target function that are read when building a trampoline.
+const MAX_CODE_SIZE: usize = 64;
+
+/// Size of the memory slot each trampoline is placed in.
+pub(crate) const MEMORY_SLOT_SIZE: usize = if cfg!(target_pointer_width = "64") {
+    64
+} else {
+    32
+};
+
+/// The code of a target function together with the address it lives at.
+pub(crate) struct Target {
+    /// The address of the target function.
+    pub(crate) address: usize,
+    /// The code located at `address`.
+    pub(crate) code: Vec<u8>,
+    /// The code located `JMP_REL_SIZE` bytes above `address`, if it is executable.
+    pub(crate) above: Option<[u8; JMP_REL_SIZE]>,
+}
+
+impl Target {
+    /// Reads the code of the target function that can be relocated into a trampoline, or returns `None` if `address`
+    /// is not executable.
+    pub(crate) fn read(address: usize) -> Option<Self> {
+        let region = os::query(address).filter(|region| region.executable())?;
+        let len = (region.end - address).min(MAX_CODE_SIZE);
+        let code = unsafe { slice::from_raw_parts(address as *const u8, len) }.to_vec();
+
+        let above = address
+            .checked_sub(JMP_REL_SIZE)
+            .filter(|&above| os::is_executable(above))
+            .map(|above| unsafe { *(above as *const [u8; JMP_REL_SIZE]) });
+
+        Some(Self {
+            address,
+            code,
+            above,
+        })
+    }
+}
+
+/// A trampoline function built for a target function.
+pub(crate) struct Trampoline {
+    /// Number of bytes of the target function that were relocated.
+    pub(crate) len: usize,
+    /// The machine code of the trampoline, followed by the relay function on x64.
+    pub(crate) code: Vec<u8>,
+    /// Offset of the relay function within `code`, which jumps to the detour on x64.
+    pub(crate) relay: Option<usize>,
+    /// Whether the long jump has to be placed above the target function.
+    pub(crate) patch_above: bool,
+    /// Pairs of instruction offsets in the target function and in the trampoline.
+    pub(crate) ips: Vec<(u8, u8)>,
+}
+
+/// The reason a trampoline could not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TrampolineError {
+    /// The instruction at the given offset could not be decoded.
+    Decode { offset: usize },
+    /// A `LOOP`/`JCXZ` instruction at the given offset jumps out of the relocated code.
+    UnsupportedBranch { offset: usize },
+    /// The instruction at the given offset is a branch target and cannot be resized.
+    ResizedBranchTarget { offset: usize },
+    /// A RIP-relative instruction at the given offset cannot reach its operand from the trampoline.
+    OutOfRange { offset: usize },
+    /// The trampoline does not fit into a memory slot.
+    TooLarge,
+    /// The trampoline would contain too many instructions.
+    TooManyInstructions,
+    /// The function is too short to be patched and there is no padding around it.
+    TooShort,
+}
+
+/// Returns the maximum size of a trampoline, which leaves room for the relay function on x64.
+pub(crate) fn max_size(mode: Mode) -> usize {
+    match mode {
+        Mode::X64 => MEMORY_SLOT_SIZE - jmp(Mode::X64, 0, 0).len(),
+        Mode::X86 => MEMORY_SLOT_SIZE,
+    }
+}
+
+/// Builds the trampoline for `target`, to be placed at `trampoline`. On x64, the trampoline is followed by a relay
+/// function that jumps to `detour`.
+pub(crate) fn create(
+    target: &Target,
+    trampoline: usize,
+    detour: usize,
+    mode: Mode,
+) -> Result<Trampoline, TrampolineError> {
+    let max_size = max_size(mode);
+    let mut code = Vec::new();
+    let mut ips = Vec::new();
+    let mut old_pos = 0;
+    // Destination address of an internal jump.
+    let mut jmp_dest = 0;
+    // Is the function completed?
+    let mut finished = false;
+
+    while !finished {
+        let old_inst = target.address + old_pos;
+        let new_inst = trampoline + code.len();
+
+        let copy = if old_pos >= JMP_REL_SIZE {
+            // The trampoline function is long enough.
+            // Complete the function with the jump to the target function.
+            finished = true;
+            jmp(mode, new_inst, old_inst)
+        } else {
+            let hs = disasm::decode(&target.code[old_pos..], mode)
+                .map_err(|_| TrampolineError::Decode { offset: old_pos })?;
+            let source = &target.code[old_pos..old_pos + hs.len];
+            let next_inst = old_inst + hs.len;
+
+            let copy = if hs.rip_relative {
+                // Instructions using RIP relative addressing. (ModR/M = 00???101B)
+                // Modify the RIP relative address.
+                let operand = next_inst.wrapping_add(hs.disp as usize);
+                let relative = operand.wrapping_sub(new_inst + hs.len) as isize;
+                let relative = i32::try_from(relative)
+                    .map_err(|_| TrampolineError::OutOfRange { offset: old_pos })?;
+
+                let mut copy = source.to_vec();
+                copy[hs.disp_offset..hs.disp_offset + 4].copy_from_slice(&relative.to_le_bytes());
+
+                // Complete the function if JMP (FF /4).
+                if hs.map == Map::Primary && hs.opcode == 0xFF && hs.modrm_reg() == Some(4) {
+                    finished = true;
+                }
+                copy
+            } else if hs.map == Map::Primary && hs.opcode == 0xE8 {
+                // Direct relative CALL
+                call(mode, new_inst, branch_destination(next_inst, &hs))
+            } else if hs.map == Map::Primary && hs.opcode & 0xFD == 0xE9 {
+                // Direct relative JMP (EB or E9)
+                let dest = branch_destination(next_inst, &hs);
+
+                if is_internal(target, dest) {
+                    // Simply copy an internal jump.
+                    jmp_dest = jmp_dest.max(dest);
+                    source.to_vec()
+                } else {
+                    // Exit the function if it is not in the branch.
+                    finished = old_inst >= jmp_dest;
+                    jmp(mode, new_inst, dest)
+                }
+            } else if (hs.map == Map::Primary
+                && (hs.opcode & 0xF0 == 0x70 || hs.opcode & 0xFC == 0xE0))
+                || (hs.map == Map::Secondary && hs.opcode & 0xF0 == 0x80)
+            {
+                // Direct relative Jcc
+                let dest = branch_destination(next_inst, &hs);
+
+                if is_internal(target, dest) {
+                    // Simply copy an internal jump.
+                    jmp_dest = jmp_dest.max(dest);
+                    source.to_vec()
+                } else if hs.map == Map::Primary && hs.opcode & 0xFC == 0xE0 {
+                    // LOOPNZ/LOOPZ/LOOP/JCXZ/JECXZ to the outside are not supported.
+                    return Err(TrampolineError::UnsupportedBranch { offset: old_pos });
+                } else {
+                    jcc(mode, new_inst, hs.opcode & 0x0F, dest)
+                }
+            } else if hs.map == Map::Primary && hs.opcode & 0xFE == 0xC2 {
+                // RET (C2 or C3)
+                // Complete the function if not in a branch.
+                finished = old_inst >= jmp_dest;
+                source.to_vec()
+            } else {
+                source.to_vec()
+            };
+
+            // Can't alter the instruction length in a branch.
+            if old_inst < jmp_dest && copy.len() != hs.len {
+                return Err(TrampolineError::ResizedBranchTarget { offset: old_pos });
+            }
+
+            old_pos += hs.len;
+            copy
+        };
+
+        // Trampoline function is too large.
+        if code.len() + copy.len() > max_size {
+            return Err(TrampolineError::TooLarge);
+        }
+
+        // Trampoline function has too many instructions.
+        if ips.len() >= MAX_IPS {
+            return Err(TrampolineError::TooManyInstructions);
+        }
+
+        ips.push(((old_inst - target.address) as u8, code.len() as u8));
+        code.extend_from_slice(&copy);
+    }
+
+    // Is there enough place for a long jump?
+    let mut patch_above = false;
+    if old_pos < JMP_REL_SIZE
+        && !is_code_padding(&target.code[old_pos..], JMP_REL_SIZE - old_pos, mode)
+    {
+        // Is there enough place for a short jump?
+        if old_pos < JMP_REL_SHORT_SIZE
+            && !is_code_padding(&target.code[old_pos..], JMP_REL_SHORT_SIZE - old_pos, mode)
+        {
+            return Err(TrampolineError::TooShort);
+        }
+
+        // Can we place the long jump above the function?
+        match target.above {
+            Some(above) if is_uniform_padding(&above) => patch_above = true,
+            _ => return Err(TrampolineError::TooShort),
+        }
+    }
+
+    // Create a relay function.
+    let relay = (mode == Mode::X64).then(|| {
+        let relay = code.len();
+        code.extend_from_slice(&jmp(mode, trampoline + relay, detour));
+        relay
+    });
+
+    Ok(Trampoline {
+        len: old_pos,
+        code,
+        relay,
+        patch_above,
+        ips,
+    })
+}
+
+/// Returns the destination of a direct relative `CALL`, `JMP`, `Jcc`, `LOOP` or `JCXZ` located at `address`.
+pub(crate) fn relative_branch(address: usize, hs: &disasm::Instruction) -> Option<usize> {
+    let is_branch = match hs.map {
+        Map::Primary => {
+            hs.opcode == 0xE8
+                || hs.opcode & 0xFD == 0xE9
+                || hs.opcode & 0xF0 == 0x70
+                || hs.opcode & 0xFC == 0xE0
+        }
+        Map::Secondary => hs.opcode & 0xF0 == 0x80,
+        _ => false,
+    };
+    is_branch.then(|| branch_destination(address + hs.len, hs))
+}
+
+fn branch_destination(next_inst: usize, hs: &disasm::Instruction) -> usize {
+    let displacement = match hs.imm_size {
+        1 => hs.imm as i8 as isize,
+        2 => hs.imm as i16 as isize,
+        _ => hs.imm as i32 as isize,
+    };
+    next_inst.wrapping_add_signed(displacement)
+}
+
+fn is_internal(target: &Target, dest: usize) -> bool {
+    target.address <= dest && dest < target.address + JMP_REL_SIZE
+}
+
+/// Returns `true` if the first `size` bytes of `code` are padding between functions.
+fn is_code_padding(code: &[u8], size: usize, mode: Mode) -> bool {
+    if code.get(..size).is_some_and(is_uniform_padding) {
+        return true;
+    }
+
+    // ELF toolchains pad functions with multi-byte NOPs rather than repeating a single byte.
+    let mut pos = 0;
+    while pos < size {
+        match disasm::decode(&code[pos..], mode) {
+            Ok(inst) if inst.is_nop() || (inst.map == Map::Primary && inst.opcode == 0xCC) => {
+                pos += inst.len
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+pub(crate) fn is_uniform_padding(code: &[u8]) -> bool {
+    matches!(code.first(), Some(0x00 | 0x90 | 0xCC)) && code.iter().all(|&byte| byte == code[0])
+}
+
+/// `JMP` to `dest`, placed at `address`.
+pub(crate) fn jmp(mode: Mode, address: usize, dest: usize) -> Vec<u8> {
+    match mode {
+        // FF25 00000000: JMP [RIP+6]
+        Mode::X64 => [
+            &[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00][..],
+            &(dest as u64).to_le_bytes(),
+        ]
+        .concat(),
+        Mode::X86 => [&[0xE9][..], &rel32(address + 5, dest)].concat(),
+    }
+}
+
+/// `CALL` to `dest`, placed at `address`.
+fn call(mode: Mode, address: usize, dest: usize) -> Vec<u8> {
+    match mode {
+        // FF15 00000002: CALL [RIP+8]
+        // EB 08:         JMP +10
+        Mode::X64 => [
+            &[0xFF, 0x15, 0x02, 0x00, 0x00, 0x00, 0xEB, 0x08][..],
+            &(dest as u64).to_le_bytes(),
+        ]
+        .concat(),
+        Mode::X86 => [&[0xE8][..], &rel32(address + 5, dest)].concat(),
+    }
+}
+
+/// Conditional jump to `dest` using condition code `cond`, placed at `address`.
+fn jcc(mode: Mode, address: usize, cond: u8, dest: usize) -> Vec<u8> {
+    match mode {
+        // Invert the condition in x64 mode to simplify the conditional jump logic.
+        // 7* 0E:         J** +16
+        // FF25 00000000: JMP [RIP+6]
+        Mode::X64 => [
+            &[0x71 ^ cond, 0x0E, 0xFF, 0x25, 0x00, 0x00, 0x00, 0x00][..],
+            &(dest as u64).to_le_bytes(),
+        ]
+        .concat(),
+        Mode::X86 => [&[0x0F, 0x80 | cond][..], &rel32(address + 6, dest)].concat(),
+    }
+}
+
+fn rel32(next_inst: usize, dest: usize) -> [u8; 4] {
+    (dest.wrapping_sub(next_inst) as u32).to_le_bytes()
+}
@@ -0,0 +1,304 @@
+//! Batches of hook changes that are applied together or not at all.
+
+use crate::{
+    ErrorKind, HookError, HookInfo, HookTarget, MH_STATUS, MinHook, Operation, apply_queued,
+    change_hook, create_hook,
+    registry::{self, Registry},
+};
+use std::{collections::HashMap, ffi::c_void};
+
+/// A batch of hook changes that are validated up front and applied together.
+///
+/// The changes are applied with the queue of MinHook, so all hooks are enabled and disabled while the other threads are
+/// suspended once. If any step fails, the changes that took effect are rolled back. Changes queued outside of the
+/// transaction are neither applied nor discarded by it.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, HookTransaction};
+///
+/// fn main() -> Result<(), HookError> {
+///     unsafe {
+///         HookTransaction::new()
+///             .create(return_0 as _, return_1 as _)
+///             .enable(return_0 as _)
+///             .create(return_2 as _, return_1 as _)
+///             .enable(return_2 as _)
+///             .commit()?;
+///     }
+///
+///     assert_eq!(return_0(), 1);
+///     assert_eq!(return_2(), 1);
+///
+///     Ok(())
+/// }
+///
+/// fn return_0() -> i32 {
+///     0
+/// }
+///
+/// fn return_1() -> i32 {
+///     1
+/// }
+///
+/// fn return_2() -> i32 {
+///     2
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+#[must_use = "a transaction does nothing until it is committed"]
+pub struct HookTransaction {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Create { target: usize, detour: usize },
+    Enable(usize),
+    Disable(usize),
+    Remove(usize),
+}
+
+/// The effect of a transaction on one target.
+#[derive(Debug)]
+struct Change {
+    target: usize,
+    /// Whether the hook was enabled before the transaction, or `None` if it did not exist.
+    before: Option<bool>,
+    /// Whether the hook is enabled after the transaction, or `None` if it does not exist.
+    after: Option<bool>,
+    /// The detour of the hook created by the transaction.
+    detour: Option<usize>,
+    /// Set once the target is removed, after which it cannot be created again in the same transaction.
+    removed: bool,
+}
+
+impl HookTransaction {
+    /// Creates an empty transaction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a hook for the target function and detours it to the detour function. The hook is created disabled.
+    pub fn create(mut self, target: *mut c_void, detour: *mut c_void) -> Self {
+        self.steps.push(Step::Create {
+            target: target as usize,
+            detour: detour as usize,
+        });
+        self
+    }
+
+    /// Enables the hook for the target function.
+    pub fn enable(mut self, target: *mut c_void) -> Self {
+        self.steps.push(Step::Enable(target as usize));
+        self
+    }
+
+    /// Disables the hook for the target function.
+    pub fn disable(mut self, target: *mut c_void) -> Self {
+        self.steps.push(Step::Disable(target as usize));
+        self
+    }
+
+    /// Removes the hook for the target function. The target cannot be hooked again in the same transaction.
+    pub fn remove(mut self, target: *mut c_void) -> Self {
+        self.steps.push(Step::Remove(target as usize));
+        self
+    }
+
+    /// Checks that every step can be applied to the current hooks, returning the error MinHook would return for the
+    /// first step that cannot.
+    pub fn validate(&self) -> Result<(), HookError> {
+        self.changes(&registry::lock()).map(drop)
+    }
+
+    /// Validates and applies the transaction, returning the hooks it created.
+    ///
+    /// # Safety
+    ///
+    /// See [`MinHook::create_hook`], [`MinHook::enable_hook`] and [`MinHook::remove_hook`].
+    pub unsafe fn commit(self) -> Result<Vec<HookInfo>, HookError> {
+        MinHook::initialize()?;
+
+        let mut registry = registry::lock();
+        let changes = self.changes(&registry)?;
+
+        // Changes queued outside of the transaction are set aside, so applying the queue only applies this transaction.
+        let foreign = registry
+            .hooks()
+            .iter()
+            .filter_map(|hook| Some((hook.target(), hook.queued()?)))
+            .collect::<Vec<_>>();
+        for &(target, queued) in &foreign {
+            unsafe { queue(&mut registry, target, !queued)? };
+        }
+
+        let result = unsafe { apply(&mut registry, &changes) };
+
+        for (target, queued) in foreign {
+            if let Err(error) = unsafe { queue(&mut registry, target, queued) } {
+                tracing::error!("Failed to restore a queued change: {error}");
+            }
+        }
+
+        result?;
+        Ok(changes
+            .iter()
+            .filter(|change| change.before.is_none() && change.after.is_some())
+            .filter_map(|change| registry.get(change.target).copied())
+            .collect())
+    }
+
+    /// Simulates the steps on the current hooks and returns their effect on each target, in the order the targets
+    /// first appear.
+    fn changes(&self, registry: &Registry) -> Result<Vec<Change>, HookError> {
+        let mut changes: Vec<Change> = Vec::new();
+        let mut index = HashMap::new();
+
+        for &step in &self.steps {
+            let (operation, target) = match step {
+                Step::Create { target, .. } => (Operation::CreateHook, target),
+                Step::Enable(target) => (Operation::EnableHook, target),
+                Step::Disable(target) => (Operation::DisableHook, target),
+                Step::Remove(target) => (Operation::RemoveHook, target),
+            };
+            let error = |kind| HookError::new(operation, Some(HookTarget::Address(target)), kind);
+            let status = |status| error(ErrorKind::Status(status));
+
+            let position = *index.entry(target).or_insert_with(|| {
+                let before = registry.get(target).map(HookInfo::is_enabled);
+                changes.push(Change {
+                    target,
+                    before,
+                    after: before,
+                    detour: None,
+                    removed: false,
+                });
+                changes.len() - 1
+            });
+            let change = &mut changes[position];
+
+            match step {
+                Step::Create { target, detour } => {
+                    if target == 0 {
+                        return Err(error(ErrorKind::NullPointer { argument: "target" }));
+                    }
+                    if detour == 0 {
+                        return Err(error(ErrorKind::NullPointer { argument: "detour" }));
+                    }
+                    if change.after.is_some() || change.removed {
+                        return Err(status(MH_STATUS::MH_ERROR_ALREADY_CREATED));
+                    }
+
+                    change.after = Some(false);
+                    change.detour = Some(detour);
+                }
+                Step::Enable(_) | Step::Disable(_) => {
+                    let enable = matches!(step, Step::Enable(_));
+                    match change.after {
+                        None => return Err(status(MH_STATUS::MH_ERROR_NOT_CREATED)),
+                        Some(true) if enable => return Err(status(MH_STATUS::MH_ERROR_ENABLED)),
+                        Some(false) if !enable => {
+                            return Err(status(MH_STATUS::MH_ERROR_DISABLED));
+                        }
+                        Some(_) => change.after = Some(enable),
+                    }
+                }
+                Step::Remove(_) => {
+                    if change.after.is_none() {
+                        return Err(status(MH_STATUS::MH_ERROR_NOT_CREATED));
+                    }
+
+                    change.after = None;
+                    change.removed = true;
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Creates the hooks, applies all state changes with a single `MH_ApplyQueued` and removes the hooks last. Everything
+/// before the removal is rolled back on failure.
+unsafe fn apply(registry: &mut Registry, changes: &[Change]) -> Result<(), HookError> {
+    let mut created = Vec::new();
+    let result = (|| {
+        for change in changes {
+            if let (None, Some(_), Some(detour)) = (change.before, change.after, change.detour) {
+                let target = change.target as *mut c_void;
+                unsafe { create_hook(registry, target, detour as *mut c_void)? };
+                created.push(target);
+            }
+        }
+
+        for change in changes {
+            // Hooks that are removed are disabled first, which makes removing them later unable to fail.
+            let enable = change.after.unwrap_or(false);
+            if change.before.unwrap_or(false) != enable {
+                unsafe { queue(registry, change.target as *mut c_void, enable)? };
+            }
+        }
+
+        unsafe { apply_queued(registry) }
+    })();
+
+    if let Err(error) = result {
+        unsafe { rollback(registry, changes, &created) };
+        return Err(error);
+    }
+
+    for change in changes {
+        if change.before.is_some() && change.after.is_none() {
+            unsafe {
+                change_hook(
+                    registry,
+                    Operation::RemoveHook,
+                    change.target as *mut c_void,
+                )?
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores the state of the hooks that existed before the transaction and removes the hooks it created.
+unsafe fn rollback(registry: &mut Registry, changes: &[Change], created: &[*mut c_void]) {
+    let result = (|| {
+        for change in changes {
+            let target = change.target as *mut c_void;
+            if let Some(enabled) = change.before {
+                unsafe { queue(registry, target, enabled)? };
+            } else if created.contains(&target) {
+                unsafe { queue(registry, target, false)? };
+            }
+        }
+
+        unsafe { apply_queued(registry)? };
+
+        for &target in created {
+            unsafe { change_hook(registry, Operation::RemoveHook, target)? };
+        }
+
+        Ok::<_, HookError>(())
+    })();
+
+    if let Err(error) = result {
+        tracing::error!("Failed to roll back a hook transaction: {error}");
+    }
+}
+
+unsafe fn queue(
+    registry: &mut Registry,
+    target: *mut c_void,
+    enable: bool,
+) -> Result<(), HookError> {
+    let operation = if enable {
+        Operation::QueueEnableHook
+    } else {
+        Operation::QueueDisableHook
+    };
+    unsafe { change_hook(registry, operation, target) }
+}
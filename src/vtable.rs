@@ -0,0 +1,327 @@
+//! Hooks on virtual methods, which replace an entry of a virtual table instead of patching code.
+
+use crate::{ErrorKind, FnPtr, HookError, HookTarget, MH_STATUS, Operation, os};
+use std::{
+    ffi::c_void,
+    fmt,
+    mem::{self, ManuallyDrop},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing::error;
+
+/// Entries before the address point of a virtual table that `typeid` and `dynamic_cast` read: the offset to the top
+/// and the type info in the Itanium C++ ABI, and the complete object locator in the MSVC ABI.
+#[cfg(windows)]
+const PREFIX_LEN: usize = 1;
+#[cfg(not(windows))]
+const PREFIX_LEN: usize = 2;
+
+/// A copy of a virtual table installed into a single object.
+struct Shadow {
+    object: usize,
+    vtable: usize,
+    entries: Box<[usize]>,
+}
+
+impl Shadow {
+    /// Returns the address point of the copy, which the object points to.
+    fn address(&self) -> usize {
+        self.entries.as_ptr() as usize + PREFIX_LEN * size_of::<usize>()
+    }
+}
+
+/// A hook on a virtual method, which replaces its entry in a virtual table with a detour function of type `F`.
+///
+/// The entry is replaced when the hook is created and restored when it is removed or dropped. Replacing the entry of
+/// a shared virtual table affects every object of the class, while [`VTableHook::with_shadow`] points a single object
+/// to a copy of its virtual table instead. Errors while restoring the entry cannot be returned from `Drop`, so they
+/// are reported through `tracing` instead.
+///
+/// Hooks on the same entry must be removed in the reverse order they were created in.
+///
+/// # Example
+///
+/// ```rust
+/// use minhook::{HookError, VTableHook};
+///
+/// #[repr(C)]
+/// struct Object {
+///     vtable: *const [extern "C" fn(*const Object) -> i32; 1],
+///     value: i32,
+/// }
+///
+/// extern "C" fn get(object: *const Object) -> i32 {
+///     unsafe { (*object).value }
+/// }
+///
+/// extern "C" fn get_doubled(object: *const Object) -> i32 {
+///     unsafe { (*object).value * 2 }
+/// }
+///
+/// fn main() -> Result<(), HookError> {
+///     let vtable = Box::leak(Box::new([0, 0, get as *const () as usize]));
+///     let mut object = Object {
+///         vtable: vtable[2..].as_ptr().cast(),
+///         value: 21,
+///     };
+///     let call = |object: &Object| unsafe { (*object.vtable)[0](object) };
+///
+///     {
+///         let hook = unsafe {
+///             VTableHook::<extern "C" fn(*const Object) -> i32>::with_shadow(
+///                 &raw mut object as *mut _,
+///                 1,
+///                 0,
+///                 get_doubled,
+///             )?
+///         };
+///         assert_eq!(call(&object), 42);
+///         assert_eq!(hook.original()(&object), 21);
+///     }
+///
+///     assert_eq!(call(&object), 21);
+///     Ok(())
+/// }
+/// ```
+pub struct VTableHook<F: FnPtr = *mut c_void> {
+    slot: usize,
+    detour: F,
+    original: F,
+    shadow: Option<Shadow>,
+}
+
+impl<F: FnPtr> VTableHook<F> {
+    /// Replaces the entry at `index` of the virtual table at `vtable` with the detour function. This affects every
+    /// object that uses the virtual table.
+    ///
+    /// Returns `MH_ERROR_NOT_EXECUTABLE` if the entry does not point to executable memory, and
+    /// `MH_ERROR_MEMORY_PROTECT` if the virtual table cannot be made writable.
+    ///
+    /// # Safety
+    ///
+    /// `vtable` must point to the address point of a virtual table with more than `index` entries, and the method at
+    /// `index` must have the signature and calling convention of `F`. The virtual table must stay loaded until the hook
+    /// is removed.
+    pub unsafe fn new(
+        vtable: *mut *mut c_void,
+        index: usize,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        if vtable.is_null() {
+            return Err(create_error(
+                None,
+                ErrorKind::NullPointer { argument: "vtable" },
+            ));
+        }
+
+        let slot = vtable.wrapping_add(index) as usize;
+        let original = unsafe { check_slot(slot, detour) }?;
+        if !unsafe { os::write_pointer(slot, detour.to_ptr() as usize) } {
+            return Err(create_error(
+                Some(slot),
+                ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_PROTECT),
+            ));
+        }
+
+        Ok(Self {
+            slot,
+            detour,
+            original: unsafe { F::from_ptr(original as *mut c_void) },
+            shadow: None,
+        })
+    }
+
+    /// Replaces the entry at `index` of the virtual table of `object`, which is read from the start of the object.
+    /// This affects every object that uses the same virtual table.
+    ///
+    /// # Safety
+    ///
+    /// `object` must point to a polymorphic object that starts with its virtual table pointer. See also
+    /// [`VTableHook::new`].
+    pub unsafe fn with_object(
+        object: *mut c_void,
+        index: usize,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        if object.is_null() {
+            return Err(create_error(
+                None,
+                ErrorKind::NullPointer { argument: "object" },
+            ));
+        }
+
+        unsafe { Self::new(*(object as *const *mut *mut c_void), index, detour) }
+    }
+
+    /// Points `object` to a copy of the first `len` entries of its virtual table, with the entry at `index` replaced
+    /// by the detour function. Other objects of the class are not affected, and the virtual table itself is not
+    /// written to. The entries before the address point that are read by `typeid` and `dynamic_cast` are copied as
+    /// well.
+    ///
+    /// When the hook is removed, the object is pointed back to its virtual table, unless its virtual table pointer was
+    /// changed in the meantime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than `len`.
+    ///
+    /// # Safety
+    ///
+    /// The virtual table of `object` must have at least `len` entries, and the object must outlive the hook. No thread
+    /// may be calling a method through the copy when the hook is removed. See also [`VTableHook::with_object`].
+    pub unsafe fn with_shadow(
+        object: *mut c_void,
+        len: usize,
+        index: usize,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        assert!(index < len, "index {index} out of range for {len} entries");
+        if object.is_null() {
+            return Err(create_error(
+                None,
+                ErrorKind::NullPointer { argument: "object" },
+            ));
+        }
+
+        let vtable = unsafe { *(object as *const usize) };
+        let start = vtable.wrapping_sub(PREFIX_LEN * size_of::<usize>());
+        let mut entries =
+            unsafe { std::slice::from_raw_parts(start as *const usize, PREFIX_LEN + len) }
+                .to_vec()
+                .into_boxed_slice();
+
+        let original = unsafe { check_slot(vtable + index * size_of::<usize>(), detour) }?;
+        entries[PREFIX_LEN + index] = detour.to_ptr() as usize;
+
+        let shadow = Shadow {
+            object: object as usize,
+            vtable,
+            entries,
+        };
+        unsafe {
+            AtomicUsize::from_ptr(object as *mut usize).store(shadow.address(), Ordering::SeqCst)
+        };
+
+        Ok(Self {
+            slot: shadow.address() + index * size_of::<usize>(),
+            detour,
+            original: unsafe { F::from_ptr(original as *mut c_void) },
+            shadow: Some(shadow),
+        })
+    }
+
+    /// Returns the address of the replaced entry, which is in the copy of the virtual table for a shadow hook.
+    pub fn slot(&self) -> *mut *mut c_void {
+        self.slot as *mut *mut c_void
+    }
+
+    /// Returns `true` if the hook points a single object to a copy of its virtual table.
+    pub fn is_shadow(&self) -> bool {
+        self.shadow.is_some()
+    }
+
+    /// Returns the detour function.
+    pub fn detour(&self) -> F {
+        self.detour
+    }
+
+    /// Returns the original method, which was in the replaced entry.
+    pub fn original(&self) -> F {
+        self.original
+    }
+
+    /// Restores the entry, or points the object back to its virtual table for a shadow hook.
+    ///
+    /// # Safety
+    ///
+    /// Calls to the detour that are already in progress are not waited for. See also [`VTableHook::with_shadow`].
+    pub unsafe fn remove(self) -> Result<(), HookError> {
+        let mut this = ManuallyDrop::new(self);
+        let result = unsafe { this.restore() };
+        if result.is_ok() {
+            // Only released once the object no longer points to it.
+            drop(this.shadow.take());
+        }
+        result
+    }
+
+    /// Releases the hook without restoring the entry. A copy of the virtual table stays alive for the rest of the
+    /// process.
+    pub fn leak(self) {
+        let mut this = ManuallyDrop::new(self);
+        mem::forget(this.shadow.take());
+    }
+
+    unsafe fn restore(&self) -> Result<(), HookError> {
+        match &self.shadow {
+            Some(shadow) => {
+                let object = unsafe { AtomicUsize::from_ptr(shadow.object as *mut usize) };
+                let _ = object.compare_exchange(
+                    shadow.address(),
+                    shadow.vtable,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+                Ok(())
+            }
+            None if unsafe { os::write_pointer(self.slot, self.original.to_ptr() as usize) } => {
+                Ok(())
+            }
+            None => Err(HookError::new(
+                Operation::RemoveVTableHook,
+                Some(HookTarget::Address(self.slot)),
+                ErrorKind::Status(MH_STATUS::MH_ERROR_MEMORY_PROTECT),
+            )),
+        }
+    }
+}
+
+impl<F: FnPtr> Drop for VTableHook<F> {
+    fn drop(&mut self) {
+        if let Err(error) = unsafe { self.restore() } {
+            error!(
+                "Could not restore the virtual table entry at {:#x}: {}",
+                self.slot, error
+            );
+        }
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for VTableHook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VTableHook")
+            .field("slot", &self.slot())
+            .field("detour", &self.detour.to_ptr())
+            .field("original", &self.original.to_ptr())
+            .field("shadow", &self.is_shadow())
+            .finish()
+    }
+}
+
+fn create_error(slot: Option<usize>, kind: ErrorKind) -> HookError {
+    HookError::new(
+        Operation::CreateVTableHook,
+        slot.map(HookTarget::Address),
+        kind,
+    )
+}
+
+/// Returns the method in a virtual table entry, checking that it can be replaced by `detour`.
+unsafe fn check_slot(slot: usize, detour: impl FnPtr) -> Result<usize, HookError> {
+    if detour.to_ptr().is_null() {
+        return Err(create_error(
+            Some(slot),
+            ErrorKind::NullPointer { argument: "detour" },
+        ));
+    }
+
+    let method = unsafe { *(slot as *const usize) };
+    if !os::is_executable(method) {
+        return Err(create_error(
+            Some(slot),
+            ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_EXECUTABLE),
+        ));
+    }
+
+    Ok(method)
+}
@@ -0,0 +1,212 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Analyzes hand-written functions without hooking them.
+
+use minhook::{ErrorKind, Issue, MH_STATUS, MinHook, Operation};
+use std::{arch::global_asm, ffi::c_void, slice};
+
+global_asm!(
+    // A prologue that is relocated as is.
+    ".globl minhook_analyze_prologue",
+    ".p2align 4",
+    "minhook_analyze_prologue:",
+    "push rbp",
+    "mov rbp, rsp",
+    "mov eax, 1",
+    "pop rbp",
+    "ret",
+    // A loop that jumps back into the patched prologue.
+    ".globl minhook_analyze_loop",
+    ".p2align 4",
+    "minhook_analyze_loop:",
+    "xor eax, eax",
+    "2:",
+    "inc eax",
+    "cmp eax, 10",
+    "jne 2b",
+    "ret",
+    // A short function preceded by a hot-patch area.
+    ".p2align 4",
+    ".fill 11, 1, 0xcc",
+    ".globl minhook_analyze_hot_patch",
+    "minhook_analyze_hot_patch:",
+    "xor eax, eax",
+    "ret",
+    "ud2",
+    // A short function preceded by code.
+    ".p2align 4",
+    ".fill 6, 1, 0xcc",
+    "ud2",
+    "ud2",
+    "ud2",
+    "ud2",
+    "ud2",
+    ".globl minhook_analyze_too_short",
+    "minhook_analyze_too_short:",
+    "xor eax, eax",
+    "ret",
+    "ud2",
+    // Loads addresses almost 2GB after and before the instruction, which a trampoline placed a page away from the
+    // function cannot reach.
+    ".globl minhook_analyze_far_forward",
+    ".p2align 4",
+    "minhook_analyze_far_forward:",
+    ".byte 0x48, 0x8D, 0x05, 0xF0, 0xFF, 0xFF, 0x7F",
+    "ret",
+    ".globl minhook_analyze_far_backward",
+    ".p2align 4",
+    "minhook_analyze_far_backward:",
+    ".byte 0x48, 0x8D, 0x05, 0x10, 0x00, 0x00, 0x80",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_analyze_prologue() -> i32;
+    fn minhook_analyze_loop() -> i32;
+    fn minhook_analyze_hot_patch() -> i32;
+    fn minhook_analyze_too_short() -> i32;
+    fn minhook_analyze_far_forward() -> usize;
+    fn minhook_analyze_far_backward() -> usize;
+}
+
+extern "C" fn test_fn_hook() -> i32 {
+    -1
+}
+
+fn code(target: *mut c_void, len: usize) -> Vec<u8> {
+    unsafe { slice::from_raw_parts(target as *const u8, len) }.to_vec()
+}
+
+#[test]
+fn test_analyze_prologue() {
+    let target = minhook_analyze_prologue as *const () as *mut c_void;
+    let before = code(target, 16);
+
+    let analysis = MinHook::analyze(target).unwrap();
+    assert_eq!(analysis.target(), target);
+    assert!(analysis.is_supported());
+    assert!(analysis.issues().is_empty());
+    assert_eq!(analysis.relocated_len(), 9);
+    assert_eq!(analysis.patch_len(), 5);
+    assert!(!analysis.uses_hot_patch_area());
+
+    let lengths = analysis
+        .instructions()
+        .iter()
+        .map(|(offset, instruction)| (*offset, instruction.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(lengths, [(0, 1), (1, 3), (4, 5)]);
+
+    // The trampoline is built in a memory slot within reach of the target.
+    let trampoline = analysis.trampoline().unwrap();
+    assert_ne!(trampoline.address(), target);
+    assert!((trampoline.address() as usize).abs_diff(target as usize) < 0x4000_0000);
+    assert_eq!(trampoline.offsets(), [(0, 0), (1, 1), (4, 4), (9, 9)]);
+    assert_eq!(&trampoline.code()[..9], &before[..9]);
+    // The jump back to the target function is followed by the relay function.
+    assert_eq!(trampoline.relay(), Some(23));
+    assert_eq!(trampoline.code().len(), 37);
+
+    // Nothing is patched or created.
+    assert_eq!(code(target, 16), before);
+    assert!(!MinHook::is_created(target));
+
+    unsafe {
+        MinHook::create_hook(target, test_fn_hook as *const () as *mut c_void).unwrap();
+        MinHook::enable_hook(target).unwrap();
+        assert_eq!(minhook_analyze_prologue(), -1);
+
+        let error = MinHook::analyze(target).unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::Analyze));
+        assert_eq!(error, MH_STATUS::MH_ERROR_ALREADY_CREATED);
+
+        MinHook::remove_hook(target).unwrap();
+    }
+    assert_eq!(unsafe { minhook_analyze_prologue() }, 1);
+}
+
+#[test]
+fn test_analyze_branch_into_patch() {
+    let target = minhook_analyze_loop as *const () as *mut c_void;
+
+    let analysis = MinHook::analyze(target).unwrap();
+    assert!(!analysis.is_supported());
+    assert_eq!(
+        analysis.issues(),
+        [Issue::BranchIntoPatch {
+            offset: 7,
+            destination: 2,
+        }]
+    );
+    assert_eq!(analysis.relocated_len(), 7);
+    assert!(analysis.trampoline().is_some());
+    assert_eq!(unsafe { minhook_analyze_loop() }, 10);
+}
+
+#[test]
+fn test_analyze_hot_patch() {
+    let target = minhook_analyze_hot_patch as *const () as *mut c_void;
+
+    let analysis = MinHook::analyze(target).unwrap();
+    assert!(analysis.is_supported());
+    assert!(analysis.has_hot_patch_area());
+    assert!(analysis.uses_hot_patch_area());
+    assert_eq!(analysis.patch_len(), 2);
+    assert_eq!(analysis.relocated_len(), 3);
+    // The trampoline ends with the copied return, so there is no jump back.
+    assert_eq!(analysis.trampoline().unwrap().offsets(), [(0, 0), (2, 2)]);
+
+    unsafe {
+        MinHook::create_hook(target, test_fn_hook as *const () as *mut c_void).unwrap();
+        MinHook::enable_hook(target).unwrap();
+        assert_eq!(minhook_analyze_hot_patch(), -1);
+        MinHook::remove_hook(target).unwrap();
+    }
+}
+
+#[test]
+fn test_analyze_too_short() {
+    let target = minhook_analyze_too_short as *const () as *mut c_void;
+
+    let analysis = MinHook::analyze(target).unwrap();
+    assert!(!analysis.is_supported());
+    assert!(!analysis.has_hot_patch_area());
+    assert_eq!(analysis.issues(), [Issue::TooShort]);
+    assert!(analysis.trampoline().is_none());
+    assert_eq!(analysis.relocated_len(), 0);
+    assert_eq!(analysis.instructions()[0].0, 0);
+
+    let error = unsafe {
+        MinHook::create_hook(target, test_fn_hook as *const () as *mut c_void).unwrap_err()
+    };
+    assert_eq!(error, MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION);
+}
+
+#[test]
+fn test_analyze_operand_out_of_range() {
+    let forward =
+        MinHook::analyze(minhook_analyze_far_forward as *const () as *mut c_void).unwrap();
+    let backward =
+        MinHook::analyze(minhook_analyze_far_backward as *const () as *mut c_void).unwrap();
+
+    // The trampoline lies either below or above the function, so the operand of one of them is out of range.
+    let out_of_range = [Issue::OperandOutOfRange { offset: 0 }];
+    assert!(forward.issues() == out_of_range || backward.issues() == out_of_range);
+    assert!(forward.is_supported() || backward.is_supported());
+    let _ = unsafe {
+        (
+            minhook_analyze_far_forward(),
+            minhook_analyze_far_backward(),
+        )
+    };
+}
+
+#[test]
+fn test_analyze_invalid_target() {
+    let error = MinHook::analyze(std::ptr::null_mut()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NullPointer { argument: "target" });
+
+    static DATA: u64 = 0;
+    let error = MinHook::analyze(&DATA as *const u64 as *mut c_void).unwrap_err();
+    assert_eq!(error, MH_STATUS::MH_ERROR_NOT_EXECUTABLE);
+}
@@ -0,0 +1,144 @@
+use minhook::{ChainedHook, MH_STATUS, MinHook};
+use std::sync::OnceLock;
+
+type Fn = extern "C" fn(i32) -> i32;
+
+static ADD_ONE: OnceLock<Fn> = OnceLock::new();
+static ADD_HUNDRED: OnceLock<Fn> = OnceLock::new();
+static DOUBLE: OnceLock<Fn> = OnceLock::new();
+static INNER: OnceLock<Fn> = OnceLock::new();
+static MIDDLE: OnceLock<Fn> = OnceLock::new();
+static OUTER: OnceLock<Fn> = OnceLock::new();
+
+#[test]
+fn test_chained_hook() {
+    unsafe {
+        let add_one_layer = ChainedHook::new(test_fn as Fn, add_one, 0).unwrap();
+        let double_layer = ChainedHook::new(test_fn as Fn, double, 10).unwrap();
+        let add_hundred_layer = ChainedHook::new(test_fn as Fn, add_hundred, 5).unwrap();
+        ADD_ONE.set(add_one_layer.original()).unwrap();
+        DOUBLE.set(double_layer.original()).unwrap();
+        ADD_HUNDRED.set(add_hundred_layer.original()).unwrap();
+
+        // The target has a single hook, which is enabled with the first detour.
+        assert!(MinHook::is_created(test_fn as _));
+        assert_eq!(
+            MinHook::create_hook(test_fn as _, test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+        assert!(!MinHook::is_enabled(test_fn as _));
+        assert_eq!(test_fn(1), 1);
+
+        add_one_layer.enable().unwrap();
+        assert!(MinHook::is_enabled(test_fn as _));
+        assert_eq!(test_fn(1), 2);
+        assert_eq!(
+            add_one_layer.enable().unwrap_err(),
+            MH_STATUS::MH_ERROR_ENABLED
+        );
+
+        // Detours with a higher priority are called first.
+        double_layer.enable().unwrap();
+        add_hundred_layer.enable().unwrap();
+        assert_eq!(test_fn(1), 2 * (100 + (1 + 1)));
+
+        // Each detour can be disabled and removed on its own.
+        add_hundred_layer.disable().unwrap();
+        assert!(!add_hundred_layer.is_enabled());
+        assert_eq!(test_fn(1), 2 * (1 + 1));
+        double_layer.remove().unwrap();
+        assert_eq!(test_fn(1), 1 + 1);
+        add_hundred_layer.enable().unwrap();
+        assert_eq!(test_fn(1), 100 + (1 + 1));
+
+        add_one_layer.disable().unwrap();
+        add_hundred_layer.disable().unwrap();
+        assert!(!MinHook::is_enabled(test_fn as _));
+        assert_eq!(test_fn(1), 1);
+
+        // The hook of the target is removed with the last detour.
+        add_one_layer.remove().unwrap();
+        add_hundred_layer.remove().unwrap();
+        assert!(!MinHook::is_created(test_fn as _));
+    }
+
+    #[inline(never)]
+    extern "C" fn test_fn(x: i32) -> i32 {
+        std::hint::black_box(x)
+    }
+
+    extern "C" fn add_one(x: i32) -> i32 {
+        ADD_ONE.get().unwrap()(x) + 1
+    }
+
+    extern "C" fn add_hundred(x: i32) -> i32 {
+        ADD_HUNDRED.get().unwrap()(x) + 100
+    }
+
+    extern "C" fn double(x: i32) -> i32 {
+        DOUBLE.get().unwrap()(x) * 2
+    }
+}
+
+#[test]
+fn test_chained_hook_drop() {
+    unsafe {
+        let inner_layer = ChainedHook::new(test_fn as Fn, inner, 0).unwrap();
+        let middle_layer = ChainedHook::new(test_fn as Fn, middle, 5).unwrap();
+        let outer_layer = ChainedHook::new(test_fn as Fn, outer, 10).unwrap();
+        INNER.set(inner_layer.original()).unwrap();
+        MIDDLE.set(middle_layer.original()).unwrap();
+        OUTER.set(outer_layer.original()).unwrap();
+
+        inner_layer.enable().unwrap();
+        middle_layer.enable().unwrap();
+        outer_layer.enable().unwrap();
+        assert_eq!(test_fn(1), ((1 + 1) * 10) - 100);
+
+        // Dropping a detour removes it from the chain, and the others keep calling each other.
+        drop(middle_layer);
+        assert_eq!(test_fn(1), (1 + 1) - 100);
+
+        // A leaked detour stays in the chain, so the hook of the target is not removed with the last handle.
+        outer_layer.leak();
+        drop(inner_layer);
+        assert!(MinHook::is_enabled(test_fn as _));
+        assert_eq!(test_fn(1), 1 - 100);
+    }
+
+    #[inline(never)]
+    extern "C" fn test_fn(x: i32) -> i32 {
+        std::hint::black_box(x)
+    }
+
+    extern "C" fn inner(x: i32) -> i32 {
+        INNER.get().unwrap()(x) + 1
+    }
+
+    extern "C" fn middle(x: i32) -> i32 {
+        MIDDLE.get().unwrap()(x) * 10
+    }
+
+    extern "C" fn outer(x: i32) -> i32 {
+        OUTER.get().unwrap()(x) - 100
+    }
+}
+
+#[test]
+fn test_chained_hook_on_hooked_target() {
+    unsafe {
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        assert_eq!(
+            ChainedHook::new(test_fn as fn() -> i32, test_fn_hook, 0).unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+    }
+
+    fn test_fn() -> i32 {
+        0
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
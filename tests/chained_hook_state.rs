@@ -0,0 +1,51 @@
+//! Changes the hooks of chained detours behind their back, which affects every hook in the process, so these run in a
+//! test binary of their own.
+
+use minhook::{ChainedHook, MH_STATUS, MinHook};
+
+#[test]
+fn test_chained_hook_state() {
+    type Fn = fn() -> i32;
+
+    unsafe {
+        let first = ChainedHook::new(test_fn as Fn, test_fn_hook, 0).unwrap();
+        let second = ChainedHook::new(test_fn as Fn, test_fn_hook, 0).unwrap();
+        first.enable().unwrap();
+        assert_eq!(test_fn(), 1);
+
+        // Enabling another detour enables the hook of the target again after all hooks were disabled.
+        MinHook::disable_all_hooks().unwrap();
+        assert_eq!(test_fn(), 0);
+        second.enable().unwrap();
+        assert!(MinHook::is_enabled(test_fn as _));
+        assert_eq!(test_fn(), 1);
+
+        // Shutting down removes the hook of the target, and with it the chain.
+        MinHook::shutdown().unwrap();
+        assert_eq!(test_fn(), 0);
+        assert!(!first.is_enabled());
+        assert_eq!(first.enable().unwrap_err(), MH_STATUS::MH_ERROR_NOT_CREATED);
+        drop(first);
+
+        // The target gets a new chain, which the detours of the old one do not affect.
+        let third = ChainedHook::new(test_fn as Fn, test_fn_hook, 0).unwrap();
+        third.enable().unwrap();
+        assert_eq!(test_fn(), 1);
+        second.remove().unwrap();
+        assert!(third.is_enabled());
+        assert_eq!(test_fn(), 1);
+
+        third.remove().unwrap();
+        assert!(!MinHook::is_created(test_fn as _));
+        assert_eq!(test_fn(), 0);
+    }
+
+    #[inline(never)]
+    fn test_fn() -> i32 {
+        std::hint::black_box(0)
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
@@ -0,0 +1,130 @@
+use minhook::ClosureHook;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+#[test]
+fn test_closure_hook_captured_state() {
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    unsafe {
+        let hook = ClosureHook::<fn(i32) -> i32>::new(test_fn, {
+            let calls = calls.clone();
+            Box::new(move |original, x| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                original(x) + 1
+            })
+        })
+        .unwrap();
+
+        hook.enable().unwrap();
+        assert_eq!(test_fn(1), 2);
+        assert_eq!(test_fn(2), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The original function does not enter the closure.
+        assert_eq!(hook.original()(3), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        hook.disable().unwrap();
+        assert_eq!(test_fn(4), 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    // The closure is released together with the hook.
+    assert_eq!(Arc::strong_count(&calls), 1);
+
+    fn test_fn(x: i32) -> i32 {
+        x
+    }
+}
+
+#[test]
+fn test_closure_hook_arguments() {
+    unsafe {
+        let hook =
+            ClosureHook::<extern "C" fn(u64, f64, u64, u64, f32, u64, u64, u64, u64) -> f64>::new(
+                test_fn,
+                Box::new(|original, a, b, c, d, e, f, g, h, i| {
+                    original(a, b, c, d, e, f, g, h, i) * 2.0
+                }),
+            )
+            .unwrap();
+
+        hook.enable().unwrap();
+        assert_eq!(test_fn(1, 0.5, 2, 3, 0.25, 4, 5, 6, 7), 57.5);
+    }
+
+    extern "C" fn test_fn(
+        a: u64,
+        b: f64,
+        c: u64,
+        d: u64,
+        e: f32,
+        f: u64,
+        g: u64,
+        h: u64,
+        i: u64,
+    ) -> f64 {
+        (a + c + d + f + g + h + i) as f64 + b + e as f64
+    }
+}
+
+#[test]
+fn test_closure_hook_threads() {
+    unsafe {
+        let hook = ClosureHook::<fn(usize) -> usize>::new(
+            test_fn,
+            Box::new(|original, x| original(x) * 2),
+        )
+        .unwrap();
+        hook.enable().unwrap();
+
+        let threads = (0..8)
+            .map(|i| thread::spawn(move || (0..1000).all(|j| test_fn(i + j) == (i + j) * 2)))
+            .collect::<Vec<_>>();
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+    }
+
+    fn test_fn(x: usize) -> usize {
+        x
+    }
+}
+
+#[test]
+fn test_closure_hook_leak() {
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    unsafe {
+        let hook = ClosureHook::<fn(i32) -> i32>::new(test_fn, {
+            let calls = calls.clone();
+            Box::new(move |original, x| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                original(x) + 1
+            })
+        })
+        .unwrap();
+        hook.enable().unwrap();
+
+        // Leaking the hook keeps it installed, together with the closure.
+        let hook = hook.leak();
+        assert_eq!(test_fn(1), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(Arc::strong_count(&calls), 2);
+
+        // Removing the hook does not release the leaked closure.
+        hook.remove().unwrap();
+        assert_eq!(test_fn(1), 1);
+        assert_eq!(Arc::strong_count(&calls), 2);
+    }
+
+    fn test_fn(x: i32) -> i32 {
+        x
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(windows)]
+
 use minhook::MinHook;
 
 #[test]
@@ -0,0 +1,75 @@
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+//! Hooks versioned symbols of glibc, which exports an old and a default version of `realpath`.
+
+use minhook::{MH_STATUS, MinHook};
+use std::{
+    ffi::{c_char, c_void},
+    mem,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+type RealPath = unsafe extern "C" fn(*const c_char, *mut c_char) -> *mut c_char;
+
+static ORIGINAL: AtomicUsize = AtomicUsize::new(0);
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn test_fn_realpath(path: *const c_char, resolved: *mut c_char) -> *mut c_char {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    let original: RealPath = unsafe { mem::transmute(ORIGINAL.load(Ordering::SeqCst)) };
+    unsafe { original(path, resolved) }
+}
+
+#[test]
+fn test_create_hook_api_versioned() {
+    let detour = test_fn_realpath as *const () as *mut c_void;
+
+    unsafe {
+        let (_, old) =
+            MinHook::create_hook_api_ex("libc.so.6", "realpath@GLIBC_2.2.5", detour).unwrap();
+        let (original, target) =
+            MinHook::create_hook_api_ex("libc.so.6", "realpath@@GLIBC_2.3", detour).unwrap();
+        assert_ne!(old, target);
+
+        // The unversioned name resolves to the default version.
+        let error = MinHook::create_hook_api("libc.so.6", "realpath", detour).unwrap_err();
+        assert_eq!(error, MH_STATUS::MH_ERROR_ALREADY_CREATED);
+
+        ORIGINAL.store(original as usize, Ordering::SeqCst);
+        MinHook::enable_hook(target).unwrap();
+        assert_eq!(Path::new("/").canonicalize().unwrap(), Path::new("/"));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        MinHook::remove_hook(target).unwrap();
+        MinHook::remove_hook(old).unwrap();
+    }
+    assert_eq!(Path::new("/").canonicalize().unwrap(), Path::new("/"));
+    assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_create_hook_api_not_found() {
+    let detour = test_fn_realpath as *const () as *mut c_void;
+
+    let error =
+        unsafe { MinHook::create_hook_api("libc.so.6", "realpath@GLIBC_0.1", detour).unwrap_err() };
+    assert_eq!(error, MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+
+    // The old version is hidden, so it is not the default version.
+    let error = unsafe {
+        MinHook::create_hook_api("libc.so.6", "realpath@@GLIBC_2.2.5", detour).unwrap_err()
+    };
+    assert_eq!(error, MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+
+    let error = unsafe {
+        MinHook::create_hook_api("libc.so.6", "minhook_no_such_symbol", detour).unwrap_err()
+    };
+    assert_eq!(error, MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+
+    let error = unsafe {
+        MinHook::create_hook_api("libminhook_missing.so", "realpath@GLIBC_2.2.5", detour)
+            .unwrap_err()
+    };
+    assert_eq!(error, MH_STATUS::MH_ERROR_MODULE_NOT_FOUND);
+}
@@ -0,0 +1,78 @@
+#![cfg(all(target_os = "linux", target_env = "gnu"))]
+
+//! Hooks a function of a glibc module that is loaded and unloaded by the test.
+
+use minhook::{DeferredHook, MinHook};
+use std::ffi::{c_char, c_int, c_void};
+
+const RTLD_NOW: c_int = 2;
+const RTLD_NOLOAD: c_int = 4;
+
+const MODULE: &str = "libBrokenLocale.so.1";
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+type MbCurMax = extern "C" fn() -> usize;
+
+extern "C" fn test_fn_mb_cur_max() -> usize {
+    42
+}
+
+fn open(flags: c_int) -> *mut c_void {
+    unsafe { dlopen(c"libBrokenLocale.so.1".as_ptr(), flags) }
+}
+
+fn symbol(handle: *mut c_void) -> MbCurMax {
+    let address = unsafe { dlsym(handle, c"__ctype_get_mb_cur_max".as_ptr()) };
+    assert!(!address.is_null());
+    unsafe { std::mem::transmute(address) }
+}
+
+#[test]
+fn test_deferred_hook() {
+    assert!(open(RTLD_NOW | RTLD_NOLOAD).is_null());
+
+    let hook = unsafe {
+        DeferredHook::<MbCurMax>::new(MODULE, "__ctype_get_mb_cur_max", test_fn_mb_cur_max).unwrap()
+    };
+    assert_eq!(hook.module_name(), MODULE);
+    assert_eq!(hook.symbol(), "__ctype_get_mb_cur_max");
+    assert!(!hook.is_installed());
+    assert!(hook.target().is_none() && hook.original().is_none());
+    assert!(MinHook::is_created(dlopen as *mut c_void));
+
+    // The hook is installed when the module is loaded.
+    let handle = open(RTLD_NOW);
+    assert!(!handle.is_null());
+    assert!(hook.is_installed());
+    let function = symbol(handle);
+    assert_eq!(hook.target().unwrap() as usize, function as usize);
+    assert_eq!(function(), 42);
+    assert_ne!(hook.original().unwrap()(), 42);
+
+    // It is removed before the module is unloaded.
+    let target = hook.target().unwrap();
+    assert_eq!(unsafe { dlclose(handle) }, 0);
+    assert!(open(RTLD_NOW | RTLD_NOLOAD).is_null());
+    assert!(!hook.is_installed());
+    assert!(!MinHook::is_created(target as *mut c_void));
+
+    // And installed again when it is loaded again.
+    let handle = open(RTLD_NOW);
+    assert!(hook.is_installed());
+    let function = symbol(handle);
+    assert_eq!(function(), 42);
+
+    drop(hook);
+    assert_ne!(function(), 42);
+    assert!(!MinHook::is_created(function as *mut c_void));
+    assert_eq!(unsafe { dlclose(handle) }, 0);
+
+    // The loader is unhooked with the last deferred hook.
+    assert!(!MinHook::is_created(dlopen as *mut c_void));
+    assert!(!MinHook::is_created(dlclose as *mut c_void));
+}
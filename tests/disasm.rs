@@ -0,0 +1,154 @@
+use minhook::disasm::{self, DecodeError, Map, Mode};
+
+#[test]
+fn test_decode_x64() {
+    // push rbp
+    let instruction = disasm::decode(&[0x55], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 1);
+    assert_eq!(instruction.opcode(), 0x55);
+    assert_eq!(instruction.modrm(), None);
+
+    // mov rbp, rsp
+    let instruction = disasm::decode(&[0x48, 0x89, 0xE5], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 3);
+    assert_eq!(instruction.prefixes().rex(), Some(0x48));
+    assert!(instruction.prefixes().w());
+    assert_eq!(instruction.modrm(), Some(0xE5));
+    assert_eq!(instruction.modrm_reg(), Some(4));
+
+    // sub rsp, 0x20
+    let instruction = disasm::decode(&[0x48, 0x83, 0xEC, 0x20], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 4);
+    let immediate = instruction.immediate().unwrap();
+    assert_eq!((immediate.offset(), immediate.size()), (3, 1));
+    assert_eq!(immediate.value(), 0x20);
+
+    // mov eax, [rsp+8]
+    let instruction = disasm::decode(&[0x8B, 0x44, 0x24, 0x08], Mode::X64).unwrap();
+    assert_eq!(instruction.sib(), Some(0x24));
+    let displacement = instruction.displacement().unwrap();
+    assert_eq!((displacement.offset(), displacement.size()), (3, 1));
+    assert_eq!(displacement.value(), 8);
+    assert!(!instruction.is_rip_relative());
+
+    // mov rax, [rip-0x10]
+    let instruction =
+        disasm::decode(&[0x48, 0x8B, 0x05, 0xF0, 0xFF, 0xFF, 0xFF], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 7);
+    assert!(instruction.is_rip_relative());
+    assert_eq!(instruction.displacement().unwrap().value(), -0x10);
+
+    // mov rax, gs:[0x28]
+    let instruction = disasm::decode(
+        &[0x65, 0x48, 0x8B, 0x04, 0x25, 0x28, 0x00, 0x00, 0x00],
+        Mode::X64,
+    )
+    .unwrap();
+    assert_eq!(instruction.len(), 9);
+    assert_eq!(instruction.prefixes().segment(), Some(0x65));
+    assert_eq!(instruction.sib(), Some(0x25));
+    assert_eq!(instruction.displacement().unwrap().value(), 0x28);
+    assert!(!instruction.is_rip_relative());
+
+    // call -0x10
+    let instruction = disasm::decode(&[0xE8, 0xF0, 0xFF, 0xFF, 0xFF], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 5);
+    assert_eq!(instruction.immediate().unwrap().signed_value(), -0x10);
+
+    // mov rax, 0x1122334455667788
+    let instruction = disasm::decode(
+        &[0x48, 0xB8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11],
+        Mode::X64,
+    )
+    .unwrap();
+    assert_eq!(instruction.len(), 10);
+    assert_eq!(instruction.immediate().unwrap().value(), 0x1122334455667788);
+
+    // lock cmpxchg [rdx], ecx
+    let instruction = disasm::decode(&[0xF0, 0x0F, 0xB1, 0x0A], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 4);
+    assert!(instruction.prefixes().lock());
+    assert_eq!(instruction.map(), Map::Secondary);
+    assert_eq!(instruction.opcode(), 0xB1);
+
+    // rep movsq
+    let instruction = disasm::decode(&[0xF3, 0x48, 0xA5], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 3);
+    assert!(instruction.prefixes().rep());
+    assert!(!instruction.prefixes().repne());
+
+    // nop word [rax+rax]
+    let instruction = disasm::decode(&[0x66, 0x0F, 0x1F, 0x44, 0x00, 0x00], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 6);
+    assert!(instruction.is_nop());
+    assert!(instruction.prefixes().operand_size());
+
+    // vzeroupper
+    let instruction = disasm::decode(&[0xC5, 0xF8, 0x77], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 3);
+    assert_eq!(instruction.prefixes().vex(), Some(0xC5));
+    assert_eq!(instruction.modrm(), None);
+
+    // vinsertf128 ymm0, ymm0, xmm1, 1
+    let instruction = disasm::decode(&[0xC4, 0xE3, 0x7D, 0x18, 0xC1, 0x01], Mode::X64).unwrap();
+    assert_eq!(instruction.len(), 6);
+    assert_eq!(instruction.map(), Map::Escape3A);
+    assert_eq!(instruction.immediate().unwrap().value(), 1);
+}
+
+#[test]
+fn test_decode_x86() {
+    // inc eax, which is a REX prefix in 64-bit mode
+    let instruction = disasm::decode(&[0x40], Mode::X86).unwrap();
+    assert_eq!(instruction.len(), 1);
+    assert_eq!(instruction.prefixes().rex(), None);
+
+    // mov ax, 0x1234
+    let instruction = disasm::decode(&[0x66, 0xB8, 0x34, 0x12], Mode::X86).unwrap();
+    assert_eq!(instruction.len(), 4);
+    assert_eq!(instruction.immediate().unwrap().size(), 2);
+
+    // mov eax, [0x12345678] has an absolute address instead of a RIP-relative one.
+    let instruction = disasm::decode(&[0x8B, 0x05, 0x78, 0x56, 0x34, 0x12], Mode::X86).unwrap();
+    assert_eq!(instruction.len(), 6);
+    assert!(!instruction.is_rip_relative());
+    assert_eq!(instruction.displacement().unwrap().value(), 0x12345678);
+
+    // push es only exists in 32-bit mode.
+    assert_eq!(disasm::decode(&[0x06], Mode::X86).unwrap().len(), 1);
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(
+        disasm::decode(&[0x48, 0x8B], Mode::X64),
+        Err(DecodeError::Truncated)
+    );
+    assert_eq!(disasm::decode(&[], Mode::X64), Err(DecodeError::Truncated));
+    assert_eq!(
+        disasm::decode(&[0x06], Mode::X64),
+        Err(DecodeError::InvalidOpcode)
+    );
+
+    let mut code = [0x66; 16];
+    code[15] = 0x90;
+    assert_eq!(disasm::decode(&code, Mode::X64), Err(DecodeError::TooLong));
+}
+
+#[test]
+fn test_instructions() {
+    // push rbp; mov rbp, rsp; sub rsp, 0x20; ret
+    let code = [0x55, 0x48, 0x89, 0xE5, 0x48, 0x83, 0xEC, 0x20, 0xC3];
+    let offsets = disasm::instructions(&code, Mode::X64)
+        .map(|result| result.map(|(offset, _)| offset))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(offsets, [0, 1, 4, 8]);
+
+    // Decoding stops at the first error.
+    let mut instructions = disasm::instructions(&code[..6], Mode::X64);
+    assert!(instructions.next().unwrap().is_ok());
+    assert!(instructions.next().unwrap().is_ok());
+    assert_eq!(instructions.next(), Some(Err(DecodeError::Truncated)));
+    assert_eq!(instructions.next(), None);
+}
@@ -0,0 +1,182 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Calls closures around hand-written functions.
+
+use minhook::{Context, EnterLeaveHook};
+use std::{
+    arch::global_asm,
+    sync::{Arc, Mutex},
+};
+
+global_asm!(
+    // Returns x + y.
+    ".globl minhook_enter_leave_add",
+    ".p2align 4",
+    "minhook_enter_leave_add:",
+    "mov eax, edi",
+    "add eax, esi",
+    "ret",
+    // Returns 0 + 1 + ... + n, calling itself through the hook.
+    ".globl minhook_enter_leave_sum",
+    ".p2align 4",
+    "minhook_enter_leave_sum:",
+    "test edi, edi",
+    "jz 2f",
+    "push rdi",
+    "dec edi",
+    "call minhook_enter_leave_sum",
+    "pop rdi",
+    "add eax, edi",
+    "ret",
+    "2:",
+    "xor eax, eax",
+    "ret",
+    // Returns x.
+    ".globl minhook_enter_leave_identity",
+    ".p2align 4",
+    "minhook_enter_leave_identity:",
+    "mov eax, edi",
+    "ret",
+    // Returns 0 + 1 + ... + n like minhook_enter_leave_sum, for a hook of its own.
+    ".globl minhook_enter_leave_deep",
+    ".p2align 4",
+    "minhook_enter_leave_deep:",
+    "test edi, edi",
+    "jz 2f",
+    "push rdi",
+    "dec edi",
+    "call minhook_enter_leave_deep",
+    "pop rdi",
+    "add eax, edi",
+    "ret",
+    "2:",
+    "xor eax, eax",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_enter_leave_add(x: i32, y: i32) -> i32;
+    fn minhook_enter_leave_sum(n: i32) -> i32;
+    fn minhook_enter_leave_identity(x: i32) -> i32;
+    fn minhook_enter_leave_deep(n: i32) -> i32;
+}
+
+#[test]
+fn test_enter_leave_hook_values() {
+    let hook = unsafe {
+        EnterLeaveHook::new(
+            minhook_enter_leave_add as *mut _,
+            Box::new(|context: &mut Context| context.rsi = 100),
+            Box::new(|context: &mut Context| context.rax *= 2),
+        )
+        .unwrap()
+    };
+    assert_eq!(hook.target(), minhook_enter_leave_add as *mut _);
+    assert_eq!(unsafe { minhook_enter_leave_add(1, 2) }, 3);
+
+    unsafe { hook.enable().unwrap() };
+    assert_eq!(unsafe { minhook_enter_leave_add(1, 2) }, 202);
+
+    // The trampoline bypasses the closures.
+    let original: unsafe extern "C" fn(i32, i32) -> i32 =
+        unsafe { std::mem::transmute(hook.original()) };
+    assert_eq!(unsafe { original(1, 2) }, 3);
+
+    unsafe { hook.disable().unwrap() };
+    assert_eq!(unsafe { minhook_enter_leave_add(1, 2) }, 3);
+
+    unsafe { hook.enable().unwrap() };
+    drop(hook);
+    assert_eq!(unsafe { minhook_enter_leave_add(2, 2) }, 4);
+}
+
+#[test]
+fn test_enter_leave_hook_recursion() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let hook = unsafe {
+        EnterLeaveHook::new(
+            minhook_enter_leave_sum as *mut _,
+            Box::new({
+                let calls = calls.clone();
+                move |context: &mut Context| calls.lock().unwrap().push(("enter", context.rdi))
+            }),
+            Box::new({
+                let calls = calls.clone();
+                move |context: &mut Context| calls.lock().unwrap().push(("leave", context.rax))
+            }),
+        )
+        .unwrap()
+    };
+    unsafe { hook.enable().unwrap() };
+
+    assert_eq!(unsafe { minhook_enter_leave_sum(3) }, 6);
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            ("enter", 3),
+            ("enter", 2),
+            ("enter", 1),
+            ("enter", 0),
+            ("leave", 0),
+            ("leave", 1),
+            ("leave", 3),
+            ("leave", 6),
+        ]
+    );
+
+    // Calls from several threads keep their own return addresses.
+    calls.lock().unwrap().clear();
+    let threads = (0..4)
+        .map(|n| std::thread::spawn(move || unsafe { minhook_enter_leave_sum(n * 10) }))
+        .collect::<Vec<_>>();
+    for (n, thread) in threads.into_iter().enumerate() {
+        let n = n as i32 * 10;
+        assert_eq!(thread.join().unwrap(), n * (n + 1) / 2);
+    }
+    assert_eq!(calls.lock().unwrap().len(), 2 * (1 + 11 + 21 + 31));
+}
+
+#[test]
+fn test_enter_leave_hook_reentrant() {
+    // The enter closure calls the hooked function again, just before its return address is pushed.
+    let hook = unsafe {
+        EnterLeaveHook::new(
+            minhook_enter_leave_identity as *mut _,
+            Box::new(|context: &mut Context| {
+                if context.rdi > 0 {
+                    context.rdi += minhook_enter_leave_identity(context.rdi as i32 - 1) as u64;
+                }
+            }),
+            Box::new(|context: &mut Context| context.rax += 1),
+        )
+        .unwrap()
+    };
+    unsafe { hook.enable().unwrap() };
+
+    // identity(0) = 1, identity(1) = 1 + 1 + 1, identity(2) = 2 + 3 + 1.
+    assert_eq!(unsafe { minhook_enter_leave_identity(0) }, 1);
+    assert_eq!(unsafe { minhook_enter_leave_identity(1) }, 3);
+    assert_eq!(unsafe { minhook_enter_leave_identity(2) }, 6);
+}
+
+#[test]
+fn test_enter_leave_hook_depth() {
+    let leaves = Arc::new(Mutex::new(0));
+    let hook = unsafe {
+        EnterLeaveHook::new(
+            minhook_enter_leave_deep as *mut _,
+            Box::new(|_: &mut Context| {}),
+            Box::new({
+                let leaves = leaves.clone();
+                move |_: &mut Context| *leaves.lock().unwrap() += 1
+            }),
+        )
+        .unwrap()
+    };
+    unsafe { hook.enable().unwrap() };
+
+    // Calls deeper than the stack of return addresses return normally, without the leave closure.
+    assert_eq!(unsafe { minhook_enter_leave_deep(1000) }, 500500);
+    let leaves = *leaves.lock().unwrap();
+    assert!(leaves > 0 && leaves < 1001, "{leaves} leave calls");
+}
@@ -0,0 +1,90 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Enables and disables a hook while other threads keep calling the function. Its prologue spends most of its time
+//! on `pause` instructions within the bytes the jump replaces, so threads are often stopped there, and would resume in
+//! the middle of the jump if their instruction pointers were not moved.
+
+use minhook::MinHook;
+use std::{
+    arch::global_asm,
+    ffi::c_void,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+global_asm!(
+    // Returns 1.
+    ".globl minhook_freeze_target",
+    ".p2align 4",
+    "minhook_freeze_target:",
+    "push rbx",
+    "pause",
+    "pause",
+    "pop rbx",
+    "mov eax, 1",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_freeze_target() -> u32;
+}
+
+extern "C" fn test_fn_freeze_detour() -> u32 {
+    2
+}
+
+#[test]
+fn test_freeze_threads_stress() {
+    const THREADS: usize = 8;
+    const ITERATIONS: usize = 50;
+
+    let target = minhook_freeze_target as *mut c_void;
+    unsafe { MinHook::create_hook(target, test_fn_freeze_detour as *mut c_void).unwrap() };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let calls = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let threads = (0..THREADS)
+        .map(|_| {
+            let (stop, calls) = (stop.clone(), calls.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match unsafe { minhook_freeze_target() } {
+                        result @ (1 | 2) => {
+                            calls[result as usize - 1].fetch_add(1, Ordering::Relaxed)
+                        }
+                        result => panic!("unexpected result {result}"),
+                    };
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The other threads run between the changes, so they are stopped in both versions of the prologue.
+    for _ in 0..ITERATIONS {
+        unsafe {
+            MinHook::enable_hook(target).unwrap();
+            thread::yield_now();
+            MinHook::disable_hook(target).unwrap();
+            thread::yield_now();
+            MinHook::queue_enable_hook(target).unwrap();
+            MinHook::apply_queued().unwrap();
+            thread::yield_now();
+            MinHook::queue_disable_hook(target).unwrap();
+            MinHook::apply_queued().unwrap();
+            thread::yield_now();
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert!(calls[0].load(Ordering::Relaxed) > 0);
+    assert!(calls[1].load(Ordering::Relaxed) > 0);
+    assert_eq!(unsafe { minhook_freeze_target() }, 1);
+    unsafe { MinHook::remove_hook(target).unwrap() };
+}
@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+//! Enables a hook while another thread blocks the signal that suspends threads, which is not sent to it.
+
+use minhook::MinHook;
+use std::{mem, ptr, sync::mpsc, thread};
+
+#[test]
+fn test_freeze_threads_blocked() {
+    let signal = libc::SIGRTMAX() - 1;
+    let (ready_sender, ready) = mpsc::channel();
+    let (done, done_receiver) = mpsc::channel::<()>();
+
+    let thread = thread::spawn(move || unsafe {
+        let mut blocked: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut blocked);
+        libc::sigaddset(&mut blocked, signal);
+        assert_eq!(
+            libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, ptr::null_mut()),
+            0
+        );
+        ready_sender.send(()).unwrap();
+        done_receiver.recv().unwrap();
+
+        let mut pending: libc::sigset_t = mem::zeroed();
+        libc::sigpending(&mut pending);
+        libc::sigismember(&pending, signal) == 1
+    });
+    ready.recv().unwrap();
+
+    unsafe {
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert_eq!(test_fn(), 1);
+        MinHook::remove_hook(test_fn as _).unwrap();
+    }
+
+    done.send(()).unwrap();
+    assert!(
+        !thread.join().unwrap(),
+        "the signal was sent to a thread that blocks it"
+    );
+
+    #[inline(never)]
+    fn test_fn() -> i32 {
+        std::hint::black_box(0)
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
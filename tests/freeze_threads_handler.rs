@@ -0,0 +1,37 @@
+#![cfg(target_os = "linux")]
+
+//! Enables a hook after the application installed a handler for the signal that suspends threads, which is kept.
+
+use minhook::MinHook;
+use std::{ffi::c_int, mem, ptr};
+
+extern "C" fn handler(_signal: c_int) {}
+
+#[test]
+fn test_freeze_threads_handler() {
+    let signal = libc::SIGRTMAX() - 1;
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as *const () as usize;
+        assert_eq!(libc::sigaction(signal, &action, ptr::null_mut()), 0);
+
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert_eq!(test_fn(), 1);
+        MinHook::remove_hook(test_fn as _).unwrap();
+        assert_eq!(test_fn(), 0);
+
+        let mut current: libc::sigaction = mem::zeroed();
+        assert_eq!(libc::sigaction(signal, ptr::null(), &mut current), 0);
+        assert_eq!(current.sa_sigaction, handler as *const () as usize);
+    }
+
+    #[inline(never)]
+    fn test_fn() -> i32 {
+        std::hint::black_box(0)
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "macros")]
+
+use minhook::{MH_STATUS, hook};
+
+fn test_fn(x: i32) -> i32 {
+    x
+}
+
+#[hook(target = test_fn)]
+fn test_fn_hook(x: i32) -> i32 {
+    test_fn_hook::original()(x + 1)
+}
+
+#[test]
+fn test_hook_attribute_target() {
+    unsafe {
+        test_fn_hook::install().unwrap();
+        assert!(test_fn_hook::is_installed());
+        assert_eq!(test_fn(1), 2);
+
+        // Installing twice is reported.
+        assert_eq!(
+            test_fn_hook::install().unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+
+        test_fn_hook::uninstall().unwrap();
+        assert!(!test_fn_hook::is_installed());
+        assert_eq!(test_fn(1), 1);
+
+        // The hook can be installed again.
+        test_fn_hook::install().unwrap();
+        assert_eq!(test_fn(1), 2);
+        test_fn_hook::uninstall().unwrap();
+
+        assert_eq!(
+            test_fn_hook::uninstall().unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+    }
+}
+
+mod reexport {
+    pub use minhook as renamed;
+}
+
+fn test_fn_crate(x: i32) -> i32 {
+    x
+}
+
+// The generated code refers to the crate through the re-export.
+#[hook(crate = reexport::renamed, target = test_fn_crate)]
+fn test_fn_crate_hook(x: i32) -> i32 {
+    test_fn_crate_hook::original()(x * 2)
+}
+
+#[test]
+fn test_hook_attribute_crate() {
+    unsafe {
+        test_fn_crate_hook::install().unwrap();
+        assert_eq!(test_fn_crate(3), 6);
+        test_fn_crate_hook::uninstall().unwrap();
+    }
+    assert_eq!(test_fn_crate(3), 3);
+}
+
+#[cfg(windows)]
+#[hook(module = "kernel32.dll", symbol = "GetCurrentProcessId")]
+extern "system" fn get_current_process_id() -> u32 {
+    42
+}
+
+#[cfg(target_os = "linux")]
+#[hook(module = "libc.so.6", symbol = "getpid")]
+extern "C" fn get_current_process_id() -> i32 {
+    42
+}
+
+#[test]
+fn test_hook_attribute_module() {
+    let original_pid = std::process::id();
+
+    unsafe {
+        get_current_process_id::install().unwrap();
+
+        // The Rust std library calls the hooked function.
+        assert_eq!(std::process::id(), 42);
+        assert_eq!(get_current_process_id::original()() as u32, original_pid);
+
+        get_current_process_id::uninstall().unwrap();
+    }
+
+    assert_eq!(std::process::id(), original_pid);
+}
+
+#[hook(module = "does_not_exist.dll")]
+fn does_not_exist() {}
+
+#[test]
+fn test_hook_attribute_module_not_found() {
+    unsafe {
+        assert_eq!(
+            does_not_exist::install().unwrap_err(),
+            MH_STATUS::MH_ERROR_MODULE_NOT_FOUND
+        );
+    }
+    assert!(!does_not_exist::is_installed());
+}
@@ -0,0 +1,104 @@
+use minhook::{ErrorKind, HookError, HookTarget, MH_STATUS, MinHook, Operation};
+use std::ptr::null_mut;
+
+#[test]
+fn test_error_context() {
+    unsafe {
+        let error = MinHook::enable_hook(test_fn as _).unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::EnableHook));
+        assert_eq!(
+            error.target(),
+            Some(&HookTarget::Address(test_fn as *const () as usize))
+        );
+        assert_eq!(
+            error.kind(),
+            ErrorKind::Status(MH_STATUS::MH_ERROR_NOT_CREATED)
+        );
+        assert_eq!(error.status(), MH_STATUS::MH_ERROR_NOT_CREATED);
+        assert!(
+            error
+                .to_string()
+                .starts_with("Failed to enable hook for 0x")
+        );
+    }
+
+    fn test_fn() -> i32 {
+        0
+    }
+}
+
+#[test]
+fn test_error_symbol() {
+    unsafe {
+        let error =
+            MinHook::create_hook_api("does_not_exist.dll", "does_not_exist", test_fn_hook as _)
+                .unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::CreateHookApi));
+        assert_eq!(
+            error.target(),
+            Some(&HookTarget::Symbol {
+                module: "does_not_exist.dll".to_owned(),
+                symbol: "does_not_exist".to_owned(),
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Failed to create hook for does_not_exist.dll!does_not_exist: The specified module is not loaded."
+        );
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_invalid_input() {
+    unsafe {
+        // Names with interior NUL bytes are rejected instead of panicking.
+        let error =
+            MinHook::create_hook_api("does_not_exist.dll", "does\0not_exist", test_fn_hook as _)
+                .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InteriorNul {
+                argument: "proc_name",
+                position: 4
+            }
+        );
+        assert_eq!(error.status(), MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+
+        let error =
+            MinHook::create_hook_api_ex("does_not\0exist.dll", "f", test_fn_hook as _).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InteriorNul {
+                argument: "module_name",
+                position: 8
+            }
+        );
+
+        let error = MinHook::create_hook(null_mut(), test_fn_hook as _).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NullPointer { argument: "target" });
+        assert_eq!(error.status(), MH_STATUS::MH_ERROR_NOT_EXECUTABLE);
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_status_conversions() {
+    let error = HookError::from(MH_STATUS::MH_ERROR_MEMORY_ALLOC);
+    assert_eq!(error.operation(), None);
+    assert_eq!(error, MH_STATUS::MH_ERROR_MEMORY_ALLOC);
+    assert_eq!(error.to_string(), "Failed to allocate memory.");
+
+    // Errors convert back into status codes, so `?` keeps working in functions returning `MH_STATUS`.
+    fn disable() -> Result<(), MH_STATUS> {
+        unsafe { MinHook::disable_hook(disable as _)? };
+        Ok(())
+    }
+    assert_eq!(disable(), Err(MH_STATUS::MH_ERROR_NOT_CREATED));
+}
@@ -0,0 +1,77 @@
+use minhook::{HookGuard, MH_STATUS, MinHook};
+use std::panic;
+
+#[test]
+fn test_hook_guard_drop() {
+    unsafe {
+        {
+            let guard = MinHook::create_hook_guard(test_fn as _, test_fn_hook as _).unwrap();
+            guard.enable().unwrap();
+            assert_eq!(test_fn(), 1);
+        }
+
+        // The hook is disabled and removed once the guard goes out of scope.
+        assert_eq!(test_fn(), 0);
+        assert_eq!(
+            MinHook::remove_hook(test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+    }
+
+    fn test_fn() -> i32 {
+        0
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_hook_guard_panic() {
+    let result = panic::catch_unwind(|| unsafe {
+        let guard = HookGuard::<fn() -> i32>::new(test_fn, test_fn_hook).unwrap();
+        guard.enable().unwrap();
+        assert_eq!(test_fn(), 1);
+
+        panic!("unwinding through the guard");
+    });
+    assert!(result.is_err());
+
+    // The hook is removed during unwinding.
+    assert_eq!(test_fn(), 0);
+
+    fn test_fn() -> i32 {
+        0
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_hook_guard_leak() {
+    unsafe {
+        let guard = HookGuard::<fn() -> i32>::new(test_fn, test_fn_hook).unwrap();
+        guard.enable().unwrap();
+        guard.disable().unwrap();
+        guard.enable().unwrap();
+
+        // Leaking the guard keeps the hook installed.
+        let hook = guard.leak();
+        assert_eq!(test_fn(), 1);
+        assert_eq!(hook.original()(), 0);
+
+        hook.remove().unwrap();
+        assert_eq!(test_fn(), 0);
+    }
+
+    fn test_fn() -> i32 {
+        0
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
@@ -0,0 +1,56 @@
+#![cfg(any(target_os = "linux", windows))]
+
+//! Hooks an import of the test program, leaving the imported function itself untouched.
+
+use minhook::{MH_STATUS, MinHook, Operation};
+use std::{ffi::c_void, mem, process};
+
+#[cfg(target_os = "linux")]
+const SYMBOL: &str = "getpid";
+#[cfg(windows)]
+const SYMBOL: &str = "GetCurrentProcessId";
+
+extern "system" fn test_fn_id() -> u32 {
+    12345
+}
+
+#[test]
+fn test_import_hook() {
+    let id = process::id();
+
+    let original = unsafe {
+        MinHook::create_import_hook("", SYMBOL, test_fn_id as *const () as *mut c_void).unwrap()
+    };
+    assert_eq!(process::id(), 12345);
+
+    // The returned function is the imported one.
+    let original: extern "system" fn() -> u32 = unsafe { mem::transmute(original) };
+    assert_eq!(original(), id);
+
+    let error = unsafe {
+        MinHook::create_import_hook("", SYMBOL, test_fn_id as *const () as *mut c_void).unwrap_err()
+    };
+    assert_eq!(error.operation(), Some(Operation::CreateImportHook));
+    assert_eq!(error, MH_STATUS::MH_ERROR_ALREADY_CREATED);
+
+    unsafe { MinHook::remove_import_hook("", SYMBOL).unwrap() };
+    assert_eq!(process::id(), id);
+
+    let error = unsafe { MinHook::remove_import_hook("", SYMBOL).unwrap_err() };
+    assert_eq!(error.operation(), Some(Operation::RemoveImportHook));
+    assert_eq!(error, MH_STATUS::MH_ERROR_NOT_CREATED);
+}
+
+#[test]
+fn test_import_hook_not_found() {
+    let detour = test_fn_id as *const () as *mut c_void;
+
+    let error = unsafe {
+        MinHook::create_import_hook("minhook_no_such_module", SYMBOL, detour).unwrap_err()
+    };
+    assert_eq!(error, MH_STATUS::MH_ERROR_MODULE_NOT_FOUND);
+
+    let error =
+        unsafe { MinHook::create_import_hook("", "minhook_no_such_import", detour).unwrap_err() };
+    assert_eq!(error, MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+}
@@ -1,9 +1,67 @@
-use minhook::MinHook;
+use minhook::{Config, MH_STATUS, MinHook, Operation};
 
 #[test]
 fn test_hook() {
-    // Minhook will automatically be initialized, so there is no need to ever call initialize().
-    // However, we can call uninitialize() when we are done with MinHook.
-    // It is not unsafe to call uninitialize(), even multiple times, but it will only uninitialize MinHook once.
-    MinHook::uninitialize();
+    unsafe {
+        // MinHook is initialized on first use, so there is no need to ever call init().
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert!(MinHook::is_initialized());
+        assert_eq!(test_fn(), 1);
+
+        let error = MinHook::init(Config::new()).unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::Initialize));
+        assert_eq!(error, MH_STATUS::MH_ERROR_ALREADY_INITIALIZED);
+
+        // Shutting down removes all hooks.
+        MinHook::shutdown().unwrap();
+        assert!(!MinHook::is_initialized());
+        assert_eq!(test_fn(), 0);
+        assert_eq!(
+            MinHook::shutdown().unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_INITIALIZED
+        );
+
+        // MinHook can be initialized again, and the same target can be hooked again.
+        MinHook::init(Config::new().auto_initialize(false)).unwrap();
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert_eq!(test_fn(), 1);
+        MinHook::shutdown().unwrap();
+        assert_eq!(test_fn(), 0);
+
+        // Without automatic initialization, nothing can be hooked until MinHook is initialized again.
+        let error = MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::CreateHook));
+        assert_eq!(error, MH_STATUS::MH_ERROR_NOT_INITIALIZED);
+        assert!(!MinHook::is_initialized());
+
+        // That is reported even for targets that cannot be hooked.
+        static DATA: u64 = 0;
+        let error = MinHook::create_hook(&DATA as *const u64 as *mut _, test_fn_hook as _);
+        assert_eq!(error.unwrap_err(), MH_STATUS::MH_ERROR_NOT_INITIALIZED);
+
+        MinHook::init(Config::new().freeze_threads(false)).unwrap();
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert_eq!(test_fn(), 1);
+
+        // The deprecated uninitialize() shuts down too. It ignores errors, so calling it again is harmless.
+        #[allow(deprecated)]
+        {
+            MinHook::uninitialize();
+            assert!(!MinHook::is_initialized());
+            assert_eq!(test_fn(), 0);
+            MinHook::uninitialize();
+        }
+    }
+
+    #[inline(never)]
+    fn test_fn() -> i32 {
+        std::hint::black_box(0)
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
 }
@@ -0,0 +1,81 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Inspects the trampolines of hooks on hand-written functions.
+
+use minhook::{Hook, MH_STATUS, MinHook, Operation};
+use std::{arch::global_asm, ffi::c_void, slice};
+
+global_asm!(
+    // A RIP-relative operand that is rewritten in the trampoline.
+    ".globl minhook_inspect_relocated",
+    ".p2align 4",
+    "minhook_inspect_relocated:",
+    "xor eax, eax",
+    "add eax, dword ptr [rip + 2f]",
+    "ret",
+    "2:",
+    ".long 2",
+);
+
+unsafe extern "C" {
+    fn minhook_inspect_relocated() -> i32;
+}
+
+extern "C" fn test_fn_hook() -> i32 {
+    -1
+}
+
+#[test]
+fn test_inspect_trampoline() {
+    let target = minhook_inspect_relocated as *const () as *mut c_void;
+    let detour = test_fn_hook as *const () as *mut c_void;
+
+    let error = MinHook::inspect_trampoline(target).unwrap_err();
+    assert_eq!(error.operation(), Some(Operation::InspectTrampoline));
+    assert_eq!(error, MH_STATUS::MH_ERROR_NOT_CREATED);
+
+    let hook = unsafe {
+        Hook::<unsafe extern "C" fn() -> i32>::new(minhook_inspect_relocated, test_fn_hook).unwrap()
+    };
+    unsafe { hook.enable().unwrap() };
+
+    let trampoline = hook.inspect_trampoline().unwrap();
+    assert_eq!(trampoline.target(), target);
+    assert_eq!(trampoline.address(), hook.original() as *mut c_void);
+    assert_eq!(trampoline.relocated_len(), 8);
+    assert_eq!(trampoline.offsets(), [(0, 0), (2, 2), (8, 8)]);
+
+    let code = unsafe { slice::from_raw_parts(trampoline.address() as *const u8, 22) };
+    assert_eq!(&trampoline.code()[..22], code);
+    assert_eq!(&trampoline.code()[..2], [0x31, 0xC0]);
+
+    // The relay function jumps to the detour.
+    let relay = trampoline.relay().unwrap();
+    assert_eq!(relay, 22);
+    assert_eq!(
+        &trampoline.code()[relay..relay + 6],
+        [0xFF, 0x25, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        &trampoline.code()[relay + 6..],
+        (detour as u64).to_le_bytes()
+    );
+
+    let dump = trampoline.to_string();
+    let lines = dump.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5, "{dump}");
+    assert!(lines[0].contains("8 bytes relocated"));
+    assert!(lines[1].starts_with("  +0x00  31 c0 "));
+    assert!(lines[1].ends_with("target+0x00"));
+    let operand = target as usize + 9;
+    assert!(lines[2].contains(&format!("target+0x02  [rip] {operand:#x}")));
+    assert!(lines[3].contains(&format!("target+0x08  jmp {:#x}", target as usize + 8)));
+    assert!(lines[4].contains(&format!("relay  jmp {detour:p}")));
+
+    // The relocated operand still points at the constant of the target function.
+    assert_eq!(unsafe { minhook_inspect_relocated() }, -1);
+    assert_eq!(unsafe { hook.original()() }, 2);
+
+    unsafe { hook.remove().unwrap() };
+    assert!(MinHook::inspect_trampoline(target).is_err());
+}
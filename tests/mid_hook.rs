@@ -0,0 +1,126 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Hooks instructions in the middle of hand-written functions.
+
+use minhook::{Context, MidHook};
+use std::{
+    arch::global_asm,
+    sync::{Arc, Mutex},
+};
+
+global_asm!(
+    // Computes (x + 10) * 2, with the stack pointer copied into rdx.
+    ".globl minhook_mid_compute",
+    ".p2align 4",
+    "minhook_mid_compute:",
+    "mov eax, edi",
+    "mov rdx, rsp",
+    ".globl minhook_mid_compute_add",
+    "minhook_mid_compute_add:",
+    "add eax, 10",
+    "imul eax, eax, 2",
+    "ret",
+    // Returns the value on top of the stack, with the stack pointer restored from rbx.
+    ".globl minhook_mid_stack",
+    ".p2align 4",
+    "minhook_mid_stack:",
+    "push rbx",
+    "mov rbx, rsp",
+    "push 7",
+    ".globl minhook_mid_stack_load",
+    "minhook_mid_stack_load:",
+    "mov eax, dword ptr [rsp]",
+    "mov rsp, rbx",
+    "pop rbx",
+    "ret",
+    // Returns 1 if the zero flag is set at the hooked instruction.
+    ".globl minhook_mid_flags",
+    ".p2align 4",
+    "minhook_mid_flags:",
+    "xor eax, eax",
+    "test edi, edi",
+    ".globl minhook_mid_flags_set",
+    "minhook_mid_flags_set:",
+    "setz al",
+    "movzx eax, al",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_mid_compute(x: i32) -> i32;
+    fn minhook_mid_compute_add();
+    fn minhook_mid_stack() -> i32;
+    fn minhook_mid_stack_load();
+    fn minhook_mid_flags(x: i32) -> i32;
+    fn minhook_mid_flags_set();
+}
+
+#[test]
+fn test_mid_hook_registers() {
+    let seen = Arc::new(Mutex::new(None));
+    let hook = unsafe {
+        MidHook::new(
+            minhook_mid_compute_add as *mut _,
+            Box::new({
+                let seen = seen.clone();
+                move |context: &mut Context| {
+                    *seen.lock().unwrap() = Some(*context);
+                    context.rax = 100;
+                }
+            }),
+        )
+        .unwrap()
+    };
+    assert_eq!(hook.address(), minhook_mid_compute_add as *mut _);
+    assert_eq!(unsafe { minhook_mid_compute(1) }, 22);
+
+    unsafe { hook.enable().unwrap() };
+    assert_eq!(unsafe { minhook_mid_compute(1) }, 220);
+
+    let context = seen.lock().unwrap().unwrap();
+    assert_eq!(context.rax, 1);
+    assert_eq!(context.rdi, 1);
+    assert_eq!(context.rdx, context.rsp);
+    // The reserved bit of the flags is always set.
+    assert_ne!(context.rflags & 0x2, 0);
+
+    unsafe { hook.disable().unwrap() };
+    assert_eq!(unsafe { minhook_mid_compute(1) }, 22);
+
+    drop(hook);
+    assert_eq!(unsafe { minhook_mid_compute(2) }, 24);
+}
+
+#[test]
+fn test_mid_hook_stack_pointer() {
+    // Pushes 42 below the value the function pushed.
+    let push = |context: &mut Context| {
+        context.rsp -= 16;
+        unsafe { *(context.rsp as *mut u64) = 42 };
+    };
+    let hook = unsafe { MidHook::new(minhook_mid_stack_load as *mut _, Box::new(push)).unwrap() };
+    assert_eq!(unsafe { minhook_mid_stack() }, 7);
+
+    unsafe { hook.enable().unwrap() };
+    for _ in 0..3 {
+        assert_eq!(unsafe { minhook_mid_stack() }, 42);
+    }
+}
+
+#[test]
+fn test_mid_hook_flags() {
+    const ZERO_FLAG: u64 = 1 << 6;
+
+    let hook = unsafe {
+        MidHook::new(
+            minhook_mid_flags_set as *mut _,
+            Box::new(|context: &mut Context| context.rflags ^= ZERO_FLAG),
+        )
+        .unwrap()
+    };
+    assert_eq!(unsafe { minhook_mid_flags(0) }, 1);
+
+    unsafe { hook.enable().unwrap() };
+    assert_eq!(unsafe { minhook_mid_flags(0) }, 0);
+    assert_eq!(unsafe { minhook_mid_flags(5) }, 1);
+}
@@ -0,0 +1,79 @@
+use minhook::{HookTarget, MH_STATUS, MinHook, process};
+use std::{ffi::c_void, ptr::null};
+
+static DATA: [u8; 16] = [0; 16];
+
+#[test]
+fn test_modules() {
+    let modules = process::modules();
+    let exe = std::env::current_exe().unwrap();
+
+    // The main program comes first and contains the test functions.
+    let main = &modules[0];
+    assert_eq!(main.path(), exe);
+    assert!(main.size() > 0);
+    assert!(main.contains(test_fn as *const c_void));
+    assert_eq!(
+        process::module_containing(test_fn as *const c_void).as_ref(),
+        Some(main)
+    );
+
+    #[cfg(target_os = "linux")]
+    let system = "libc.so.6";
+    #[cfg(windows)]
+    let system = "kernel32.dll";
+    assert!(modules.iter().any(|module| {
+        module
+            .path()
+            .file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case(system))
+    }));
+
+    assert!(process::module_containing(null()).is_none());
+}
+
+#[test]
+fn test_regions() {
+    let regions = process::regions();
+    assert!(
+        regions
+            .windows(2)
+            .all(|pair| pair[0].start() < pair[1].start())
+    );
+
+    let code = process::region(test_fn as *const c_void).unwrap();
+    assert!(code.contains(test_fn as *const c_void));
+    assert!(code.is_executable() && code.is_readable() && !code.is_writable());
+    assert!(regions.contains(&code));
+
+    let data = process::region(DATA.as_ptr().cast()).unwrap();
+    assert!(data.is_readable() && !data.is_executable());
+
+    let heap = Box::new([0u8; 64]);
+    let heap = process::region(heap.as_ptr().cast()).unwrap();
+    assert!(heap.is_readable() && heap.is_writable() && !heap.is_executable());
+    assert!(heap.size() >= 64);
+
+    assert!(process::region(null()).is_none());
+}
+
+#[test]
+fn test_is_executable() {
+    assert!(process::is_executable(test_fn as *const c_void));
+    assert!(!process::is_executable(DATA.as_ptr().cast()));
+    assert!(!process::is_executable(null()));
+
+    // Hooks on memory that is not executable fail before the engine is called.
+    let error =
+        unsafe { MinHook::create_hook(DATA.as_ptr() as *mut _, test_fn as *mut _) }.unwrap_err();
+    assert_eq!(error, MH_STATUS::MH_ERROR_NOT_EXECUTABLE);
+    assert_eq!(
+        error.target(),
+        Some(&HookTarget::Address(DATA.as_ptr() as usize))
+    );
+    assert!(!MinHook::is_created(DATA.as_ptr() as *mut _));
+}
+
+fn test_fn() -> i32 {
+    std::hint::black_box(0)
+}
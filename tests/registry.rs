@@ -0,0 +1,64 @@
+use minhook::MinHook;
+
+#[test]
+fn test_registry() {
+    unsafe {
+        assert!(!MinHook::is_created(test_fn as _));
+
+        let original = MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+        MinHook::create_hook(test_fn_2 as _, test_fn_hook as _).unwrap();
+        assert!(MinHook::is_created(test_fn as _));
+        assert!(!MinHook::is_enabled(test_fn as _));
+
+        let hooks = MinHook::hooks();
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks[0].target(), test_fn as _);
+        assert_eq!(hooks[0].detour(), test_fn_hook as _);
+        assert_eq!(hooks[0].trampoline(), original);
+        assert_eq!(hooks[1].target(), test_fn_2 as _);
+
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert!(MinHook::is_enabled(test_fn as _));
+        assert!(!MinHook::is_enabled(test_fn_2 as _));
+
+        // Failed calls leave the state unchanged.
+        MinHook::enable_hook(test_fn as _).unwrap_err();
+        assert!(MinHook::is_enabled(test_fn as _));
+
+        // Queued changes are pending until they are applied.
+        MinHook::queue_disable_hook(test_fn as _).unwrap();
+        MinHook::queue_enable_hook(test_fn_2 as _).unwrap();
+        let hooks = MinHook::hooks();
+        assert_eq!(hooks[0].queued(), Some(false));
+        assert_eq!(hooks[1].queued(), Some(true));
+        assert!(MinHook::is_enabled(test_fn as _));
+
+        MinHook::apply_queued().unwrap();
+        assert!(!MinHook::is_enabled(test_fn as _));
+        assert!(MinHook::is_enabled(test_fn_2 as _));
+        assert!(MinHook::hooks().iter().all(|hook| hook.queued().is_none()));
+        assert_eq!(test_fn(), 0);
+        assert_eq!(test_fn_2(), 2);
+
+        MinHook::enable_all_hooks().unwrap();
+        assert!(MinHook::hooks().iter().all(|hook| hook.is_enabled()));
+        MinHook::disable_all_hooks().unwrap();
+        assert!(MinHook::hooks().iter().all(|hook| !hook.is_enabled()));
+
+        MinHook::remove_hook(test_fn as _).unwrap();
+        assert!(!MinHook::is_created(test_fn as _));
+        assert_eq!(MinHook::hooks().len(), 1);
+    }
+
+    fn test_fn() -> i32 {
+        std::hint::black_box(0)
+    }
+
+    fn test_fn_2() -> i32 {
+        std::hint::black_box(1)
+    }
+
+    fn test_fn_hook() -> i32 {
+        2
+    }
+}
@@ -0,0 +1,98 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Hooks hand-written functions whose prologues have to be relocated into the trampoline.
+
+use minhook::MinHook;
+use std::{arch::global_asm, mem};
+
+global_asm!(
+    // Conditional jump out of the copied prologue.
+    ".globl minhook_test_jcc",
+    ".p2align 4",
+    "minhook_test_jcc:",
+    "xor eax, eax",
+    "jz 2f",
+    "mov eax, 1",
+    "ret",
+    "2:",
+    "mov eax, 2",
+    "ret",
+    // Short jump followed by padding.
+    ".globl minhook_test_jmp",
+    ".p2align 4",
+    "minhook_test_jmp:",
+    "jmp 2f",
+    "int3",
+    "int3",
+    "int3",
+    "2:",
+    "mov eax, 3",
+    "ret",
+    // Relative call.
+    ".globl minhook_test_call",
+    ".p2align 4",
+    "minhook_test_call:",
+    "push rax",
+    "call 2f",
+    "pop rcx",
+    "ret",
+    "2:",
+    "mov eax, 4",
+    "ret",
+    // RIP-relative memory operand.
+    ".globl minhook_test_rip_relative",
+    ".p2align 4",
+    "minhook_test_rip_relative:",
+    "mov eax, dword ptr [rip + 2f]",
+    "ret",
+    "2:",
+    ".long 5",
+);
+
+unsafe extern "C" {
+    fn minhook_test_jcc() -> i32;
+    fn minhook_test_jmp() -> i32;
+    fn minhook_test_call() -> i32;
+    fn minhook_test_rip_relative() -> i32;
+}
+
+extern "C" fn test_fn_hook() -> i32 {
+    -1
+}
+
+unsafe fn assert_relocated(target: unsafe extern "C" fn() -> i32, expected: i32) {
+    unsafe {
+        let original = MinHook::create_hook(target as _, test_fn_hook as _).unwrap();
+        let original: unsafe extern "C" fn() -> i32 = mem::transmute(original);
+
+        MinHook::enable_hook(target as _).unwrap();
+        assert_eq!(target(), -1);
+        assert_eq!(original(), expected);
+
+        MinHook::disable_hook(target as _).unwrap();
+        assert_eq!(target(), expected);
+        assert_eq!(original(), expected);
+
+        MinHook::remove_hook(target as _).unwrap();
+    }
+}
+
+#[test]
+fn test_relocate_jcc() {
+    unsafe { assert_relocated(minhook_test_jcc, 2) };
+}
+
+#[test]
+fn test_relocate_jmp() {
+    unsafe { assert_relocated(minhook_test_jmp, 3) };
+}
+
+#[test]
+fn test_relocate_call() {
+    unsafe { assert_relocated(minhook_test_call, 4) };
+}
+
+#[test]
+fn test_relocate_rip_relative() {
+    unsafe { assert_relocated(minhook_test_rip_relative, 5) };
+}
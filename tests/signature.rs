@@ -0,0 +1,121 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Finds hand-written functions in the code of the test binary.
+
+use minhook::{ErrorKind, MH_STATUS, MinHook, Signature};
+use std::{arch::global_asm, ffi::c_void};
+
+global_asm!(
+    // Returns a constant that only occurs here.
+    ".globl minhook_signature_target",
+    ".p2align 4",
+    "minhook_signature_target:",
+    "mov eax, 0x5A17C0DE",
+    "xor ecx, ecx",
+    "xor edx, edx",
+    "ret",
+    // Calls the function above after loading another unique constant.
+    ".globl minhook_signature_caller",
+    ".p2align 4",
+    "minhook_signature_caller:",
+    "mov edx, 0x6B28D1EF",
+    "call minhook_signature_target",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_signature_target() -> u32;
+    fn minhook_signature_caller() -> u32;
+}
+
+extern "C" fn test_fn_signature_detour() -> u32 {
+    7
+}
+
+#[test]
+fn test_signature_find() {
+    let target = Signature::new("B8 DE C0 17 5A 31 C9")
+        .unwrap()
+        .find("")
+        .unwrap();
+    assert_eq!(target, minhook_signature_target as *mut c_void);
+
+    // Wildcards, an offset and the operand of the call.
+    let called = Signature::new("BA EF ?? 28 6B E8 ? ? ? ?")
+        .unwrap()
+        .offset(5)
+        .rip_relative(1, 5)
+        .find("")
+        .unwrap();
+    assert_eq!(called, minhook_signature_target as *mut c_void);
+
+    let call = Signature::new("BA EF D1 28 6B")
+        .unwrap()
+        .offset(5)
+        .find("")
+        .unwrap();
+    assert_eq!(
+        call as usize,
+        minhook_signature_caller as *const () as usize + 5
+    );
+
+    // The address can be hooked directly.
+    unsafe {
+        let original =
+            MinHook::create_hook(target, test_fn_signature_detour as *mut c_void).unwrap();
+        MinHook::enable_hook(target).unwrap();
+        assert_eq!(minhook_signature_target(), 7);
+        assert_eq!(minhook_signature_caller(), 7);
+
+        let original: unsafe extern "C" fn() -> u32 = std::mem::transmute(original);
+        assert_eq!(original(), 0x5A17C0DE);
+        MinHook::remove_hook(target).unwrap();
+    }
+}
+
+#[test]
+fn test_signature_scan() {
+    let signature = Signature::new("  48 8b ?? C3 ").unwrap();
+    assert_eq!(signature.pattern(), "  48 8b ?? C3 ");
+    assert_eq!(signature.scan(&[0x90, 0x48, 0x8B, 0x05, 0xC3]), Some(1));
+    assert_eq!(signature.scan(&[0x48, 0x8B, 0x05, 0xC2]), None);
+    assert_eq!(signature.scan(&[0x48, 0x8B]), None);
+}
+
+#[test]
+fn test_signature_errors() {
+    for (pattern, position) in [
+        ("", 0),
+        ("   ", 0),
+        ("48 8G", 3),
+        ("48 4 8B", 3),
+        ("48 ???", 3),
+    ] {
+        let error = Signature::new(pattern).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidPattern { position });
+        assert_eq!(error.status(), MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+    }
+
+    let signature = Signature::new("0F 0B 0F 0B 0F 0B 0F 0B 0F 0B 0F 0B").unwrap();
+    assert_eq!(
+        signature.find("").unwrap_err(),
+        MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND
+    );
+    assert_eq!(
+        signature.find("libminhook_missing.so").unwrap_err(),
+        MH_STATUS::MH_ERROR_MODULE_NOT_FOUND
+    );
+
+    // The operand to resolve lies past the end of the code.
+    let error = Signature::new("B8 DE C0 17 5A")
+        .unwrap()
+        .offset(isize::MAX)
+        .rip_relative(1, 5)
+        .find("")
+        .unwrap_err();
+    assert_eq!(error, MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND);
+    assert_eq!(
+        error.to_string(),
+        "Failed to find signature for \"B8 DE C0 17 5A\" in the main program: The specified function is not found."
+    );
+}
@@ -0,0 +1,83 @@
+use minhook::{MH_STATUS, MinHook, static_hook};
+
+static_hook! {
+    static TEST_FN_HOOK: fn(i32) -> i32;
+    static TEST_FN_SYSTEM_HOOK: unsafe extern "system" fn(u32, u32) -> u32;
+    static UNINITIALIZED_HOOK: fn() -> i32;
+}
+
+#[test]
+fn test_static_hook() {
+    unsafe {
+        TEST_FN_HOOK.initialize(test_fn, test_fn_hook).unwrap();
+        assert!(!TEST_FN_HOOK.is_enabled());
+
+        TEST_FN_HOOK.enable().unwrap();
+        assert!(TEST_FN_HOOK.is_enabled());
+        assert_eq!(test_fn(69), 42);
+        assert_eq!(TEST_FN_HOOK.call_original(69), 69);
+
+        TEST_FN_HOOK.disable().unwrap();
+        assert!(!TEST_FN_HOOK.is_enabled());
+        assert_eq!(test_fn(69), 69);
+
+        // Changes made without the handle are reported too.
+        MinHook::queue_enable_hook(test_fn as _).unwrap();
+        MinHook::apply_queued().unwrap();
+        assert!(TEST_FN_HOOK.is_enabled());
+        MinHook::disable_hook(test_fn as _).unwrap();
+        assert!(!TEST_FN_HOOK.is_enabled());
+
+        // A static hook can only be initialized once.
+        assert_eq!(
+            TEST_FN_HOOK.initialize(test_fn, test_fn_hook).unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+    }
+
+    fn test_fn(x: i32) -> i32 {
+        x
+    }
+
+    fn test_fn_hook(_x: i32) -> i32 {
+        // Call the original function with a different value.
+        TEST_FN_HOOK.call_original(42)
+    }
+}
+
+#[test]
+fn test_static_hook_unsafe_system() {
+    unsafe {
+        TEST_FN_SYSTEM_HOOK
+            .initialize(test_fn, test_fn_hook)
+            .unwrap()
+            .enable()
+            .unwrap();
+        assert_eq!(test_fn(6, 7), 13);
+        assert_eq!(TEST_FN_SYSTEM_HOOK.call_original(6, 7), 42);
+    }
+
+    unsafe extern "system" fn test_fn(a: u32, b: u32) -> u32 {
+        a * b
+    }
+
+    unsafe extern "system" fn test_fn_hook(a: u32, b: u32) -> u32 {
+        a + b
+    }
+}
+
+#[test]
+fn test_static_hook_uninitialized() {
+    unsafe {
+        assert_eq!(
+            UNINITIALIZED_HOOK.enable().unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+        assert_eq!(
+            UNINITIALIZED_HOOK.disable().unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+    }
+    assert!(!UNINITIALIZED_HOOK.is_initialized());
+    assert!(UNINITIALIZED_HOOK.original().is_none());
+}
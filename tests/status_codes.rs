@@ -0,0 +1,105 @@
+use minhook::{ErrorKind, HookError, MH_STATUS, MinHook};
+
+#[test]
+fn test_hook_status_codes() {
+    unsafe {
+        MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap();
+
+        // A target can only be hooked once.
+        assert_eq!(
+            MinHook::create_hook(test_fn as _, test_fn_hook as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+
+        // Enabling or disabling twice is reported.
+        MinHook::enable_hook(test_fn as _).unwrap();
+        assert_eq!(
+            MinHook::enable_hook(test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_ENABLED
+        );
+        MinHook::disable_hook(test_fn as _).unwrap();
+        assert_eq!(
+            MinHook::disable_hook(test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_DISABLED
+        );
+
+        // Removed hooks are no longer known.
+        MinHook::remove_hook(test_fn as _).unwrap();
+        assert_eq!(
+            MinHook::remove_hook(test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+        assert_eq!(
+            MinHook::enable_hook(test_fn as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_CREATED
+        );
+    }
+
+    fn test_fn() -> i32 {
+        0
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_not_executable() {
+    static DATA: [u8; 16] = [0; 16];
+
+    unsafe {
+        assert_eq!(
+            MinHook::create_hook(DATA.as_ptr() as _, test_fn_hook as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_EXECUTABLE
+        );
+        assert_eq!(
+            MinHook::create_hook(test_fn_hook as _, DATA.as_ptr() as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_NOT_EXECUTABLE
+        );
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_module_and_function_not_found() {
+    #[cfg(windows)]
+    const MODULE: &str = "kernel32.dll";
+    #[cfg(target_os = "linux")]
+    const MODULE: &str = "libc.so.6";
+
+    unsafe {
+        assert_eq!(
+            MinHook::create_hook_api("does_not_exist.dll", "does_not_exist", test_fn_hook as _)
+                .unwrap_err(),
+            MH_STATUS::MH_ERROR_MODULE_NOT_FOUND
+        );
+        assert_eq!(
+            MinHook::create_hook_api(MODULE, "does_not_exist", test_fn_hook as _).unwrap_err(),
+            MH_STATUS::MH_ERROR_FUNCTION_NOT_FOUND
+        );
+    }
+
+    fn test_fn_hook() -> i32 {
+        1
+    }
+}
+
+#[test]
+fn test_status_from_raw() {
+    for code in -1..=12 {
+        let status = MH_STATUS::try_from(code).unwrap();
+        assert_eq!(i32::from(status), code);
+    }
+
+    // Codes added by newer versions of MinHook are kept instead of being misread.
+    assert_eq!(MH_STATUS::try_from(13), Err(13));
+    assert_eq!(MH_STATUS::try_from(-2), Err(-2));
+
+    let error = HookError::from(ErrorKind::Unrecognized(13));
+    assert_eq!(error.status(), MH_STATUS::MH_UNKNOWN);
+    assert_eq!(error.to_string(), "Unrecognized status code 13.");
+}
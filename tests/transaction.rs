@@ -0,0 +1,100 @@
+use minhook::{HookTransaction, MH_STATUS, MinHook, Operation};
+
+#[test]
+fn test_transaction() {
+    unsafe {
+        // Hooks queued outside of the transaction are left alone.
+        MinHook::create_hook(test_fn_3 as _, test_fn_hook as _).unwrap();
+        MinHook::queue_enable_hook(test_fn_3 as _).unwrap();
+
+        let created = HookTransaction::new()
+            .create(test_fn_1 as _, test_fn_hook as _)
+            .enable(test_fn_1 as _)
+            .create(test_fn_2 as _, test_fn_hook as _)
+            .commit()
+            .unwrap();
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0].target(), test_fn_1 as _);
+        assert!(created[0].is_enabled());
+        assert!(!created[1].is_enabled());
+        assert_eq!(test_fn_1(), 9);
+        assert_eq!(test_fn_2(), 2);
+        assert!(!MinHook::is_enabled(test_fn_3 as _));
+
+        // Invalid steps are rejected before anything is changed.
+        let error = HookTransaction::new()
+            .disable(test_fn_1 as _)
+            .enable(test_fn_4 as _)
+            .commit()
+            .unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::EnableHook));
+        assert_eq!(error, MH_STATUS::MH_ERROR_NOT_CREATED);
+        assert!(MinHook::is_enabled(test_fn_1 as _));
+
+        let transaction = HookTransaction::new()
+            .enable(test_fn_2 as _)
+            .enable(test_fn_2 as _);
+        assert_eq!(
+            transaction.validate().unwrap_err(),
+            MH_STATUS::MH_ERROR_ENABLED
+        );
+
+        // A step that fails while applying rolls back the steps before it.
+        static DATA: [u8; 16] = [0; 16];
+        let error = HookTransaction::new()
+            .disable(test_fn_1 as _)
+            .create(test_fn_4 as _, test_fn_hook as _)
+            .enable(test_fn_4 as _)
+            .create(DATA.as_ptr() as _, test_fn_hook as _)
+            .commit()
+            .unwrap_err();
+        assert_eq!(error.operation(), Some(Operation::CreateHook));
+        assert_eq!(error, MH_STATUS::MH_ERROR_NOT_EXECUTABLE);
+        assert!(MinHook::is_enabled(test_fn_1 as _));
+        assert!(!MinHook::is_created(test_fn_4 as _));
+        assert_eq!(test_fn_1(), 9);
+        assert_eq!(test_fn_4(), 4);
+
+        // Hooks are disabled and removed together.
+        HookTransaction::new()
+            .remove(test_fn_1 as _)
+            .enable(test_fn_2 as _)
+            .commit()
+            .unwrap();
+        assert!(!MinHook::is_created(test_fn_1 as _));
+        assert_eq!(test_fn_1(), 1);
+        assert_eq!(test_fn_2(), 9);
+
+        // The change queued before is still pending.
+        assert_eq!(
+            MinHook::hooks()
+                .iter()
+                .find(|hook| hook.target() == test_fn_3 as _)
+                .unwrap()
+                .queued(),
+            Some(true)
+        );
+        MinHook::apply_queued().unwrap();
+        assert_eq!(test_fn_3(), 9);
+    }
+
+    fn test_fn_1() -> i32 {
+        std::hint::black_box(1)
+    }
+
+    fn test_fn_2() -> i32 {
+        std::hint::black_box(2)
+    }
+
+    fn test_fn_3() -> i32 {
+        std::hint::black_box(3)
+    }
+
+    fn test_fn_4() -> i32 {
+        std::hint::black_box(4)
+    }
+
+    fn test_fn_hook() -> i32 {
+        9
+    }
+}
@@ -0,0 +1,56 @@
+use minhook::{Hook, MH_STATUS};
+
+#[test]
+fn test_typed_hook() {
+    unsafe {
+        let hook = Hook::<fn(i32) -> i32>::new(test_fn, test_fn_hook).unwrap();
+
+        // The original function is returned with the type of the target.
+        let original: fn(i32) -> i32 = hook.original();
+
+        hook.enable().unwrap();
+        assert_eq!(test_fn(69), 42);
+        assert_eq!(original(69), 69);
+
+        hook.disable().unwrap();
+        assert_eq!(test_fn(69), 69);
+
+        hook.remove().unwrap();
+    }
+
+    fn test_fn(x: i32) -> i32 {
+        x
+    }
+
+    fn test_fn_hook(_x: i32) -> i32 {
+        42
+    }
+}
+
+#[test]
+fn test_typed_hook_extern_c() {
+    unsafe {
+        let hook = Hook::<extern "C" fn(u64, u64) -> u64>::new(test_fn, test_fn_hook).unwrap();
+
+        hook.enable().unwrap();
+        assert_eq!(test_fn(2, 3), 6);
+        assert_eq!(hook.original()(2, 3), 5);
+
+        // The same target cannot be hooked twice.
+        assert_eq!(
+            Hook::<extern "C" fn(u64, u64) -> u64>::new(test_fn, test_fn_hook).unwrap_err(),
+            MH_STATUS::MH_ERROR_ALREADY_CREATED
+        );
+
+        hook.disable().unwrap();
+        hook.remove().unwrap();
+    }
+
+    extern "C" fn test_fn(a: u64, b: u64) -> u64 {
+        a + b
+    }
+
+    extern "C" fn test_fn_hook(a: u64, b: u64) -> u64 {
+        a * b
+    }
+}
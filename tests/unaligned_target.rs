@@ -0,0 +1,98 @@
+#![cfg(target_arch = "x86_64")]
+
+//! Hooks functions whose first byte is the last byte of an aligned quadword, so the jump to the detour cannot be
+//! written with a single 8-byte store.
+
+use minhook::{Config, MH_STATUS, MinHook};
+use std::{
+    arch::global_asm,
+    ffi::c_void,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+global_asm!(
+    // Returns 1, at offset 7 of an aligned 16-byte block.
+    ".globl minhook_unaligned_target",
+    ".p2align 4",
+    ".skip 7, 0xCC",
+    "minhook_unaligned_target:",
+    "mov eax, 1",
+    "ret",
+    // Returns 1, at offset 15 of an aligned 16-byte block, so the jump crosses into the next block.
+    ".globl minhook_straddling_target",
+    ".p2align 4",
+    ".skip 15, 0xCC",
+    "minhook_straddling_target:",
+    "mov eax, 1",
+    "ret",
+);
+
+unsafe extern "C" {
+    fn minhook_unaligned_target() -> u32;
+    fn minhook_straddling_target() -> u32;
+}
+
+extern "C" fn test_fn_unaligned_detour() -> u32 {
+    2
+}
+
+#[test]
+fn test_unaligned_target() {
+    // Without frozen threads, the other threads only ever see the whole jump or the whole original instruction.
+    MinHook::init(Config::new().freeze_threads(false)).unwrap();
+
+    let target = minhook_unaligned_target as *mut c_void;
+    assert_eq!(target as usize % 8, 7);
+    unsafe { MinHook::create_hook(target, test_fn_unaligned_detour as *mut c_void).unwrap() };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let threads = (0..4)
+        .map(|_| {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let result = unsafe { minhook_unaligned_target() };
+                    assert!(result == 1 || result == 2, "unexpected result {result}");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..1000 {
+        unsafe {
+            MinHook::enable_hook(target).unwrap();
+            MinHook::disable_hook(target).unwrap();
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    unsafe {
+        MinHook::enable_hook(target).unwrap();
+        assert_eq!(minhook_unaligned_target(), 2);
+        MinHook::remove_hook(target).unwrap();
+    }
+    assert_eq!(unsafe { minhook_unaligned_target() }, 1);
+
+    // The jump over the end of a 16-byte block cannot be written atomically.
+    let target = minhook_straddling_target as *mut c_void;
+    assert_eq!(target as usize % 16, 15);
+    let result = unsafe { MinHook::create_hook(target, test_fn_unaligned_detour as *mut c_void) };
+    if cfg!(rust_engine) {
+        assert_eq!(
+            result.unwrap_err(),
+            MH_STATUS::MH_ERROR_UNSUPPORTED_FUNCTION
+        );
+        assert!(!MinHook::is_created(target));
+    } else {
+        unsafe { MinHook::remove_hook(target).unwrap() };
+    }
+    assert_eq!(unsafe { minhook_straddling_target() }, 1);
+}
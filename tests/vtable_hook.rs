@@ -0,0 +1,119 @@
+//! Hooks methods of objects laid out like C++ objects with virtual tables.
+
+use minhook::{MH_STATUS, Operation, VTableHook};
+use std::{ffi::c_void, ptr::null_mut};
+
+type Get = extern "C" fn(*const Object) -> i32;
+
+#[repr(C)]
+struct Object {
+    vtable: *const [Get; 2],
+    value: i32,
+}
+
+impl Object {
+    fn new(vtable: *mut [usize; 4], value: i32) -> Self {
+        Self {
+            vtable: unsafe { vtable.cast::<usize>().add(2).cast() },
+            value,
+        }
+    }
+
+    fn get(&self) -> i32 {
+        unsafe { (*self.vtable)[0](self) }
+    }
+
+    fn get_squared(&self) -> i32 {
+        unsafe { (*self.vtable)[1](self) }
+    }
+}
+
+extern "C" fn test_fn_get(object: *const Object) -> i32 {
+    let object = unsafe { &*object };
+    object.value
+}
+
+extern "C" fn test_fn_get_squared(object: *const Object) -> i32 {
+    let object = unsafe { &*object };
+    object.value * object.value
+}
+
+extern "C" fn test_fn_hook(object: *const Object) -> i32 {
+    let object = unsafe { &*object };
+    -object.value
+}
+
+/// A virtual table with an offset to the top and a type info before its address point.
+fn vtable() -> *mut [usize; 4] {
+    Box::leak(Box::new([
+        0,
+        0x1234,
+        test_fn_get as *const () as usize,
+        test_fn_get_squared as *const () as usize,
+    ]))
+}
+
+#[test]
+fn test_vtable_hook_shared() {
+    let vtable = vtable();
+    let (a, b) = (Object::new(vtable, 2), Object::new(vtable, 3));
+
+    let hook = unsafe {
+        VTableHook::<Get>::with_object(&a as *const _ as *mut _, 1, test_fn_hook).unwrap()
+    };
+    assert!(!hook.is_shadow());
+    assert_eq!(hook.slot(), unsafe { (&raw mut (*vtable)[3]).cast() });
+    assert_eq!(
+        hook.original() as usize,
+        test_fn_get_squared as *const () as usize
+    );
+
+    // Every object using the virtual table is affected.
+    assert_eq!((a.get(), a.get_squared()), (2, -2));
+    assert_eq!((b.get(), b.get_squared()), (3, -3));
+    assert_eq!(hook.original()(&b), 9);
+
+    unsafe { hook.remove().unwrap() };
+    assert_eq!((a.get_squared(), b.get_squared()), (4, 9));
+}
+
+#[test]
+fn test_vtable_hook_shadow() {
+    let vtable = vtable();
+    let mut a = Object::new(vtable, 2);
+    let b = Object::new(vtable, 3);
+    let address_point = a.vtable;
+
+    {
+        let hook = unsafe {
+            VTableHook::<Get>::with_shadow(&raw mut a as *mut c_void, 2, 0, test_fn_hook).unwrap()
+        };
+        assert!(hook.is_shadow());
+        assert_ne!(a.vtable, address_point);
+        assert_eq!(hook.slot() as usize, a.vtable as usize);
+
+        // Only the object is affected, and the entries before the address point are copied.
+        assert_eq!((a.get(), a.get_squared()), (-2, 4));
+        assert_eq!((b.get(), b.get_squared()), (3, 9));
+        assert_eq!(unsafe { *(a.vtable as *const usize).sub(1) }, 0x1234);
+        assert_eq!(unsafe { (*vtable)[2] }, test_fn_get as *const () as usize);
+    }
+
+    // Dropping the hook points the object back to its virtual table.
+    assert_eq!(a.vtable, address_point);
+    assert_eq!(a.get(), 2);
+}
+
+#[test]
+fn test_vtable_hook_errors() {
+    let error = unsafe { VTableHook::<Get>::new(null_mut(), 0, test_fn_hook).unwrap_err() };
+    assert_eq!(error.operation(), Some(Operation::CreateVTableHook));
+    assert!(error.target().is_none());
+
+    // The entry before the address point is not a method.
+    let vtable = vtable();
+    let address_point = unsafe { (&raw mut (*vtable)[1]).cast() };
+    let error = unsafe { VTableHook::<Get>::new(address_point, 0, test_fn_hook).unwrap_err() };
+    assert_eq!(error, MH_STATUS::MH_ERROR_NOT_EXECUTABLE);
+    assert_eq!(unsafe { (*vtable)[1] }, 0x1234);
+}